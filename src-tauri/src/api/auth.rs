@@ -34,8 +34,18 @@ pub fn clear_all_tokens() -> Result<(), String> {
     }
 }
 
-// 设置Token事件通道
+// 订阅Token事件，返回订阅者ID；可只接收指定系统的事件
 #[tauri::command]
-pub fn set_token_event_channel(channel: Channel<auth::TokenEvent>) -> Result<(), String> {
-    auth::set_token_event_channel_sync(channel).map_err(|e| e.to_string())
+pub fn subscribe_token_events(
+    webview: tauri::Webview,
+    channel: Channel<auth::TokenEvent>,
+    filter: Option<auth::TokenEventFilter>,
+) -> Result<u64, String> {
+    auth::subscribe_token_events(channel, filter.unwrap_or_default(), webview.label()).map_err(|e| e.to_string())
+}
+
+// 取消Token事件订阅
+#[tauri::command]
+pub fn unsubscribe_token_events(subscriber_id: u64) -> bool {
+    auth::unsubscribe_token_events(subscriber_id)
 }
\ No newline at end of file
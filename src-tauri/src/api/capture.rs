@@ -1,35 +1,183 @@
 use crate::service::capture;
+use crate::service::redact;
 use tauri::ipc::Channel;
 
-// 获取捕获状态
+// 获取所有捕获会话的状态
 #[tauri::command]
-pub fn get_capture_status() -> capture::CaptureStatus {
+pub fn get_capture_status() -> Vec<capture::CaptureStatus> {
     // 打印日志显示当前捕获状态
     capture::get_capture_status()
 }
 
-// 设置状态更新通道
+// 获取捕获统计（pcap 内核计数、帧数、解析错误、HTTP 消息数、速率）
 #[tauri::command]
-pub fn set_status_channel(channel: Channel<capture::CaptureStatus>) -> Result<(), String> {
-    capture::set_status_channel(channel).map_err(|e| e.to_string())
+pub fn get_capture_stats(session_id: Option<String>) -> Result<Vec<capture::stats::CaptureStats>, String> {
+    capture::get_capture_stats(session_id.as_deref()).map_err(|e| e.to_string())
 }
 
-// 设置 HTTP 数据包通道
+// 订阅状态更新，返回订阅者ID（每个窗口可以各自订阅）
 #[tauri::command]
-pub fn set_http_channel(channel: Channel<capture::HttpPacket>) -> Result<(), String> {
-    capture::set_http_channel(channel).map_err(|e| e.to_string())
+pub fn subscribe_capture_status(
+    webview: tauri::Webview,
+    channel: Channel<capture::CaptureStatus>,
+    filter: Option<capture::StatusFilter>,
+) -> Result<u64, String> {
+    capture::subscribe_status(channel, filter.unwrap_or_default(), webview.label()).map_err(|e| e.to_string())
 }
 
-// 启动数据包捕获
+// 取消状态更新订阅
 #[tauri::command]
-pub fn init_capture(device_name: String) -> Result<(), String> {
-    capture::start_capture_with_device(device_name.clone()).map_err(|e| e.to_string())
+pub fn unsubscribe_capture_status(subscriber_id: u64) -> bool {
+    capture::unsubscribe_status(subscriber_id)
 }
 
-// 停止数据包捕获
+// 订阅 HTTP 数据包，返回订阅者ID；可按会话、主机和类型过滤
 #[tauri::command]
-pub fn stop_capture() -> Result<(), String> {
-    capture::stop_capture().map_err(|e| e.to_string())
+pub fn subscribe_http_packets(
+    webview: tauri::Webview,
+    channel: Channel<capture::HttpPacket>,
+    filter: Option<capture::HttpPacketFilter>,
+) -> Result<u64, String> {
+    capture::subscribe_http(channel, filter.unwrap_or_default(), webview.label()).map_err(|e| e.to_string())
+}
+
+// 取消 HTTP 数据包订阅
+#[tauri::command]
+pub fn unsubscribe_http_packets(subscriber_id: u64) -> bool {
+    capture::unsubscribe_http(subscriber_id)
+}
+
+// 订阅 HTTP 事务（请求/响应配对结果），返回订阅者ID；可按会话过滤
+#[tauri::command]
+pub fn subscribe_http_transactions(
+    webview: tauri::Webview,
+    channel: Channel<capture::transaction::HttpTransaction>,
+    filter: Option<capture::SessionFilter>,
+) -> Result<u64, String> {
+    capture::subscribe_transactions(channel, filter.unwrap_or_default(), webview.label()).map_err(|e| e.to_string())
+}
+
+// 取消 HTTP 事务订阅
+#[tauri::command]
+pub fn unsubscribe_http_transactions(subscriber_id: u64) -> bool {
+    capture::unsubscribe_transactions(subscriber_id)
+}
+
+// 订阅 TLS 连接事件，返回订阅者ID
+#[tauri::command]
+pub fn subscribe_tls_events(
+    webview: tauri::Webview,
+    channel: Channel<capture::tls::TlsConnectionEvent>,
+) -> Result<u64, String> {
+    capture::subscribe_tls_events(channel, webview.label()).map_err(|e| e.to_string())
+}
+
+// 取消 TLS 连接事件订阅
+#[tauri::command]
+pub fn unsubscribe_tls_events(subscriber_id: u64) -> bool {
+    capture::unsubscribe_tls_events(subscriber_id)
+}
+
+// 订阅 DNS 查询日志，返回订阅者ID；可按会话过滤
+#[tauri::command]
+pub fn subscribe_dns_events(
+    webview: tauri::Webview,
+    channel: Channel<capture::dns::DnsEvent>,
+    filter: Option<capture::SessionFilter>,
+) -> Result<u64, String> {
+    capture::subscribe_dns_events(channel, filter.unwrap_or_default(), webview.label()).map_err(|e| e.to_string())
+}
+
+// 取消 DNS 查询日志订阅
+#[tauri::command]
+pub fn unsubscribe_dns_events(subscriber_id: u64) -> bool {
+    capture::unsubscribe_dns_events(subscriber_id)
+}
+
+// 订阅 TCP 连接更新（按推送间隔批量推送有变化的连接），返回订阅者ID；可按会话过滤
+#[tauri::command]
+pub fn subscribe_flow_updates(
+    webview: tauri::Webview,
+    channel: Channel<capture::flows::FlowUpdateEvent>,
+    filter: Option<capture::SessionFilter>,
+) -> Result<u64, String> {
+    capture::subscribe_flow_updates(channel, filter.unwrap_or_default(), webview.label()).map_err(|e| e.to_string())
+}
+
+// 取消 TCP 连接更新订阅
+#[tauri::command]
+pub fn unsubscribe_flow_updates(subscriber_id: u64) -> bool {
+    capture::unsubscribe_flow_updates(subscriber_id)
+}
+
+// 订阅网络设备变化（设备出现、消失或地址变化时推送），返回订阅者ID
+#[tauri::command]
+pub fn subscribe_device_changes(
+    webview: tauri::Webview,
+    channel: Channel<capture::devices::DeviceChangeEvent>,
+) -> Result<u64, String> {
+    capture::subscribe_device_changes(channel, webview.label()).map_err(|e| e.to_string())
+}
+
+// 取消网络设备变化订阅
+#[tauri::command]
+pub fn unsubscribe_device_changes(subscriber_id: u64) -> bool {
+    capture::unsubscribe_device_changes(subscriber_id)
+}
+
+// 订阅捕获会话结束摘要，返回订阅者ID；可按会话过滤
+#[tauri::command]
+pub fn subscribe_capture_summaries(
+    webview: tauri::Webview,
+    channel: Channel<capture::stop::CaptureSummary>,
+    filter: Option<capture::SessionFilter>,
+) -> Result<u64, String> {
+    capture::subscribe_summaries(channel, filter.unwrap_or_default(), webview.label()).map_err(|e| e.to_string())
+}
+
+// 取消捕获摘要订阅
+#[tauri::command]
+pub fn unsubscribe_capture_summaries(subscriber_id: u64) -> bool {
+    capture::unsubscribe_summaries(subscriber_id)
+}
+
+// 启动数据包捕获，返回会话ID
+#[tauri::command]
+pub fn init_capture(
+    device_name: String,
+    options: Option<capture::CaptureOptions>,
+    session_id: Option<String>,
+) -> Result<String, String> {
+    capture::start_capture_with_device(device_name, options.unwrap_or_default(), session_id).map_err(|e| e.to_string())
+}
+
+// 启动代理捕获（无需 pcap 权限，可解密 HTTPS），用 stop_capture 停止
+#[tauri::command]
+pub async fn start_proxy_capture(
+    options: Option<capture::proxy::ProxyOptions>,
+    session_id: Option<String>,
+) -> Result<capture::proxy::ProxyInfo, String> {
+    capture::proxy::start_proxy(options.unwrap_or_default(), session_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 获取代理根证书（路径和 PEM），用于导入受信任根证书
+#[tauri::command]
+pub fn get_proxy_ca() -> Result<capture::proxy::ProxyCaInfo, String> {
+    capture::proxy::ca_info().map_err(|e| e.to_string())
+}
+
+// 校验 BPF 过滤表达式
+#[tauri::command]
+pub fn validate_capture_filter(filter: String) -> Result<(), String> {
+    capture::validate_bpf_filter(&filter).map_err(|e| e.to_string())
+}
+
+// 停止数据包捕获，未指定会话ID时停止所有会话
+#[tauri::command]
+pub fn stop_capture(session_id: Option<String>) -> Result<(), String> {
+    capture::stop_capture(session_id.as_deref()).map_err(|e| e.to_string())
 }
 
 // 检查是否安装了ChmodBPF
@@ -38,8 +186,116 @@ pub fn has_pcap() -> bool {
     capture::has_capture_prerequisites()
 }
 
+// 获取认证队列统计
+#[tauri::command]
+pub fn get_auth_queue_stats() -> capture::auth_queue::AuthQueueStats {
+    capture::auth_queue::auth_queue_stats()
+}
+
+// 设置认证队列容量和溢出策略
+#[tauri::command]
+pub fn set_auth_queue_config(config: capture::auth_queue::AuthQueueConfig) -> Result<(), String> {
+    capture::auth_queue::set_auth_queue_config(config).map_err(|e| e.to_string())
+}
+
+// 获取捕获前置条件诊断报告（权限、驱动、各设备能否打开）
+#[tauri::command]
+pub async fn get_capture_prerequisites() -> Result<capture::prerequisites::CapturePrerequisiteReport, String> {
+    tauri::async_runtime::spawn_blocking(capture::prerequisites::check_capture_prerequisites)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 // 获取网络设备列表
 #[tauri::command]
 pub fn get_network_devices() -> Result<Vec<capture::NetworkDevice>, String> {
     capture::get_network_devices().map_err(|e| e.to_string())
-}
\ No newline at end of file
+}
+
+// 离线分析 pcap/pcapng 文件
+#[tauri::command]
+pub async fn analyze_capture_file(
+    file_path: String,
+    feed_auth: Option<bool>,
+    keylog_file: Option<String>,
+) -> Result<capture::offline::OfflineAnalysisSummary, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        capture::offline::analyze_capture_file(&file_path, feed_auth.unwrap_or(false), keylog_file.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+// 导出 HAR 文件，返回写入的条目数
+#[tauri::command]
+pub fn export_har(packets: Vec<capture::HttpPacket>, file_path: String) -> Result<usize, String> {
+    capture::har::export_har(&packets, &file_path).map_err(|e| e.to_string())
+}
+
+// 从 HAR 文件导入数据包
+#[tauri::command]
+pub fn import_har(file_path: String) -> Result<Vec<capture::HttpPacket>, String> {
+    capture::har::import_har(&file_path).map_err(|e| e.to_string())
+}
+
+// 按条件分页查询数据包历史
+#[tauri::command]
+pub fn query_packets(query: Option<capture::store::PacketQuery>) -> Result<capture::store::PacketPage, String> {
+    capture::store::query_packets(&query.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+// 按ID获取数据包详情（详情窗口使用），附带结构化消息体视图
+#[tauri::command]
+pub fn get_packet_by_id(id: u64) -> Result<capture::store::PacketDetail, String> {
+    capture::store::get_packet_detail(id).ok_or_else(|| format!("未找到数据包: {}", id))
+}
+
+// 清空数据包历史
+#[tauri::command]
+pub fn clear_packet_store() {
+    capture::store::clear_packets();
+}
+
+// 设置数据包历史的数量和字节上限
+#[tauri::command]
+pub fn set_packet_store_limits(max_packets: usize, max_bytes: usize) -> Result<capture::store::PacketStoreStats, String> {
+    capture::store::set_store_limits(max_packets, max_bytes).map_err(|e| e.to_string())?;
+    capture::store::store_stats().map_err(|e| e.to_string())
+}
+
+// 获取数据包历史的占用情况
+#[tauri::command]
+pub fn get_packet_store_stats() -> Result<capture::store::PacketStoreStats, String> {
+    capture::store::store_stats().map_err(|e| e.to_string())
+}
+
+// 按条件分页查询 TCP 连接
+#[tauri::command]
+pub fn list_flows(query: Option<capture::flows::FlowQuery>) -> Result<capture::flows::FlowPage, String> {
+    capture::flows::list_flows(&query.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+// 按ID获取 TCP 连接详情
+#[tauri::command]
+pub fn get_flow(id: u64) -> Result<capture::flows::FlowInfo, String> {
+    capture::flows::get_flow(id).ok_or_else(|| format!("未找到连接: {}", id))
+}
+
+// 获取敏感信息脱敏策略
+#[tauri::command]
+pub fn get_redaction_policy() -> redact::RedactionPolicy {
+    redact::get_policy()
+}
+
+// 设置敏感信息脱敏策略
+#[tauri::command]
+pub fn set_redaction_policy(policy: redact::RedactionPolicy) -> Result<(), String> {
+    redact::set_policy(policy).map_err(|e| e.to_string())
+}
+
+// 为某个捕获会话显示或隐藏敏感信息
+#[tauri::command]
+pub fn set_show_secrets(session_id: String, show: bool) {
+    redact::set_show_secrets(&session_id, show);
+}
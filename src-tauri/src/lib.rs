@@ -15,19 +15,54 @@ pub fn run() {
         .plugin(tauri_plugin_prevent_default::init())
         .invoke_handler(tauri::generate_handler![
             api::get_capture_status,
-            api::set_status_channel,
-            api::set_http_channel,
+            api::get_capture_stats,
+            api::subscribe_capture_status,
+            api::unsubscribe_capture_status,
+            api::subscribe_http_packets,
+            api::unsubscribe_http_packets,
+            api::subscribe_http_transactions,
+            api::unsubscribe_http_transactions,
+            api::subscribe_tls_events,
+            api::unsubscribe_tls_events,
+            api::subscribe_dns_events,
+            api::unsubscribe_dns_events,
+            api::subscribe_flow_updates,
+            api::unsubscribe_flow_updates,
+            api::subscribe_capture_summaries,
+            api::unsubscribe_capture_summaries,
+            api::subscribe_device_changes,
+            api::unsubscribe_device_changes,
             api::init_capture,
+            api::start_proxy_capture,
+            api::get_proxy_ca,
+            api::validate_capture_filter,
             api::stop_capture,
             api::has_pcap,
+            api::get_capture_prerequisites,
+            api::get_auth_queue_stats,
+            api::set_auth_queue_config,
             api::get_network_devices,
+            api::analyze_capture_file,
+            api::export_har,
+            api::import_har,
+            api::query_packets,
+            api::get_packet_by_id,
+            api::clear_packet_store,
+            api::set_packet_store_limits,
+            api::get_packet_store_stats,
+            api::list_flows,
+            api::get_flow,
+            api::get_redaction_policy,
+            api::set_redaction_policy,
+            api::set_show_secrets,
             api::create_packet_window,
             // Auth系统命令
             api::get_all_token_status,
             api::get_system_token,
             api::clear_system_token,
             api::clear_all_tokens,
-            api::set_token_event_channel,
+            api::subscribe_token_events,
+            api::unsubscribe_token_events,
             // 日志系统命令
             api::get_recent_logs,
             api::get_total_log_count,
@@ -43,6 +78,13 @@ pub fn run() {
             // BI平台查询命令
             api::send_bi_query,
         ])
+        .on_window_event(|window, event| {
+            // 窗口关闭后移除它的事件订阅
+            if let tauri::WindowEvent::Destroyed = event {
+                service::capture::remove_window_subscriptions(window.label());
+                service::auth::remove_window_subscriptions(window.label());
+            }
+        })
         .setup(|app| {
             // 初始化日志管理器基础组件（同步）
             service::logread::LogManager::init();
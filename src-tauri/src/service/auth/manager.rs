@@ -1,4 +1,4 @@
-use crate::service::capture::HttpPacket;
+use crate::service::capture::{session, HttpPacket};
 use crate::service::auth::{
     store::{TokenStatus, TokenStore},
     systems::{self, SystemAuth},
@@ -88,6 +88,9 @@ impl AuthService {
                         // 直接发送事件到前端（不经过内部通道）
                         send_token_event(event);
                         info!("📤 系统 [{}] 发送token更新事件", system_id);
+
+                        // 通知捕获会话，用于“获取到Token后停止”条件
+                        session::notify_token_acquired(&packet.session_id, system_id);
                     }
                 }
                 Ok(None) => {
@@ -4,17 +4,19 @@ pub mod store;
 
 use anyhow::Result;
 use crate::service::capture::HttpPacket;
+use crate::service::subscription::{EventFilter, SubscriptionRegistry};
 use log::{info, error};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use manager::AuthService;
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell};
 use tauri::ipc::Channel;
 
 // 重新导出主要类型
 pub use store::{TokenStatus};
 
-// 全局静态变量存储Token事件通道（参考抓包模块的实现）
-static TOKEN_EVENT_CHANNEL: OnceCell<Arc<Mutex<Option<Channel<TokenEvent>>>>> = OnceCell::new();
+// Token事件订阅表，多个窗口可以同时订阅（参考抓包模块的实现）
+static TOKEN_EVENT_SUBSCRIBERS: Lazy<SubscriptionRegistry<TokenEvent, TokenEventFilter>> =
+    Lazy::new(|| SubscriptionRegistry::new("Token事件"));
 static AUTH_SERVICE: OnceCell<Arc<AuthService>> = OnceCell::new();
 
 /// Token事件类型 - 和前端保持完全一致
@@ -45,58 +47,75 @@ pub enum TokenEvent {
     },
 }
 
-/// 设置Token事件通道（参考抓包模块）
-pub fn set_token_event_channel(channel: Channel<TokenEvent>) -> Result<()> {
-    if let Some(channels) = TOKEN_EVENT_CHANNEL.get() {
-        let mut guard = channels.lock().unwrap();
-        *guard = Some(channel);
-        info!("🔗 Token事件通道已设置");
-        Ok(())
-    } else {
-        let channels = Arc::new(Mutex::new(Some(channel)));
-        TOKEN_EVENT_CHANNEL
-            .set(channels)
-            .map_err(|_| anyhow::anyhow!("已经初始化过Token事件通道"))?;
-        info!("🔗 Token事件通道已初始化");
-        Ok(())
+impl TokenEvent {
+    /// 事件所属的系统ID
+    pub fn system_id(&self) -> &str {
+        match self {
+            TokenEvent::TokenAcquired { system_id, .. }
+            | TokenEvent::TokenExpired { system_id, .. }
+            | TokenEvent::TokenFailed { system_id, .. } => system_id,
+        }
     }
-}
 
-/// 直接发送Token事件到前端（参考抓包模块）
-pub fn send_token_event(event: TokenEvent) {
-    if let Some(channels) = TOKEN_EVENT_CHANNEL.get() {
-        if let Ok(guard) = channels.try_lock() {
-            if let Some(channel) = &*guard {
-                info!("📤 发送Token事件到前端: {:?}", event);
-                let channel_clone = channel.clone();
-                drop(guard); // 立即释放锁
-                if let Err(e) = channel_clone.send(event) {
-                    error!("❌ 发送Token事件失败: {}", e);
-                } else {
-                    info!("✅ Token事件发送成功");
-                }
-            } else {
-                error!("❌ Token事件通道未设置");
+    /// 用于日志的事件描述，不包含Token本身
+    fn describe(&self) -> String {
+        match self {
+            TokenEvent::TokenAcquired { system_id, source_url, .. } => {
+                format!("TokenAcquired 系统[{}] 来源 {}", system_id, source_url)
+            }
+            TokenEvent::TokenExpired { system_id, .. } => format!("TokenExpired 系统[{}]", system_id),
+            TokenEvent::TokenFailed { system_id, error, .. } => {
+                format!("TokenFailed 系统[{}]: {}", system_id, error)
             }
-        } else {
-            error!("❌ Token事件通道正忙，跳过发送");
         }
-    } else {
-        error!("❌ Token事件通道未初始化");
     }
 }
 
+/// Token事件订阅过滤条件
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TokenEventFilter {
+    /// 只接收这些系统的事件，为空时接收全部
+    pub system_ids: Vec<String>,
+}
+
+impl EventFilter<TokenEvent> for TokenEventFilter {
+    fn matches(&self, event: &TokenEvent) -> bool {
+        self.system_ids.is_empty() || self.system_ids.iter().any(|id| id == event.system_id())
+    }
+}
+
+/// 订阅Token事件，返回订阅者ID；owner 为订阅所在的窗口标签
+pub fn subscribe_token_events(channel: Channel<TokenEvent>, filter: TokenEventFilter, owner: &str) -> Result<u64> {
+    let id = TOKEN_EVENT_SUBSCRIBERS.subscribe(channel, filter, owner)?;
+    info!("🔗 Token事件订阅已添加: {}", id);
+    Ok(id)
+}
+
+/// 取消Token事件订阅，返回订阅者是否存在
+pub fn unsubscribe_token_events(subscriber_id: u64) -> bool {
+    TOKEN_EVENT_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+/// 窗口销毁时移除它的Token事件订阅
+pub fn remove_window_subscriptions(owner: &str) {
+    TOKEN_EVENT_SUBSCRIBERS.remove_owner(owner);
+}
+
+/// 发送Token事件给所有订阅者（参考抓包模块）
+pub fn send_token_event(event: TokenEvent) {
+    if TOKEN_EVENT_SUBSCRIBERS.subscriber_count() == 0 {
+        error!("❌ 没有Token事件订阅者，跳过发送: {}", event.describe());
+        return;
+    }
+    info!("📤 发送Token事件到前端: {}", event.describe());
+    TOKEN_EVENT_SUBSCRIBERS.send(&event);
+}
+
 /// 初始化简化的认证系统
 pub async fn init_auth_system() -> Result<()> {
     info!("🚀 开始初始化简化的Token认证系统...");
     
-    // 初始化Token事件通道存储
-    if TOKEN_EVENT_CHANNEL.get().is_none() {
-        TOKEN_EVENT_CHANNEL
-            .set(Arc::new(Mutex::new(None)))
-            .map_err(|_| anyhow::anyhow!("Token事件通道存储已经初始化过"))?;
-    }
-    
     // 创建认证服务
     let auth_service = Arc::new(AuthService::new().await);
     
@@ -162,9 +181,4 @@ pub async fn get_system_token(system_id: &str) -> Option<String> {
         error!("❌ 认证系统未初始化，无法获取token");
         None
     }
-}
-
-/// 设置前端Token事件通道
-pub fn set_token_event_channel_sync(channel: Channel<TokenEvent>) -> Result<()> {
-    set_token_event_channel(channel)
 }
\ No newline at end of file
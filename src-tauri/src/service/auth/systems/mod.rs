@@ -31,7 +31,8 @@ pub trait SystemAuth {
 
 /// Token验证器接口
 pub trait TokenValidator: Send + Sync + std::fmt::Debug {
-    fn validate(&self, token: &str) -> Result<()>;
+    /// 验证提取到的token，Cookie 类token直接使用数据包已解析的 Cookie
+    fn validate(&self, token: &str, packet: &HttpPacket) -> Result<()>;
 }
 
 /// 系统配置
@@ -82,9 +83,8 @@ impl SystemConfig {
             
         let auth_header = match auth_header {
             Some(header) => {
-                debug!("📋 系统[{}]找到{}header: {}...", 
-                       self.system_id, self.header_name,
-                       &header[..header.len().min(20)]);
+                debug!("📋 系统[{}]找到{}header（{} 字节）", 
+                       self.system_id, self.header_name, header.len());
                 header
             }
             None => {
@@ -128,13 +128,11 @@ impl SystemAuth for SystemConfig {
             return Ok(None); // 没有token更新
         }
         
-        let url = build_url(packet);
-        
-        // 检查URL是否匹配
-        if !self.matches_url(&url) {
+        // 检查URL是否匹配（Host 头或目标地址，以及 DNS 解析出的主机名）
+        let Some(url) = build_urls(packet).into_iter().find(|url| self.matches_url(url)) else {
             debug!("⏭️ 系统[{}]跳过处理：URL不匹配", self.system_id);
             return Ok(None); // 没有token更新
-        }
+        };
         
         info!("🎯 系统[{}]检测到匹配的URL: {}", self.system_id, url);
         
@@ -152,7 +150,7 @@ impl SystemAuth for SystemConfig {
         };
         
         // 验证token
-        if let Err(e) = self.validator.validate(&token) {
+        if let Err(e) = self.validator.validate(&token, packet) {
             warn!("❌ 系统[{}]token验证失败: {}", self.system_id, e);
             return Ok(None); // 没有token更新
         }
@@ -226,7 +224,23 @@ fn build_url(packet: &HttpPacket) -> String {
     } else {
         format!("{}:{}", packet.dst_ip, packet.dst_port)
     };
-    
+    url_with_host(packet, &host)
+}
+
+/// 构建候选URL：先是 build_url 的结果，目标地址有 DNS 解析出的主机名时再加上以主机名构建的URL
+fn build_urls(packet: &HttpPacket) -> Vec<String> {
+    let mut urls = vec![build_url(packet)];
+    if let Some(name) = packet.dst_name.as_deref().filter(|name| !name.eq_ignore_ascii_case(&packet.host)) {
+        let host = match packet.dst_port {
+            80 | 443 => name.to_string(),
+            port => format!("{}:{}", name, port),
+        };
+        urls.push(url_with_host(packet, &host));
+    }
+    urls
+}
+
+fn url_with_host(packet: &HttpPacket, host: &str) -> String {
     let protocol = if packet.dst_port == 443 { "https" } else { "http" };
     let default_path = "/".to_string();
     let path = packet.path.as_ref().unwrap_or(&default_path);
@@ -1,40 +1,27 @@
 use super::{TokenValidator, SystemConfig};
 use anyhow::{Result, anyhow};
 use log::{warn, debug, info};
+use crate::service::capture::HttpPacket;
 
 /// 长度Token验证器
 #[derive(Debug)]
 struct LengthValidator;
 
 impl TokenValidator for LengthValidator {
-    fn validate(&self, token: &str) -> Result<()> {
+    fn validate(&self, _token: &str, packet: &HttpPacket) -> Result<()> {
         debug!("🔐 开始验证BI系统Cookie");
         
-        // 检查是否包含x_login_pk字段
-        if !token.contains("x_login_pk=") {
+        // 从 Cookie 中查找x_login_pk字段
+        let Some(value) = packet.cookie("x_login_pk") else {
             let error_msg = "Cookie中缺少x_login_pk字段";
             warn!("❌ Cookie验证失败: {}", error_msg);
             return Err(anyhow!(error_msg));
+        };
+        if value.is_empty() {
+            warn!("❌ Cookie验证失败: x_login_pk值为空");
+            return Err(anyhow!("x_login_pk值为空"));
         }
-        
-        // 提取x_login_pk的值
-        let re = regex::Regex::new(r"x_login_pk=([^;]+)").unwrap();
-        if let Some(captures) = re.captures(token) {
-            if let Some(pk_value) = captures.get(1) {
-                let value = pk_value.as_str();
-                if value.is_empty() {
-                    warn!("❌ Cookie验证失败: x_login_pk值为空");
-                    return Err(anyhow!("x_login_pk值为空"));
-                }
-                debug!("✅ 找到有效的x_login_pk值");
-            } else {
-                warn!("❌ Cookie验证失败: 无法提取x_login_pk的值");
-                return Err(anyhow!("无法提取x_login_pk的值"));
-            }
-        } else {
-            warn!("❌ Cookie验证失败: x_login_pk格式不正确");
-            return Err(anyhow!("x_login_pk格式不正确"));
-        }
+        debug!("✅ 找到有效的x_login_pk值");
         
         info!("🔐 BI系统Cookie验证通过");
         Ok(())
@@ -1,40 +1,27 @@
 use super::{TokenValidator, SystemConfig};
 use anyhow::{Result, anyhow};
 use log::{warn, debug, info};
+use crate::service::capture::HttpPacket;
 
 /// 长度Token验证器
 #[derive(Debug)]
 struct LengthValidator;
 
 impl TokenValidator for LengthValidator {
-    fn validate(&self, token: &str) -> Result<()> {
+    fn validate(&self, _token: &str, packet: &HttpPacket) -> Result<()> {
         debug!("🔐 开始验证测试系统Cookie");
         
-        // 检查是否包含wdcid字段
-        if !token.contains("wdcid=") {
+        // 从 Cookie 中查找wdcid字段
+        let Some(value) = packet.cookie("wdcid") else {
             let error_msg = "Cookie中缺少wdcid字段";
             warn!("❌ Cookie验证失败: {}", error_msg);
             return Err(anyhow!(error_msg));
+        };
+        if value.is_empty() {
+            warn!("❌ Cookie验证失败: wdcid值为空");
+            return Err(anyhow!("wdcid值为空"));
         }
-        
-        // 提取wdcid的值
-        let re = regex::Regex::new(r"wdcid=([^;]+)").unwrap();
-        if let Some(captures) = re.captures(token) {
-            if let Some(pk_value) = captures.get(1) {
-                let value = pk_value.as_str();
-                if value.is_empty() {
-                    warn!("❌ Cookie验证失败: wdcid值为空");
-                    return Err(anyhow!("wdcid值为空"));
-                }
-                debug!("✅ 找到有效的wdcid值");
-            } else {
-                warn!("❌ Cookie验证失败: 无法提取wdcid的值");
-                return Err(anyhow!("无法提取wdcid的值"));
-            }
-        } else {
-            warn!("❌ Cookie验证失败: wdcid格式不正确");
-            return Err(anyhow!("wdcid格式不正确"));
-        }
+        debug!("✅ 找到有效的wdcid值");
         
         info!("🔐 测试系统Cookie验证通过");
         Ok(())
@@ -1,13 +1,14 @@
 use super::{TokenValidator, SystemConfig};
 use anyhow::{Result, anyhow};
 use log::{warn, debug, info};
+use crate::service::capture::HttpPacket;
 
 /// 数据Token验证器
 #[derive(Debug)]
 struct DataTokenValidator;
 
 impl TokenValidator for DataTokenValidator {
-    fn validate(&self, token: &str) -> Result<()> {
+    fn validate(&self, token: &str, _packet: &HttpPacket) -> Result<()> {
         debug!("🔐 数据Token验证器开始验证token，长度: {}", token.len());
         
         if token.len() <= 10 {
@@ -0,0 +1,235 @@
+use super::HttpPacket;
+use anyhow::{anyhow, Result};
+use log::{debug, error, info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// 默认队列容量
+const DEFAULT_CAPACITY: usize = 1024;
+/// Block 策略下最长等待时间，超时后丢弃新数据包，避免认证系统卡住时拖死捕获线程
+const MAX_BLOCK_WAIT: Duration = Duration::from_secs(5);
+
+static AUTH_QUEUE: Lazy<AuthQueue> = Lazy::new(AuthQueue::new);
+
+/// 队列满时的处理策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// 丢弃最早入队的数据包
+    #[default]
+    DropOldest,
+    /// 阻塞捕获线程直到有空位（最长等待 5 秒）
+    Block,
+}
+
+/// 认证队列配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthQueueConfig {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for AuthQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// 认证队列统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthQueueStats {
+    pub config: AuthQueueConfig,
+    /// 当前排队的数据包数量
+    pub queued: usize,
+    pub enqueued: u64,
+    pub processed: u64,
+    /// 认证系统处理失败的数量
+    pub failed: u64,
+    /// 因队列溢出（或消费者未启动）被丢弃的数量
+    pub dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    packets: VecDeque<HttpPacket>,
+    config: AuthQueueConfig,
+}
+
+/// 捕获线程与认证系统之间的有界队列
+///
+/// 捕获线程同步入队，应用运行时上的单个异步消费者按顺序取出处理，
+/// 保证 token 更新的顺序与数据包到达顺序一致。
+pub struct AuthQueue {
+    state: Mutex<QueueState>,
+    /// 出队时通知阻塞中的生产者
+    space: Condvar,
+    /// 入队时唤醒消费者
+    ready: Notify,
+    consumer_started: AtomicBool,
+    enqueued: AtomicU64,
+    processed: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl AuthQueue {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+            space: Condvar::new(),
+            ready: Notify::new(),
+            consumer_started: AtomicBool::new(false),
+            enqueued: AtomicU64::new(0),
+            processed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// 提交数据包，按溢出策略处理队列已满的情况
+    fn submit(&self, packet: HttpPacket) {
+        if !self.consumer_started.load(Ordering::Acquire) {
+            debug!("认证队列消费者未启动，丢弃数据包 {}", packet.id);
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let deadline = Instant::now() + MAX_BLOCK_WAIT;
+        while state.packets.len() >= state.config.capacity.max(1) {
+            match state.config.overflow {
+                OverflowPolicy::DropOldest => {
+                    state.packets.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                OverflowPolicy::Block => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        warn!("认证队列持续已满，丢弃数据包 {}", packet.id);
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                    state = self
+                        .space
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+            }
+        }
+
+        state.packets.push_back(packet);
+        drop(state);
+        self.enqueued.fetch_add(1, Ordering::Relaxed);
+        self.ready.notify_one();
+    }
+
+    fn pop(&self) -> Option<HttpPacket> {
+        let packet = self
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .packets
+            .pop_front();
+        if packet.is_some() {
+            self.space.notify_all();
+        }
+        packet
+    }
+
+    fn stats(&self) -> AuthQueueStats {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        AuthQueueStats {
+            config: state.config.clone(),
+            queued: state.packets.len(),
+            enqueued: self.enqueued.load(Ordering::Relaxed),
+            processed: self.processed.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 在应用的异步运行时上启动认证消费者，只需调用一次
+pub fn start_auth_consumer() {
+    if AUTH_QUEUE.consumer_started.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async {
+        info!("认证队列消费者已启动");
+        loop {
+            let Some(packet) = AUTH_QUEUE.pop() else {
+                AUTH_QUEUE.ready.notified().await;
+                continue;
+            };
+
+            match crate::service::auth::process_http_packet(&packet).await {
+                Ok(_) => debug!("✅ 认证系统处理数据包 {} 成功", packet.id),
+                Err(e) => {
+                    AUTH_QUEUE.failed.fetch_add(1, Ordering::Relaxed);
+                    error!("❌ 认证系统处理数据包 {} 失败: {}", packet.id, e);
+                }
+            }
+            AUTH_QUEUE.processed.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+}
+
+/// 把数据包交给认证系统处理
+pub fn submit_packet(packet: HttpPacket) {
+    AUTH_QUEUE.submit(packet);
+}
+
+pub fn set_auth_queue_config(config: AuthQueueConfig) -> Result<()> {
+    if config.capacity == 0 {
+        return Err(anyhow!("认证队列容量必须大于 0"));
+    }
+
+    let mut state = AUTH_QUEUE.state.lock().unwrap_or_else(PoisonError::into_inner);
+    // 缩小容量时按最早优先丢弃多出的数据包
+    while state.packets.len() > config.capacity {
+        state.packets.pop_front();
+        AUTH_QUEUE.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+    info!("认证队列配置已更新: 容量 {}，溢出策略 {:?}", config.capacity, config.overflow);
+    state.config = config;
+    drop(state);
+    AUTH_QUEUE.space.notify_all();
+    Ok(())
+}
+
+pub fn auth_queue_stats() -> AuthQueueStats {
+    AUTH_QUEUE.stats()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest_keeps_order() {
+        let queue = AuthQueue::new();
+        queue.consumer_started.store(true, Ordering::Release);
+        queue.state.lock().unwrap().config.capacity = 2;
+
+        for id in 1..=3 {
+            queue.submit(HttpPacket { id, ..Default::default() });
+        }
+
+        let stats = queue.stats();
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(stats.queued, 2);
+        assert_eq!(queue.pop().map(|p| p.id), Some(2));
+        assert_eq!(queue.pop().map(|p| p.id), Some(3));
+    }
+}
@@ -0,0 +1,241 @@
+use super::reassembly::find_subslice;
+use anyhow::{anyhow, Result};
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use log::debug;
+use std::io::{ErrorKind, Read};
+
+/// 解码后消息体的最大字节数，防止解压炸弹
+pub const MAX_DECODED_BODY: usize = 32 * 1024 * 1024;
+
+/// 消息体解码结果
+#[derive(Debug, Default)]
+pub struct DecodedBody {
+    /// 去除分块编码并解压后的数据
+    pub data: Vec<u8>,
+    /// 原始的 Transfer-Encoding 头
+    pub transfer_encoding: Option<String>,
+    /// 原始的 Content-Encoding 头
+    pub content_encoding: Option<String>,
+    /// 解码结果是否不完整：超过大小上限，或压缩流在中途结束
+    pub truncated: bool,
+    /// 解码失败时的错误信息（此时 data 为最后一个成功阶段的数据）
+    pub error: Option<String>,
+}
+
+/// 按 Transfer-Encoding 和 Content-Encoding 解码消息体
+pub fn decode_body(headers: &[(String, String)], raw: &[u8]) -> DecodedBody {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim().to_string())
+    };
+
+    let mut decoded = DecodedBody {
+        transfer_encoding: header("transfer-encoding"),
+        content_encoding: header("content-encoding"),
+        ..Default::default()
+    };
+
+    let mut data = raw.to_vec();
+
+    // 先去掉分块传输编码
+    if decoded
+        .transfer_encoding
+        .as_deref()
+        .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"))
+    {
+        match decode_chunked(&data) {
+            Ok((body, truncated)) => {
+                data = body;
+                decoded.truncated |= truncated;
+            }
+            Err(e) => {
+                decoded.error = Some(e.to_string());
+                decoded.data = data;
+                return decoded;
+            }
+        }
+    }
+
+    // 多个内容编码按声明的逆序解开，例如 "gzip, br" 需要先解 br
+    if let Some(content_encoding) = decoded.content_encoding.clone() {
+        let codings: Vec<String> = content_encoding
+            .split(',')
+            .map(|c| c.trim().to_ascii_lowercase())
+            .filter(|c| !c.is_empty() && c != "identity")
+            .collect();
+
+        for coding in codings.iter().rev() {
+            match decompress(coding, &data) {
+                Ok((body, truncated)) => {
+                    data = body;
+                    decoded.truncated |= truncated;
+                }
+                Err(e) => {
+                    debug!("消息体解码失败 ({}): {}", coding, e);
+                    decoded.error = Some(format!("{}: {}", coding, e));
+                    break;
+                }
+            }
+        }
+    }
+
+    decoded.data = data;
+    decoded
+}
+
+/// 解开分块传输编码，返回 (数据, 是否被截断)
+///
+/// 数据不完整时（没有结束分块，或最后一个分块不完整）返回已经解出的部分并标记为截断，
+/// 格式错误时返回错误。
+pub fn decode_chunked(data: &[u8]) -> Result<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let Some(line_len) = find_subslice(&data[pos..], b"\r\n") else {
+            break;
+        };
+        let size_line = String::from_utf8_lossy(&data[pos..pos + line_len]);
+        let size_str = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_str, 16)
+            .map_err(|_| anyhow!("无效的分块大小: {}", size_str))?;
+        pos += line_len + 2;
+
+        if size == 0 {
+            return Ok((body, false));
+        }
+
+        let end = (pos + size).min(data.len());
+        if body.len() + (end - pos) > MAX_DECODED_BODY {
+            return Err(anyhow!("分块消息体超过 {} 字节上限", MAX_DECODED_BODY));
+        }
+        body.extend_from_slice(&data[pos..end]);
+        pos = end + 2;
+    }
+
+    Ok((body, true))
+}
+
+/// 按内容编码解压，返回 (数据, 是否被截断)
+fn decompress(coding: &str, data: &[u8]) -> Result<(Vec<u8>, bool)> {
+    match coding {
+        "gzip" | "x-gzip" => read_limited(MultiGzDecoder::new(data)),
+        // deflate 规范上是 zlib 格式，但不少服务器直接发送原始 deflate 流
+        "deflate" => read_limited(ZlibDecoder::new(data))
+            .or_else(|_| read_limited(DeflateDecoder::new(data))),
+        "br" => read_limited(brotli::Decompressor::new(data, 4096)),
+        other => Err(anyhow!("不支持的内容编码: {}", other)),
+    }
+}
+
+/// 读取解压结果，最多 `MAX_DECODED_BODY` 字节，返回 (数据, 是否被截断)
+///
+/// 压缩流不完整时（例如只捕获到部分消息体）保留已经解出的数据并标记为截断，
+/// 一个字节都解不出来时才返回错误。
+fn read_limited<R: Read>(mut reader: R) -> Result<(Vec<u8>, bool)> {
+    let mut out = Vec::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok((out, false)),
+            Ok(n) => {
+                if out.len() + n > MAX_DECODED_BODY {
+                    let keep = MAX_DECODED_BODY - out.len();
+                    out.extend_from_slice(&buf[..keep]);
+                    return Ok((out, true));
+                }
+                out.extend_from_slice(&buf[..n]);
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) if !out.is_empty() => {
+                debug!("压缩流不完整，保留已解出的 {} 字节: {}", out.len(), e);
+                return Ok((out, true));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        let raw = b"5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let decoded = decode_body(&headers(&[("Transfer-Encoding", "chunked")]), raw);
+        assert_eq!(decoded.data, b"hello world");
+        assert!(decoded.error.is_none());
+        assert!(!decoded.truncated);
+    }
+
+    #[test]
+    fn test_decode_chunked_truncated() {
+        let raw = b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let chunked = headers(&[("Transfer-Encoding", "chunked")]);
+
+        // 只捕获到一半：第二个分块不完整，也没有结束分块
+        let decoded = decode_body(&chunked, &raw[..raw.len() / 2]);
+        assert!(decoded.error.is_none());
+        assert!(decoded.truncated);
+        assert_eq!(decoded.data, b"hello");
+
+        // 最后一个分块只有部分数据
+        let decoded = decode_body(&chunked, &raw[..15]);
+        assert!(decoded.truncated);
+        assert_eq!(decoded.data, b"hello w");
+    }
+
+    #[test]
+    fn test_decode_chunked_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all("{\"data\":\"数据\"}".as_bytes()).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        let mut raw = format!("{:x}\r\n", gz.len()).into_bytes();
+        raw.extend_from_slice(&gz);
+        raw.extend_from_slice(b"\r\n0\r\n\r\n");
+
+        let decoded = decode_body(
+            &headers(&[("Transfer-Encoding", "chunked"), ("Content-Encoding", "gzip")]),
+            &raw,
+        );
+        assert_eq!(String::from_utf8(decoded.data).unwrap(), "{\"data\":\"数据\"}");
+        assert!(!decoded.truncated);
+    }
+
+    #[test]
+    fn test_invalid_encoding_keeps_data() {
+        let decoded = decode_body(&headers(&[("Content-Encoding", "gzip")]), b"not gzip");
+        assert!(decoded.error.is_some());
+        assert_eq!(decoded.data, b"not gzip");
+    }
+
+    #[test]
+    fn test_truncated_gzip_keeps_partial_output() {
+        let text: String = (0..2000).map(|i| format!("line {}: {}\n", i, i * 7919 % 10007)).collect();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        // 只捕获到前一半压缩数据
+        let decoded = decode_body(&headers(&[("Content-Encoding", "gzip")]), &gz[..gz.len() / 2]);
+        assert!(decoded.error.is_none());
+        assert!(decoded.truncated);
+        assert!(!decoded.data.is_empty());
+        assert!(text.as_bytes().starts_with(&decoded.data));
+
+        let complete = decode_body(&headers(&[("Content-Encoding", "gzip")]), &gz);
+        assert!(!complete.truncated);
+        assert_eq!(complete.data, text.as_bytes());
+    }
+}
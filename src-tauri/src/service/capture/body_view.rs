@@ -0,0 +1,221 @@
+use super::body::decode_body;
+use super::reassembly::find_subslice;
+use super::HttpPacket;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+
+/// 按 Content-Type 解析出的结构化消息体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BodyView {
+    Empty,
+    /// 格式化后的 JSON
+    Json { pretty: String },
+    /// application/x-www-form-urlencoded 字段
+    Form { fields: Vec<FormField> },
+    /// multipart/form-data 各部分
+    Multipart { boundary: String, parts: Vec<MultipartPart> },
+    Text { text: String },
+    /// 无法按文本显示的二进制数据
+    Binary { base64: String, size: usize },
+}
+
+/// 表单字段（已完成 URL 解码）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormField {
+    pub name: String,
+    pub value: String,
+    /// 字段值本身是 JSON 对象或数组时的格式化结果，例如 olapQueryParam
+    pub json: Option<String>,
+}
+
+/// multipart 中的一个部分
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultipartPart {
+    pub name: Option<String>,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub size: usize,
+    pub body: BodyView,
+}
+
+/// 为数据包生成结构化消息体视图
+///
+/// 从原始消息体重新解码，避免受 `HttpPacket.body` 有损 UTF-8 转换的影响。
+/// 脱敏后的数据包已清除编码字段，原始数据即为明文。
+pub fn view_for_packet(packet: &HttpPacket) -> BodyView {
+    body_view(&packet.content_type, &decoded_body(packet))
+}
+
+/// 从原始消息体重新解码出完整的消息体字节
+pub fn decoded_body(packet: &HttpPacket) -> Vec<u8> {
+    let raw = BASE64.decode(&packet.raw_body).unwrap_or_default();
+    if packet.transfer_encoding.is_none() && packet.content_encoding.is_none() {
+        return raw;
+    }
+    decode_body(&packet.headers, &raw).data
+}
+
+/// 按 Content-Type 选择消息体视图
+pub fn body_view(content_type: &str, data: &[u8]) -> BodyView {
+    if data.is_empty() {
+        return BodyView::Empty;
+    }
+
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if mime == "application/x-www-form-urlencoded" {
+        return BodyView::Form { fields: parse_form(data) };
+    }
+
+    if mime.starts_with("multipart/") {
+        if let Some(boundary) = content_type_param(content_type, "boundary") {
+            return BodyView::Multipart {
+                parts: parse_multipart(data, &boundary),
+                boundary,
+            };
+        }
+    }
+
+    // 声明为 JSON，或者未声明类型但内容看起来是 JSON
+    let trimmed = data.trim_ascii_start();
+    if mime.contains("json") || trimmed.starts_with(b"{") || trimmed.starts_with(b"[") {
+        if let Some(pretty) = pretty_json(data) {
+            return BodyView::Json { pretty };
+        }
+    }
+
+    match std::str::from_utf8(data) {
+        Ok(text) => BodyView::Text { text: text.to_string() },
+        Err(_) => BodyView::Binary {
+            base64: BASE64.encode(data),
+            size: data.len(),
+        },
+    }
+}
+
+/// 解析 JSON 并格式化，失败时返回 None
+fn pretty_json(data: &[u8]) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_slice(data).ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+fn parse_form(data: &[u8]) -> Vec<FormField> {
+    url::form_urlencoded::parse(data)
+        .map(|(name, value)| {
+            let trimmed = value.trim_start();
+            let json = (trimmed.starts_with('{') || trimmed.starts_with('['))
+                .then(|| pretty_json(value.as_bytes()))
+                .flatten();
+            FormField {
+                name: name.into_owned(),
+                value: value.into_owned(),
+                json,
+            }
+        })
+        .collect()
+}
+
+/// 读取 Content-Type 或 Content-Disposition 中的参数，去掉引号
+fn content_type_param(header: &str, name: &str) -> Option<String> {
+    header.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+fn parse_multipart(data: &[u8], boundary: &str) -> Vec<MultipartPart> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+
+    let Some(start) = find_subslice(data, &delimiter) else {
+        return parts;
+    };
+    let mut rest = &data[start + delimiter.len()..];
+
+    // 每个部分以 "\r\n" 开始，结束分隔符后跟 "--"
+    while let Some(after_crlf) = rest.strip_prefix(b"\r\n") {
+        let (content, next) = match find_subslice(after_crlf, &[b"\r\n".as_slice(), &delimiter].concat()) {
+            Some(end) => (&after_crlf[..end], &after_crlf[end + 2 + delimiter.len()..]),
+            // 消息体被截断，最后一个部分取到末尾
+            None => (after_crlf, &[][..]),
+        };
+        parts.push(parse_part(content));
+        rest = next;
+    }
+
+    parts
+}
+
+fn parse_part(content: &[u8]) -> MultipartPart {
+    let (head, body) = match find_subslice(content, b"\r\n\r\n") {
+        Some(pos) => (&content[..pos], &content[pos + 4..]),
+        None => (&[][..], content),
+    };
+
+    let headers: Vec<(String, String)> = String::from_utf8_lossy(head)
+        .split("\r\n")
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.clone())
+    };
+
+    let disposition = header("content-disposition").unwrap_or_default();
+    let content_type = header("content-type");
+    MultipartPart {
+        name: content_type_param(&disposition, "name"),
+        filename: content_type_param(&disposition, "filename"),
+        size: body.len(),
+        body: body_view(content_type.as_deref().unwrap_or_default(), body),
+        content_type,
+        headers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_form_with_nested_json() {
+        let data = b"olapQueryParam=%7B%22cube%22%3A%22sales%22%2C%22limit%22%3A10%7D&page=1";
+        let view = body_view("application/x-www-form-urlencoded; charset=UTF-8", data);
+        let BodyView::Form { fields } = view else {
+            panic!("expected form view");
+        };
+        assert_eq!(fields[0].name, "olapQueryParam");
+        assert_eq!(fields[0].value, r#"{"cube":"sales","limit":10}"#);
+        assert!(fields[0].json.as_deref().unwrap().contains("\"cube\": \"sales\""));
+        assert_eq!(fields[1].json, None);
+    }
+
+    #[test]
+    fn test_multipart_parts() {
+        let data = b"--XyZ\r\nContent-Disposition: form-data; name=\"desc\"\r\n\r\nreport\r\n\
+--XyZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n\xff\x00\x01\r\n--XyZ--\r\n";
+        let view = body_view("multipart/form-data; boundary=\"XyZ\"", data);
+        let BodyView::Multipart { parts, .. } = view else {
+            panic!("expected multipart view");
+        };
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].body, BodyView::Text { text: "report".to_string() });
+        assert_eq!(parts[1].filename.as_deref(), Some("a.bin"));
+        assert_eq!(parts[1].size, 3);
+        assert!(matches!(parts[1].body, BodyView::Binary { size: 3, .. }));
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyPair, KeyUsagePurpose,
+};
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::ServerConfig;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use time::{Duration, OffsetDateTime};
+
+/// 根证书的通用名称，用户在系统证书管理中按此名称查找
+const CA_COMMON_NAME: &str = "big-data-rpa-v4 Proxy CA";
+/// 根证书有效期
+const CA_VALIDITY_DAYS: i64 = 3650;
+/// 站点证书有效期
+const LEAF_VALIDITY_DAYS: i64 = 397;
+/// 最多缓存的站点证书数量
+const MAX_CACHED_HOSTS: usize = 1024;
+
+/// 本地根证书，用于为代理的 HTTPS 站点签发证书
+///
+/// 首次使用时生成并保存到数据目录，之后重复使用，用户只需信任一次。
+pub struct LocalCa {
+    cert: Certificate,
+    key: KeyPair,
+    cert_pem: String,
+    cert_path: PathBuf,
+    /// 主机名 -> TLS 服务端配置
+    host_configs: Mutex<HashMap<String, Arc<ServerConfig>>>,
+}
+
+impl LocalCa {
+    /// 从默认目录加载根证书，不存在时生成新的
+    pub fn load_or_create() -> Result<Self> {
+        let dir = dirs::data_dir()
+            .ok_or_else(|| anyhow!("无法获取数据目录"))?
+            .join("big-data-rpa-v4")
+            .join("proxy-ca");
+        Self::load_or_create_in(&dir)
+    }
+
+    pub fn load_or_create_in(dir: &Path) -> Result<Self> {
+        let cert_path = dir.join("ca.pem");
+        let key_path = dir.join("ca.key");
+
+        let (cert, key, cert_pem) = if cert_path.exists() && key_path.exists() {
+            let cert_pem = std::fs::read_to_string(&cert_path).map_err(|e| anyhow!("读取根证书失败: {}", e))?;
+            let key_pem = std::fs::read_to_string(&key_path).map_err(|e| anyhow!("读取根证书私钥失败: {}", e))?;
+            let key = KeyPair::from_pem(&key_pem).map_err(|e| anyhow!("根证书私钥无效: {}", e))?;
+            // 从已保存的参数重建证书对象，签发时使用其中的颁发者名称和密钥标识
+            let cert = CertificateParams::from_ca_cert_pem(&cert_pem)
+                .and_then(|params| params.self_signed(&key))
+                .map_err(|e| anyhow!("加载根证书失败: {}", e))?;
+            (cert, key, cert_pem)
+        } else {
+            std::fs::create_dir_all(dir).map_err(|e| anyhow!("创建根证书目录失败: {}", e))?;
+            // 只剩私钥没有证书时重新生成，先移除旧私钥
+            if key_path.exists() {
+                std::fs::remove_file(&key_path).map_err(|e| anyhow!("移除旧的根证书私钥失败: {}", e))?;
+            }
+            let key = KeyPair::generate().map_err(|e| anyhow!("生成根证书私钥失败: {}", e))?;
+            let cert = ca_params().self_signed(&key).map_err(|e| anyhow!("生成根证书失败: {}", e))?;
+            let cert_pem = cert.pem();
+            std::fs::write(&cert_path, &cert_pem).map_err(|e| anyhow!("保存根证书失败: {}", e))?;
+            write_private_key(&key_path, &key.serialize_pem())?;
+            info!("已生成代理根证书: {}", cert_path.display());
+            (cert, key, cert_pem)
+        };
+
+        Ok(Self {
+            cert,
+            key,
+            cert_pem,
+            cert_path,
+            host_configs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 根证书 PEM，用于导入系统或浏览器的受信任根证书
+    pub fn cert_pem(&self) -> &str {
+        &self.cert_pem
+    }
+
+    pub fn cert_path(&self) -> &Path {
+        &self.cert_path
+    }
+
+    /// 获取主机对应的 TLS 服务端配置，首次访问时签发证书
+    pub fn server_config(&self, host: &str) -> Result<Arc<ServerConfig>> {
+        let host = host.to_ascii_lowercase();
+        if let Some(config) = self.host_configs.lock().ok().and_then(|c| c.get(&host).cloned()) {
+            return Ok(config);
+        }
+
+        let config = Arc::new(self.issue(&host)?);
+        if let Ok(mut configs) = self.host_configs.lock() {
+            if configs.len() >= MAX_CACHED_HOSTS {
+                configs.clear();
+            }
+            configs.insert(host, config.clone());
+        }
+        Ok(config)
+    }
+
+    /// 为主机签发站点证书并生成 TLS 配置
+    fn issue(&self, host: &str) -> Result<ServerConfig> {
+        let mut params =
+            CertificateParams::new(vec![host.to_string()]).map_err(|e| anyhow!("站点证书参数无效 {}: {}", host, e))?;
+        params.distinguished_name = DistinguishedName::new();
+        params.distinguished_name.push(DnType::CommonName, host);
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        // 提前一天生效，避免客户端时钟偏差
+        let now = OffsetDateTime::now_utc();
+        params.not_before = now - Duration::days(1);
+        params.not_after = now + Duration::days(LEAF_VALIDITY_DAYS);
+
+        let key = KeyPair::generate().map_err(|e| anyhow!("生成站点证书私钥失败: {}", e))?;
+        let cert = params
+            .signed_by(&key, &self.cert, &self.key)
+            .map_err(|e| anyhow!("签发站点证书失败 {}: {}", host, e))?;
+
+        let chain = vec![cert.der().clone(), self.cert.der().clone()];
+        let private_key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der()));
+        let mut config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| anyhow!("TLS 配置失败: {}", e))?
+            .with_no_client_auth()
+            .with_single_cert(chain, private_key)
+            .map_err(|e| anyhow!("TLS 配置失败: {}", e))?;
+        // 代理只解析 HTTP/1.1
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+fn ca_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    params.distinguished_name.push(DnType::CommonName, CA_COMMON_NAME);
+    params.distinguished_name.push(DnType::OrganizationName, "big-data-rpa-v4");
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign, KeyUsagePurpose::DigitalSignature];
+    let now = OffsetDateTime::now_utc();
+    params.not_before = now - Duration::days(1);
+    params.not_after = now + Duration::days(CA_VALIDITY_DAYS);
+    params
+}
+
+/// 保存私钥，Unix 上创建时即只允许当前用户读写，不存在可被其他用户读取的窗口期
+fn write_private_key(path: &Path, pem: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| anyhow!("创建根证书私钥文件失败: {}", e))?;
+    file.write_all(pem.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| anyhow!("保存根证书私钥失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ca_is_reused_and_issues_host_certs() {
+        let dir = std::env::temp_dir().join(format!("proxy-ca-test-{}", std::process::id()));
+        let ca = LocalCa::load_or_create_in(&dir).unwrap();
+        assert!(ca.cert_pem().contains("BEGIN CERTIFICATE"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join("ca.key")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let config = ca.server_config("portal.example.com").unwrap();
+        assert!(Arc::ptr_eq(&config, &ca.server_config("PORTAL.example.com").unwrap()));
+
+        let reloaded = LocalCa::load_or_create_in(&dir).unwrap();
+        assert_eq!(reloaded.cert_pem(), ca.cert_pem());
+        assert!(reloaded.server_config("10.1.2.3").is_ok());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
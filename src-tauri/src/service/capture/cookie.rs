@@ -0,0 +1,128 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+/// 请求 Cookie 头中的一个键值对
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+}
+
+/// 响应 Set-Cookie 头解析出的完整记录
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    /// Expires 属性原文
+    pub expires: Option<String>,
+    /// Expires 解析后的 Unix 时间戳（秒），格式无法识别时为空
+    pub expires_at: Option<i64>,
+    pub max_age: Option<i64>,
+    pub http_only: bool,
+    pub secure: bool,
+    pub same_site: Option<String>,
+}
+
+/// 解析所有 Cookie 请求头
+pub fn parse_cookies(headers: &[(String, String)]) -> Vec<Cookie> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("cookie"))
+        .flat_map(|(_, value)| parse_cookie_header(value))
+        .collect()
+}
+
+/// 解析所有 Set-Cookie 响应头
+pub fn parse_set_cookies(headers: &[(String, String)]) -> Vec<SetCookie> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("set-cookie"))
+        .filter_map(|(_, value)| parse_set_cookie(value))
+        .collect()
+}
+
+/// 解析单个 Cookie 头，例如 "x_login_pk=abc; qbi_locale=zh-CN"
+pub fn parse_cookie_header(value: &str) -> Vec<Cookie> {
+    value
+        .split(';')
+        .filter_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            let name = name.trim();
+            (!name.is_empty()).then(|| Cookie {
+                name: name.to_string(),
+                value: unquote(value.trim()).to_string(),
+            })
+        })
+        .collect()
+}
+
+/// 解析单个 Set-Cookie 头
+pub fn parse_set_cookie(value: &str) -> Option<SetCookie> {
+    let mut parts = value.split(';');
+    let (name, cookie_value) = parts.next()?.trim().split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = SetCookie {
+        name: name.to_string(),
+        value: unquote(cookie_value.trim()).to_string(),
+        ..Default::default()
+    };
+
+    for attr in parts {
+        let (key, val) = attr.trim().split_once('=').unwrap_or((attr.trim(), ""));
+        let val = val.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "domain" => cookie.domain = Some(val.trim_start_matches('.').to_string()),
+            "path" => cookie.path = Some(val.to_string()),
+            "expires" => {
+                cookie.expires = Some(val.to_string());
+                cookie.expires_at = DateTime::parse_from_rfc2822(val)
+                    .or_else(|_| DateTime::parse_from_rfc2822(&val.replace('-', " ")))
+                    .map(|t| t.timestamp())
+                    .ok();
+            }
+            "max-age" => cookie.max_age = val.parse().ok(),
+            "httponly" => cookie.http_only = true,
+            "secure" => cookie.secure = true,
+            "samesite" => cookie.same_site = Some(val.to_string()),
+            _ => {}
+        }
+    }
+
+    Some(cookie)
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cookie_and_set_cookie() {
+        let cookies = parse_cookie_header("x_login_pk=abc; qbi_locale=zh-CN; flag");
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies[0], Cookie { name: "x_login_pk".to_string(), value: "abc".to_string() });
+
+        let cookie = parse_set_cookie(
+            "pdp_cqdrs_session=s1; Domain=.example.com; Path=/; Expires=Wed, 21-Oct-2026 07:28:00 GMT; Max-Age=1200; HttpOnly; Secure; SameSite=Lax",
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "pdp_cqdrs_session");
+        assert_eq!(cookie.domain.as_deref(), Some("example.com"));
+        assert_eq!(cookie.expires_at, Some(1_792_567_680));
+        assert_eq!(cookie.max_age, Some(1200));
+        assert!(cookie.http_only && cookie.secure);
+        assert_eq!(cookie.same_site.as_deref(), Some("Lax"));
+    }
+}
@@ -0,0 +1,238 @@
+use log::debug;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+/// 重组后 IP 载荷的最大字节数
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+/// 同时等待重组的最大数据报数量
+const MAX_PENDING_DATAGRAMS: usize = 1024;
+/// 所有待重组分片占用的最大字节数
+const MAX_PENDING_BYTES: usize = 16 * 1024 * 1024;
+/// 分片重组超时时间（微秒），与 Linux 默认的 ipfrag_time 一致
+const FRAGMENT_TIMEOUT_US: u64 = 30 * 1_000_000;
+
+/// 分片所属数据报的标识
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FragmentKey {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    /// 上层协议号
+    pub protocol: u8,
+    /// IPv4 为 16 位标识，IPv6 为分片头中的 32 位标识
+    pub identification: u32,
+}
+
+/// 单个分片
+#[derive(Debug, Clone, Copy)]
+pub struct Fragment<'a> {
+    /// 分片在原始载荷中的字节偏移
+    pub offset: usize,
+    pub more_fragments: bool,
+    pub data: &'a [u8],
+    pub timestamp_us: u64,
+}
+
+/// 正在重组的数据报
+#[derive(Debug)]
+struct PendingDatagram {
+    /// 偏移 -> 分片数据
+    fragments: BTreeMap<usize, Vec<u8>>,
+    /// 收到最后一个分片后才知道总长度
+    total_len: Option<usize>,
+    bytes: usize,
+    first_seen_us: u64,
+}
+
+impl PendingDatagram {
+    /// 分片是否已覆盖完整载荷
+    fn is_complete(&self) -> bool {
+        let Some(total_len) = self.total_len else {
+            return false;
+        };
+        let mut expected = 0;
+        for (offset, data) in &self.fragments {
+            if *offset != expected {
+                return false;
+            }
+            expected += data.len();
+        }
+        expected == total_len
+    }
+
+    fn assemble(self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(self.bytes);
+        for data in self.fragments.into_values() {
+            payload.extend_from_slice(&data);
+        }
+        payload
+    }
+}
+
+/// IPv4/IPv6 分片重组器
+///
+/// 重叠、超长、超时或因内存上限被淘汰的数据报都计为重组失败。
+#[derive(Debug, Default)]
+pub struct IpDefragmenter {
+    pending: HashMap<FragmentKey, PendingDatagram>,
+    bytes: usize,
+    fragments: u64,
+    reassembled: u64,
+    failures: u64,
+}
+
+impl IpDefragmenter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 收到的分片数量
+    pub fn fragments(&self) -> u64 {
+        self.fragments
+    }
+
+    /// 重组成功的数据报数量
+    pub fn reassembled(&self) -> u64 {
+        self.reassembled
+    }
+
+    /// 重组失败的数据报数量
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// 处理一个分片，数据报完整时返回重组后的上层载荷
+    pub fn process_fragment(&mut self, key: FragmentKey, fragment: Fragment) -> Option<Vec<u8>> {
+        self.fragments += 1;
+        self.expire(fragment.timestamp_us);
+
+        let end = fragment.offset + fragment.data.len();
+        if end > MAX_DATAGRAM_SIZE {
+            debug!("IP 分片超出最大长度: {} -> {}，偏移 {}", key.src, key.dst, fragment.offset);
+            self.fail(&key);
+            return None;
+        }
+
+        if !self.pending.contains_key(&key) {
+            if self.pending.len() >= MAX_PENDING_DATAGRAMS {
+                self.evict_oldest();
+            }
+            self.pending.insert(key, PendingDatagram {
+                fragments: BTreeMap::new(),
+                total_len: None,
+                bytes: 0,
+                first_seen_us: fragment.timestamp_us,
+            });
+        }
+        let datagram = self.pending.get_mut(&key)?;
+
+        // 重传的相同分片直接忽略，其他重叠视为异常（防止重叠分片攻击）
+        if let Some(existing) = datagram.fragments.get(&fragment.offset) {
+            if existing.as_slice() == fragment.data {
+                return None;
+            }
+        }
+        let overlaps = datagram.fragments.iter().any(|(offset, data)| {
+            *offset != fragment.offset && *offset < end && fragment.offset < offset + data.len()
+        });
+        let conflicting_end = !fragment.more_fragments && datagram.total_len.is_some_and(|len| len != end);
+        if overlaps || conflicting_end || datagram.fragments.contains_key(&fragment.offset) {
+            debug!("IP 分片重叠或长度冲突: {} -> {}，标识 {}", key.src, key.dst, key.identification);
+            self.fail(&key);
+            return None;
+        }
+
+        if !fragment.more_fragments {
+            datagram.total_len = Some(end);
+        }
+        datagram.fragments.insert(fragment.offset, fragment.data.to_vec());
+        datagram.bytes += fragment.data.len();
+        self.bytes += fragment.data.len();
+
+        if datagram.is_complete() {
+            let datagram = self.pending.remove(&key)?;
+            self.bytes -= datagram.bytes;
+            self.reassembled += 1;
+            return Some(datagram.assemble());
+        }
+
+        while self.bytes > MAX_PENDING_BYTES && !self.pending.is_empty() {
+            self.evict_oldest();
+        }
+        None
+    }
+
+    /// 丢弃数据报并计为失败
+    fn fail(&mut self, key: &FragmentKey) {
+        if let Some(datagram) = self.pending.remove(key) {
+            self.bytes -= datagram.bytes;
+        }
+        self.failures += 1;
+    }
+
+    fn expire(&mut self, now_us: u64) {
+        let expired: Vec<FragmentKey> = self
+            .pending
+            .iter()
+            .filter(|(_, d)| now_us.saturating_sub(d.first_seen_us) >= FRAGMENT_TIMEOUT_US)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            debug!("IP 分片重组超时: {} -> {}，标识 {}", key.src, key.dst, key.identification);
+            self.fail(&key);
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(key) = self
+            .pending
+            .iter()
+            .min_by_key(|(_, d)| d.first_seen_us)
+            .map(|(key, _)| *key)
+        {
+            debug!("IP 分片重组缓存达到上限，丢弃最旧的数据报: {} -> {}", key.src, key.dst);
+            self.fail(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> FragmentKey {
+        FragmentKey {
+            src: "10.0.0.1".parse().unwrap(),
+            dst: "10.0.0.2".parse().unwrap(),
+            protocol: 6,
+            identification: 7,
+        }
+    }
+
+    fn fragment(offset: usize, more_fragments: bool, data: &[u8], timestamp_us: u64) -> Fragment<'_> {
+        Fragment { offset, more_fragments, data, timestamp_us }
+    }
+
+    #[test]
+    fn test_out_of_order_fragments() {
+        let mut defrag = IpDefragmenter::new();
+        assert!(defrag.process_fragment(key(), fragment(16, false, b"tail", 0)).is_none());
+        let payload = defrag.process_fragment(key(), fragment(0, true, b"0123456789abcdef", 1)).unwrap();
+        assert_eq!(payload, b"0123456789abcdeftail");
+        assert_eq!(defrag.reassembled(), 1);
+        assert_eq!(defrag.fragments(), 2);
+    }
+
+    #[test]
+    fn test_timeout_and_overlap_count_as_failures() {
+        let mut defrag = IpDefragmenter::new();
+        defrag.process_fragment(key(), fragment(0, true, b"01234567", 0));
+        assert!(defrag.process_fragment(key(), fragment(8, false, b"x", FRAGMENT_TIMEOUT_US)).is_none());
+        assert_eq!(defrag.failures(), 1);
+
+        let mut defrag = IpDefragmenter::new();
+        defrag.process_fragment(key(), fragment(0, true, b"01234567", 0));
+        defrag.process_fragment(key(), fragment(4, false, b"overlap!", 1));
+        assert_eq!(defrag.failures(), 1);
+        assert!(defrag.pending.is_empty());
+    }
+}
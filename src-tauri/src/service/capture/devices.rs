@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// 设备列表轮询间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 设备监听线程是否已启动
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+// 网络设备结构
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkDevice {
+    pub name: String,
+    pub description: String,
+    pub is_loopback: bool,
+    pub is_up: bool,
+    pub is_running: bool,
+    pub is_wireless: bool,
+    pub connection_status: ConnectionStatus,
+    pub addresses: Vec<DeviceAddress>,
+    /// 默认路由所在的设备，推荐用于捕获
+    pub recommended: bool,
+}
+
+/// 设备上的一个地址
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceAddress {
+    pub addr: IpAddr,
+    pub family: AddressFamily,
+    pub netmask: Option<IpAddr>,
+    pub broadcast: Option<IpAddr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+/// 网卡连接状态（无线网卡是否已关联、有线网卡是否插线）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    Unknown,
+    Connected,
+    Disconnected,
+    /// 不适用，例如回环和虚拟设备
+    NotApplicable,
+}
+
+impl From<pcap::ConnectionStatus> for ConnectionStatus {
+    fn from(status: pcap::ConnectionStatus) -> Self {
+        match status {
+            pcap::ConnectionStatus::Connected => ConnectionStatus::Connected,
+            pcap::ConnectionStatus::Disconnected => ConnectionStatus::Disconnected,
+            pcap::ConnectionStatus::NotApplicable => ConnectionStatus::NotApplicable,
+            pcap::ConnectionStatus::Unknown => ConnectionStatus::Unknown,
+        }
+    }
+}
+
+/// 设备变化事件，每次轮询发现变化时发送一次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceChangeEvent {
+    pub added: Vec<NetworkDevice>,
+    pub removed: Vec<NetworkDevice>,
+    /// 标志、地址或推荐状态发生变化的设备
+    pub changed: Vec<NetworkDevice>,
+    /// 变化后的完整设备列表
+    pub devices: Vec<NetworkDevice>,
+}
+
+impl DeviceChangeEvent {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// 获取网络设备列表，并标记默认路由所在的设备
+pub fn list_devices() -> Result<Vec<NetworkDevice>> {
+    let list = pcap::Device::list().map_err(|e| anyhow!("获取网络设备列表失败: {}", e))?;
+    let route_addrs = default_route_addrs();
+
+    let devices = list
+        .into_iter()
+        .map(|device| {
+            let addresses: Vec<DeviceAddress> = device
+                .addresses
+                .iter()
+                .map(|addr| DeviceAddress {
+                    addr: addr.addr,
+                    family: if addr.addr.is_ipv4() { AddressFamily::Ipv4 } else { AddressFamily::Ipv6 },
+                    netmask: addr.netmask,
+                    broadcast: addr.broadcast_addr,
+                })
+                .collect();
+            NetworkDevice {
+                description: device.desc.unwrap_or_else(|| "无描述".to_string()),
+                is_loopback: device.flags.is_loopback(),
+                is_up: device.flags.is_up(),
+                is_running: device.flags.is_running(),
+                is_wireless: device.flags.is_wireless(),
+                connection_status: device.flags.connection_status.into(),
+                recommended: addresses.iter().any(|a| route_addrs.contains(&a.addr)),
+                addresses,
+                name: device.name,
+            }
+        })
+        .collect();
+
+    Ok(devices)
+}
+
+/// 默认路由使用的本机地址
+///
+/// 对公网地址执行 UDP connect（不会发出数据包），由系统路由表选择出口地址。
+fn default_route_addrs() -> Vec<IpAddr> {
+    [("0.0.0.0:0", "8.8.8.8:53"), ("[::]:0", "[2001:4860:4860::8888]:53")]
+        .iter()
+        .filter_map(|(bind, target)| {
+            let socket = UdpSocket::bind(bind).ok()?;
+            socket.connect(target).ok()?;
+            let addr = socket.local_addr().ok()?.ip();
+            (!addr.is_unspecified()).then_some(addr)
+        })
+        .collect()
+}
+
+/// 比较两次设备列表，返回变化
+fn diff_devices(old: &[NetworkDevice], new: Vec<NetworkDevice>) -> DeviceChangeEvent {
+    let find = |list: &[NetworkDevice], name: &str| list.iter().position(|d| d.name == name);
+
+    let added = new.iter().filter(|d| find(old, &d.name).is_none()).cloned().collect();
+    let removed = old.iter().filter(|d| find(&new, &d.name).is_none()).cloned().collect();
+    let changed = new
+        .iter()
+        .filter(|d| find(old, &d.name).is_some_and(|i| old[i] != **d))
+        .cloned()
+        .collect();
+
+    DeviceChangeEvent {
+        added,
+        removed,
+        changed,
+        devices: new,
+    }
+}
+
+/// 启动设备监听线程，重复调用时只启动一次
+///
+/// 定期轮询设备列表，设备出现、消失或地址变化（例如 VPN 连接）时推送事件。
+pub fn start_device_watch() {
+    if WATCHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    thread::spawn(|| {
+        info!("网络设备监听已启动");
+        let mut known = list_devices().unwrap_or_default();
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = match list_devices() {
+                Ok(devices) => devices,
+                Err(e) => {
+                    debug!("轮询网络设备失败: {}", e);
+                    continue;
+                }
+            };
+            let event = diff_devices(&known, current);
+            if event.is_empty() {
+                continue;
+            }
+            info!(
+                "网络设备变化: 新增 {} 个，移除 {} 个，变更 {} 个",
+                event.added.len(),
+                event.removed.len(),
+                event.changed.len()
+            );
+            known = event.devices.clone();
+            super::send_device_event(event);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(name: &str, addr: &str) -> NetworkDevice {
+        NetworkDevice {
+            name: name.to_string(),
+            description: String::new(),
+            is_loopback: false,
+            is_up: true,
+            is_running: true,
+            is_wireless: false,
+            connection_status: ConnectionStatus::Connected,
+            addresses: vec![DeviceAddress {
+                addr: addr.parse().unwrap(),
+                family: AddressFamily::Ipv4,
+                netmask: None,
+                broadcast: None,
+            }],
+            recommended: false,
+        }
+    }
+
+    #[test]
+    fn test_diff_devices() {
+        let old = vec![device("en0", "192.168.1.2"), device("utun3", "10.8.0.2")];
+        let new = vec![device("en0", "192.168.1.3"), device("utun4", "10.8.0.6")];
+        let event = diff_devices(&old, new);
+        assert_eq!(event.added[0].name, "utun4");
+        assert_eq!(event.removed[0].name, "utun3");
+        assert_eq!(event.changed[0].name, "en0");
+        assert_eq!(event.devices.len(), 2);
+
+        let same = diff_devices(&event.devices, event.devices.clone());
+        assert!(same.is_empty());
+    }
+}
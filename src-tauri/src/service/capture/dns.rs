@@ -0,0 +1,524 @@
+use super::reassembly::{FlowKey, StreamState, TcpSegment};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// DNS 服务端口
+pub const DNS_PORT: u16 = 53;
+/// 缓存的最大 IP 数量
+const MAX_CACHED_ADDRS: usize = 8192;
+/// 每个 IP 最多保留的主机名数量
+const MAX_NAMES_PER_ADDR: usize = 8;
+/// 记录过期后仍作为备选保留的时间（微秒），长连接常常比 TTL 活得久
+const STALE_RETENTION_US: u64 = 3600 * 1_000_000;
+/// 同时等待响应的最大查询数量
+const MAX_PENDING_QUERIES: usize = 1024;
+/// 查询等待响应的超时时间（微秒）
+const QUERY_TIMEOUT_US: u64 = 30 * 1_000_000;
+/// 域名压缩指针的最大跳转次数，防止恶意数据造成死循环
+const MAX_NAME_JUMPS: usize = 16;
+/// 同时跟踪的 DNS over TCP 单向流数量
+const MAX_TCP_STREAMS: usize = 256;
+/// DNS over TCP 流的空闲超时时间（微秒）
+const TCP_STREAM_IDLE_TIMEOUT_US: u64 = 30 * 1_000_000;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// DNS 应答中的一条记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsAnswer {
+    pub name: String,
+    pub record_type: String,
+    pub ttl: u32,
+    /// 地址或域名，其他类型为十六进制数据
+    pub data: String,
+}
+
+/// DNS 查询日志事件，每个响应发送一次
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsEvent {
+    pub id: u64,
+    /// 响应的捕获时间（微秒）
+    pub timestamp_us: u64,
+    pub device_name: String,
+    pub session_id: String,
+    pub client_ip: String,
+    pub client_port: u16,
+    pub server_ip: String,
+    /// "udp" 或 "tcp"
+    pub transport: String,
+    pub transaction_id: u16,
+    pub query_name: String,
+    pub query_type: String,
+    /// 响应码，例如 NOERROR、NXDOMAIN
+    pub response_code: String,
+    pub answers: Vec<DnsAnswer>,
+    /// 查询到响应的耗时（微秒），没有捕获到查询时为 None
+    pub latency_us: Option<u64>,
+}
+
+/// 解析后的 DNS 消息
+#[derive(Debug)]
+struct DnsMessage {
+    id: u16,
+    is_response: bool,
+    rcode: u8,
+    question: Option<(String, u16)>,
+    answers: Vec<(String, u16, u32, RecordData)>,
+}
+
+#[derive(Debug)]
+enum RecordData {
+    Addr(IpAddr),
+    Name(String),
+    Other(Vec<u8>),
+}
+
+impl RecordData {
+    fn to_display(&self) -> String {
+        match self {
+            RecordData::Addr(addr) => addr.to_string(),
+            RecordData::Name(name) => name.clone(),
+            RecordData::Other(data) => data.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// 读取域名（支持压缩指针），返回 (小写域名, 域名之后的位置)
+fn read_name(message: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = start;
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *message.get(pos)? as usize;
+        match len {
+            0 => {
+                end.get_or_insert(pos + 1);
+                break;
+            }
+            l if l & 0xc0 == 0xc0 => {
+                jumps += 1;
+                if jumps > MAX_NAME_JUMPS {
+                    return None;
+                }
+                let offset = (read_u16(message, pos)? & 0x3fff) as usize;
+                end.get_or_insert(pos + 2);
+                pos = offset;
+            }
+            l if l & 0xc0 == 0 => {
+                let label = message.get(pos + 1..pos + 1 + l)?;
+                labels.push(String::from_utf8_lossy(label).to_lowercase());
+                pos += 1 + l;
+            }
+            _ => return None,
+        }
+    }
+
+    Some((labels.join("."), end?))
+}
+
+/// 解析 DNS 消息（不含 TCP 的长度前缀）
+fn parse_message(message: &[u8]) -> Option<DnsMessage> {
+    let id = read_u16(message, 0)?;
+    let flags = read_u16(message, 2)?;
+    let qdcount = read_u16(message, 4)?;
+    let ancount = read_u16(message, 6)?;
+
+    let mut pos = 12;
+    let mut question = None;
+    for i in 0..qdcount {
+        let (name, next) = read_name(message, pos)?;
+        let qtype = read_u16(message, next)?;
+        pos = next + 4;
+        if i == 0 {
+            question = Some((name, qtype));
+        }
+    }
+
+    let mut answers = Vec::new();
+    for _ in 0..ancount {
+        let (name, next) = read_name(message, pos)?;
+        let rtype = read_u16(message, next)?;
+        let ttl = read_u32(message, next + 4)?;
+        let rdlength = read_u16(message, next + 8)? as usize;
+        let rdata_start = next + 10;
+        let rdata = message.get(rdata_start..rdata_start + rdlength)?;
+        let data = match (rtype, rdlength) {
+            (TYPE_A, 4) => RecordData::Addr(IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]))),
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = rdata.try_into().ok()?;
+                RecordData::Addr(IpAddr::V6(Ipv6Addr::from(octets)))
+            }
+            // CNAME、NS、PTR 的数据是域名
+            (5 | 2 | 12, _) => RecordData::Name(read_name(message, rdata_start)?.0),
+            _ => RecordData::Other(rdata.to_vec()),
+        };
+        answers.push((name, rtype, ttl, data));
+        pos = rdata_start + rdlength;
+    }
+
+    Some(DnsMessage {
+        id,
+        is_response: flags & 0x8000 != 0,
+        rcode: (flags & 0x000f) as u8,
+        question,
+        answers,
+    })
+}
+
+/// 取出缓冲区开头所有完整的、带 2 字节长度前缀的 DNS 消息，不完整的消息留在缓冲区中
+fn take_tcp_messages(buffer: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut pos = 0;
+    while let Some(len) = read_u16(buffer, pos) {
+        let end = pos + 2 + len as usize;
+        if buffer.len() < end {
+            break;
+        }
+        messages.push(buffer[pos + 2..end].to_vec());
+        pos = end;
+    }
+    buffer.drain(..pos);
+    messages
+}
+
+/// DNS over TCP 的单向流，长度前缀最大 65535，缓冲区不会超过一条消息的大小
+#[derive(Debug)]
+struct TcpDnsStream {
+    stream: StreamState,
+    last_seen_us: u64,
+}
+
+fn record_type_name(rtype: u16) -> String {
+    let name = match rtype {
+        1 => "A",
+        2 => "NS",
+        5 => "CNAME",
+        6 => "SOA",
+        12 => "PTR",
+        15 => "MX",
+        16 => "TXT",
+        28 => "AAAA",
+        33 => "SRV",
+        64 => "SVCB",
+        65 => "HTTPS",
+        other => return format!("TYPE{}", other),
+    };
+    name.to_string()
+}
+
+fn response_code_name(rcode: u8) -> String {
+    let name = match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        other => return format!("RCODE{}", other),
+    };
+    name.to_string()
+}
+
+/// 一次解析得到的主机名
+#[derive(Debug, Clone)]
+struct ResolvedName {
+    name: String,
+    /// 解析时间和 TTL 到期时间（捕获时间，微秒）
+    seen_us: u64,
+    expires_us: u64,
+}
+
+/// IP 到主机名的缓存
+///
+/// 使用捕获时间而不是系统时间判断有效期，离线分析时同样适用。
+#[derive(Debug, Default)]
+pub struct DnsCache {
+    entries: HashMap<IpAddr, Vec<ResolvedName>>,
+}
+
+impl DnsCache {
+    pub fn insert(&mut self, addr: IpAddr, name: &str, ttl_secs: u32, seen_us: u64) {
+        if !self.entries.contains_key(&addr) && self.entries.len() >= MAX_CACHED_ADDRS {
+            self.evict(seen_us);
+        }
+
+        let names = self.entries.entry(addr).or_default();
+        names.retain(|n| n.name != name);
+        if names.len() >= MAX_NAMES_PER_ADDR {
+            names.remove(0);
+        }
+        names.push(ResolvedName {
+            name: name.to_string(),
+            seen_us,
+            expires_us: seen_us.saturating_add(ttl_secs as u64 * 1_000_000),
+        });
+    }
+
+    /// 查找地址在指定时间对应的主机名
+    ///
+    /// 优先返回当时仍在 TTL 内的最近一次解析；都已过期时返回此前最近一次解析的名称。
+    pub fn lookup(&self, addr: &IpAddr, at_us: u64) -> Option<&str> {
+        let names = self.entries.get(addr)?;
+        let earlier = names.iter().filter(|n| n.seen_us <= at_us);
+        earlier
+            .clone()
+            .filter(|n| at_us <= n.expires_us)
+            .max_by_key(|n| n.seen_us)
+            .or_else(|| earlier.max_by_key(|n| n.seen_us))
+            .map(|n| n.name.as_str())
+    }
+
+    /// 清理过期较久的记录，仍然超过上限时移除最久未解析的地址
+    fn evict(&mut self, now_us: u64) {
+        for names in self.entries.values_mut() {
+            names.retain(|n| n.expires_us.saturating_add(STALE_RETENTION_US) > now_us);
+        }
+        self.entries.retain(|_, names| !names.is_empty());
+
+        if self.entries.len() >= MAX_CACHED_ADDRS {
+            if let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, names)| names.iter().map(|n| n.seen_us).max().unwrap_or(0))
+                .map(|(addr, _)| *addr)
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// DNS 查询跟踪：解析 DNS 消息、匹配查询与响应并更新地址缓存
+#[derive(Debug, Default)]
+pub struct DnsTracker {
+    cache: DnsCache,
+    /// 等待响应的查询：(客户端, 服务器, 事务ID) -> 查询时间
+    pending: HashMap<(SocketAddr, SocketAddr, u16), u64>,
+    /// 已解析的 DNS 消息数量
+    messages: u64,
+    /// DNS over TCP 各方向的流，大响应（例如区域传送）可能跨越多个段
+    tcp_streams: HashMap<FlowKey, TcpDnsStream>,
+}
+
+impl DnsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages(&self) -> u64 {
+        self.messages
+    }
+
+    /// 查找地址在指定时间对应的主机名
+    pub fn lookup(&self, addr: &IpAddr, at_us: u64) -> Option<String> {
+        self.cache.lookup(addr, at_us).map(str::to_string)
+    }
+
+    /// 处理 DNS over TCP 的一个段，返回因此而完整的 DNS 消息
+    pub fn process_tcp_segment(&mut self, key: FlowKey, segment: &TcpSegment) -> Vec<Vec<u8>> {
+        let now_us = segment.timestamp_us;
+        if !self.tcp_streams.contains_key(&key) {
+            if segment.payload.is_empty() && !segment.syn {
+                return Vec::new();
+            }
+            if self.tcp_streams.len() >= MAX_TCP_STREAMS {
+                self.evict_tcp_streams(now_us);
+            }
+        }
+
+        let entry = self.tcp_streams.entry(key).or_insert_with(|| TcpDnsStream {
+            stream: StreamState::new(now_us),
+            last_seen_us: now_us,
+        });
+        entry.last_seen_us = now_us;
+        entry.stream.accept(segment);
+        let messages = take_tcp_messages(&mut entry.stream.buffer);
+
+        if segment.fin || segment.rst {
+            self.tcp_streams.remove(&key);
+            if segment.rst {
+                self.tcp_streams.remove(&key.reversed());
+            }
+        }
+        messages
+    }
+
+    /// 流数量达到上限时先清理空闲的流，仍然超限则移除最久未活动的流
+    fn evict_tcp_streams(&mut self, now_us: u64) {
+        self.tcp_streams
+            .retain(|_, s| now_us.saturating_sub(s.last_seen_us) < TCP_STREAM_IDLE_TIMEOUT_US);
+        if self.tcp_streams.len() < MAX_TCP_STREAMS {
+            return;
+        }
+        if let Some(oldest) = self
+            .tcp_streams
+            .iter()
+            .min_by_key(|(_, s)| s.last_seen_us)
+            .map(|(k, _)| *k)
+        {
+            debug!("DNS over TCP 流数量达到上限，移除最旧的流: {} -> {}", oldest.src, oldest.dst);
+            self.tcp_streams.remove(&oldest);
+        }
+    }
+
+    /// 处理一条 DNS 消息，响应返回查询日志事件
+    pub fn process_message(
+        &mut self,
+        src: SocketAddr,
+        dst: SocketAddr,
+        transport: &str,
+        payload: &[u8],
+        timestamp_us: u64,
+    ) -> Option<DnsEvent> {
+        let Some(message) = parse_message(payload) else {
+            debug!("无法解析的 DNS 消息: {} -> {}，{} 字节", src, dst, payload.len());
+            return None;
+        };
+        self.messages += 1;
+
+        if !message.is_response {
+            self.pending
+                .retain(|_, ts| timestamp_us.saturating_sub(*ts) < QUERY_TIMEOUT_US);
+            if self.pending.len() < MAX_PENDING_QUERIES {
+                self.pending.insert((src, dst, message.id), timestamp_us);
+            }
+            return None;
+        }
+
+        let latency_us = self
+            .pending
+            .remove(&(dst, src, message.id))
+            .map(|query_us| timestamp_us.saturating_sub(query_us));
+        let (query_name, query_type) = message.question.unwrap_or_default();
+
+        // 地址记录映射到客户端查询的名称（即 Host 头中会出现的名称），而不是 CNAME 链末端的名称
+        for (name, _, ttl, data) in &message.answers {
+            if let RecordData::Addr(addr) = data {
+                let name = if query_name.is_empty() { name } else { &query_name };
+                self.cache.insert(*addr, name, *ttl, timestamp_us);
+            }
+        }
+
+        Some(DnsEvent {
+            id: super::next_packet_id(),
+            timestamp_us,
+            client_ip: dst.ip().to_string(),
+            client_port: dst.port(),
+            server_ip: src.ip().to_string(),
+            transport: transport.to_string(),
+            transaction_id: message.id,
+            query_type: record_type_name(query_type),
+            query_name,
+            response_code: response_code_name(message.rcode),
+            answers: message
+                .answers
+                .iter()
+                .map(|(name, rtype, ttl, data)| DnsAnswer {
+                    name: name.clone(),
+                    record_type: record_type_name(*rtype),
+                    ttl: *ttl,
+                    data: data.to_display(),
+                })
+                .collect(),
+            latency_us,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(labels: &[&str]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for label in labels {
+            out.push(label.len() as u8);
+            out.extend_from_slice(label.as_bytes());
+        }
+        out.push(0);
+        out
+    }
+
+    #[test]
+    fn test_dns_response_populates_cache() {
+        let client: SocketAddr = "10.0.0.2:53000".parse().unwrap();
+        let server: SocketAddr = "10.0.0.1:53".parse().unwrap();
+
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        query.extend(name(&["BI", "example", "com"]));
+        query.extend([0, 1, 0, 1]);
+
+        // 响应：bi.example.com CNAME edge.example.net，edge.example.net A 23.210.227.16
+        let mut response = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0];
+        response.extend(name(&["bi", "example", "com"]));
+        response.extend([0, 1, 0, 1]);
+        let cname = name(&["edge", "example", "net"]);
+        response.extend([0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60]);
+        response.extend((cname.len() as u16).to_be_bytes());
+        let cname_offset = response.len() as u8;
+        response.extend(&cname);
+        response.extend([0xc0, cname_offset, 0, 1, 0, 1, 0, 0, 0, 30, 0, 4, 23, 210, 227, 16]);
+
+        let mut tracker = DnsTracker::new();
+        assert!(tracker.process_message(client, server, "udp", &query, 1_000_000).is_none());
+        let event = tracker.process_message(server, client, "udp", &response, 1_020_000).unwrap();
+        assert_eq!(event.query_name, "bi.example.com");
+        assert_eq!(event.query_type, "A");
+        assert_eq!(event.latency_us, Some(20_000));
+        assert_eq!(event.answers[0].data, "edge.example.net");
+        assert_eq!(event.answers[1].data, "23.210.227.16");
+
+        let addr: IpAddr = "23.210.227.16".parse().unwrap();
+        assert_eq!(tracker.lookup(&addr, 500_000), None);
+        assert_eq!(tracker.lookup(&addr, 2_000_000).as_deref(), Some("bi.example.com"));
+        // TTL 过期后仍返回最近一次解析的名称
+        assert_eq!(tracker.lookup(&addr, 100_000_000).as_deref(), Some("bi.example.com"));
+    }
+
+    #[test]
+    fn test_tcp_messages_across_segments() {
+        let client: SocketAddr = "10.0.0.2:53000".parse().unwrap();
+        let server: SocketAddr = "10.0.0.1:53".parse().unwrap();
+        let key = FlowKey::new(server, client);
+        let first = [1u8; 300];
+        let second = [2u8; 40];
+        let mut data = (first.len() as u16).to_be_bytes().to_vec();
+        data.extend(first);
+        data.extend((second.len() as u16).to_be_bytes());
+        data.extend(second);
+
+        let segment = |seq: u32, payload| TcpSegment {
+            seq,
+            syn: false,
+            fin: false,
+            rst: false,
+            payload,
+            timestamp_us: 1_000_000,
+        };
+        let mut tracker = DnsTracker::new();
+        // 长度前缀和消息被拆到三个段中，第二个段乱序到达
+        assert!(tracker.process_tcp_segment(key, &segment(1000, &data[..1])).is_empty());
+        assert!(tracker.process_tcp_segment(key, &segment(1200, &data[200..])).is_empty());
+        let messages = tracker.process_tcp_segment(key, &segment(1001, &data[1..200]));
+        assert_eq!(messages, vec![first.to_vec(), second.to_vec()]);
+
+        let mut fin = segment(1000 + data.len() as u32, &[]);
+        fin.fin = true;
+        assert!(tracker.process_tcp_segment(key, &fin).is_empty());
+        assert!(tracker.tcp_streams.is_empty());
+    }
+}
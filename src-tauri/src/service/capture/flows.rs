@@ -0,0 +1,475 @@
+use super::reassembly::{FlowKey, TcpSegment};
+use super::HttpPacket;
+use anyhow::{anyhow, Result};
+use log::debug;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::RwLock;
+
+/// 同时跟踪的最大连接数量
+const MAX_TRACKED_FLOWS: usize = 4096;
+/// 连接空闲超时时间（微秒）
+const FLOW_IDLE_TIMEOUT_US: u64 = 300 * 1_000_000;
+/// 已关闭的连接在跟踪表中保留的时间（微秒），用于统计关闭后的重传
+const CLOSED_LINGER_US: u64 = 30 * 1_000_000;
+/// 连接更新的推送间隔（微秒，捕获时间）；状态变化时立即推送
+const PUBLISH_INTERVAL_US: u64 = 1_000_000;
+/// 每个连接最多保留的 HTTP 事务数量
+const MAX_TRANSACTIONS_PER_FLOW: usize = 64;
+/// 连接表最多保留的连接数量（包括已结束的连接）
+const MAX_STORED_FLOWS: usize = 10_000;
+/// 单次查询最多返回的连接数量
+const MAX_PAGE_SIZE: usize = 1000;
+
+static FLOW_STORE: Lazy<RwLock<HashMap<u64, FlowInfo>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// TCP 连接状态（从捕获到的报文推断）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TcpState {
+    /// 客户端已发送 SYN
+    SynSent,
+    /// 服务器已回复 SYN-ACK
+    SynReceived,
+    /// 握手完成，或捕获开始时连接已经建立
+    Established,
+    /// 一方已发送 FIN
+    Closing,
+    /// 双方都已发送 FIN
+    Closed,
+    Reset,
+}
+
+/// 单个方向的统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FlowDirectionStats {
+    /// TCP 载荷字节数
+    pub bytes: u64,
+    pub segments: u64,
+    pub retransmissions: u64,
+    /// 发送方通告零窗口的次数（接收缓冲区已满）
+    pub zero_window: u64,
+}
+
+/// 连接上的一个 HTTP 事务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowTransaction {
+    /// 事务ID，与请求数据包ID相同
+    pub id: u64,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub request_us: u64,
+    /// 尚未收到响应时为 None
+    pub status_code: Option<u16>,
+    pub round_trip_us: Option<u64>,
+}
+
+/// TCP 连接信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowInfo {
+    pub id: u64,
+    pub session_id: String,
+    pub device_name: String,
+    pub client_ip: String,
+    pub client_port: u16,
+    pub server_ip: String,
+    pub server_port: u16,
+    /// DNS 解析出的服务器主机名
+    pub server_name: Option<String>,
+    /// 首次和最近一次看到报文的捕获时间（微秒）
+    pub first_seen_us: u64,
+    pub last_seen_us: u64,
+    pub state: TcpState,
+    pub client_to_server: FlowDirectionStats,
+    pub server_to_client: FlowDirectionStats,
+    /// 连接上的 HTTP 事务总数，`transactions` 只保留最近的部分
+    pub transaction_count: u64,
+    pub transactions: Vec<FlowTransaction>,
+}
+
+/// 连接更新事件，按推送间隔批量发送
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowUpdateEvent {
+    pub session_id: String,
+    pub flows: Vec<FlowInfo>,
+}
+
+/// 连接查询条件，所有条件之间为“且”关系
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlowQuery {
+    pub session_id: Option<String>,
+    /// 客户端或服务器地址
+    pub ip: Option<String>,
+    /// 客户端或服务器端口
+    pub port: Option<u16>,
+    pub state: Option<TcpState>,
+    /// 只返回尚未关闭或重置的连接
+    pub active_only: bool,
+    pub offset: usize,
+    /// 为 0 时使用默认分页大小
+    pub limit: usize,
+}
+
+/// 分页查询结果，按最近活动时间倒序排列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowPage {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub flows: Vec<FlowInfo>,
+}
+
+/// 单个方向的序列号跟踪
+#[derive(Debug, Default)]
+struct DirectionTracker {
+    /// 已发送数据的最大序列号（不含），用于识别重传
+    max_seq_end: Option<u32>,
+    fin: bool,
+}
+
+impl DirectionTracker {
+    /// 记录一个带载荷的段，返回是否为重传
+    fn observe(&mut self, seq: u32, len: usize) -> bool {
+        let end = seq.wrapping_add(len as u32);
+        match self.max_seq_end {
+            Some(max) if (end.wrapping_sub(max) as i32) <= 0 => true,
+            _ => {
+                self.max_seq_end = Some(end);
+                false
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TrackedFlow {
+    info: FlowInfo,
+    client: DirectionTracker,
+    server: DirectionTracker,
+}
+
+/// TCP 连接跟踪器
+///
+/// 以客户端 -> 服务器方向为键，根据 SYN 判断方向；捕获开始时已经建立的连接
+/// 按端口号猜测，较小的端口视为服务器。
+#[derive(Debug)]
+pub struct FlowTracker {
+    session_id: String,
+    device_name: String,
+    flows: HashMap<FlowKey, TrackedFlow>,
+    /// 自上次推送以来有变化的连接
+    dirty: HashSet<FlowKey>,
+    /// 有状态变化，需要立即推送
+    urgent: bool,
+    last_publish_us: u64,
+}
+
+impl FlowTracker {
+    pub fn new(session_id: String, device_name: String) -> Self {
+        Self {
+            session_id,
+            device_name,
+            flows: HashMap::new(),
+            dirty: HashSet::new(),
+            urgent: false,
+            last_publish_us: 0,
+        }
+    }
+
+    /// 处理一个 TCP 段，`resolve` 在新建连接时查询服务器主机名
+    pub fn observe(
+        &mut self,
+        key: FlowKey,
+        segment: &TcpSegment,
+        ack: bool,
+        window: u16,
+        resolve: impl Fn(&IpAddr) -> Option<String>,
+    ) {
+        let now_us = segment.timestamp_us;
+        let (client_key, from_client) = if self.flows.contains_key(&key) {
+            (key, true)
+        } else if self.flows.contains_key(&key.reversed()) {
+            (key.reversed(), false)
+        } else {
+            if segment.rst {
+                return;
+            }
+            let from_client = match (segment.syn, ack) {
+                (true, false) => true,
+                (true, true) => false,
+                _ => key.dst.port() <= key.src.port(),
+            };
+            let client_key = if from_client { key } else { key.reversed() };
+            if self.flows.len() >= MAX_TRACKED_FLOWS {
+                self.evict(now_us);
+            }
+            let flow = self.new_flow(client_key, now_us, resolve(&client_key.dst.ip()));
+            self.flows.insert(client_key, flow);
+            (client_key, from_client)
+        };
+
+        let Some(flow) = self.flows.get_mut(&client_key) else {
+            return;
+        };
+        let info = &mut flow.info;
+        info.last_seen_us = now_us;
+
+        let (stats, tracker) = if from_client {
+            (&mut info.client_to_server, &mut flow.client)
+        } else {
+            (&mut info.server_to_client, &mut flow.server)
+        };
+        stats.segments += 1;
+        stats.bytes += segment.payload.len() as u64;
+        if window == 0 && !segment.syn && !segment.rst {
+            stats.zero_window += 1;
+        }
+        if !segment.payload.is_empty() && tracker.observe(segment.seq, segment.payload.len()) {
+            stats.retransmissions += 1;
+        }
+        if segment.fin {
+            tracker.fin = true;
+        }
+
+        let state = if segment.rst {
+            TcpState::Reset
+        } else if flow.client.fin && flow.server.fin {
+            TcpState::Closed
+        } else if segment.fin {
+            TcpState::Closing
+        } else {
+            match (info.state, segment.syn, ack) {
+                (_, true, false) => TcpState::SynSent,
+                (_, true, true) => TcpState::SynReceived,
+                (TcpState::SynSent | TcpState::SynReceived, false, _) => TcpState::Established,
+                (state, _, _) => state,
+            }
+        };
+        if state != info.state && info.state != TcpState::Reset {
+            info.state = state;
+            self.urgent = true;
+        }
+        self.dirty.insert(client_key);
+    }
+
+    /// 记录请求或响应所属的 HTTP 事务
+    pub fn on_http_packet(&mut self, key: FlowKey, packet: &HttpPacket) {
+        let key = if self.flows.contains_key(&key) { key } else { key.reversed() };
+        let Some(flow) = self.flows.get_mut(&key) else {
+            return;
+        };
+        let info = &mut flow.info;
+        self.dirty.insert(key);
+
+        if packet.packet_type == "request" {
+            info.transaction_count += 1;
+            if info.transactions.len() >= MAX_TRANSACTIONS_PER_FLOW {
+                info.transactions.remove(0);
+            }
+            info.transactions.push(FlowTransaction {
+                id: packet.id,
+                method: packet.method.clone(),
+                path: packet.path.clone(),
+                request_us: packet.timestamp_us,
+                status_code: None,
+                round_trip_us: None,
+            });
+        } else if let Some(paired_id) = packet.paired_id {
+            if let Some(transaction) = info.transactions.iter_mut().rev().find(|t| t.id == paired_id) {
+                transaction.status_code = packet.status_code;
+                transaction.round_trip_us = packet.round_trip_us;
+            }
+        }
+    }
+
+    /// 取出需要推送的连接更新：有状态变化或距上次推送超过推送间隔时返回
+    pub fn take_updates(&mut self, now_us: u64, force: bool) -> Vec<FlowInfo> {
+        let due = now_us.saturating_sub(self.last_publish_us) >= PUBLISH_INTERVAL_US;
+        if self.dirty.is_empty() || !(force || self.urgent || due) {
+            return Vec::new();
+        }
+        self.urgent = false;
+        self.last_publish_us = now_us;
+
+        let updates = self
+            .dirty
+            .drain()
+            .filter_map(|key| self.flows.get(&key).map(|f| f.info.clone()))
+            .collect();
+        self.expire(now_us);
+        updates
+    }
+
+    fn new_flow(&self, key: FlowKey, now_us: u64, server_name: Option<String>) -> TrackedFlow {
+        TrackedFlow {
+            info: FlowInfo {
+                id: super::next_packet_id(),
+                session_id: self.session_id.clone(),
+                device_name: self.device_name.clone(),
+                client_ip: key.src.ip().to_string(),
+                client_port: key.src.port(),
+                server_ip: key.dst.ip().to_string(),
+                server_port: key.dst.port(),
+                server_name,
+                first_seen_us: now_us,
+                last_seen_us: now_us,
+                state: TcpState::Established,
+                client_to_server: FlowDirectionStats::default(),
+                server_to_client: FlowDirectionStats::default(),
+                transaction_count: 0,
+                transactions: Vec::new(),
+            },
+            client: DirectionTracker::default(),
+            server: DirectionTracker::default(),
+        }
+    }
+
+    /// 移除空闲超时和已结束一段时间的连接（最后的状态已经推送过）
+    fn expire(&mut self, now_us: u64) {
+        let dirty = &self.dirty;
+        self.flows.retain(|key, flow| {
+            let idle = now_us.saturating_sub(flow.info.last_seen_us);
+            let finished = matches!(flow.info.state, TcpState::Closed | TcpState::Reset);
+            dirty.contains(key) || (idle < FLOW_IDLE_TIMEOUT_US && !(finished && idle >= CLOSED_LINGER_US))
+        });
+    }
+
+    /// 跟踪数量达到上限时移除最久未活动的连接
+    fn evict(&mut self, now_us: u64) {
+        self.expire(now_us);
+        if self.flows.len() < MAX_TRACKED_FLOWS {
+            return;
+        }
+        if let Some(oldest) = self
+            .flows
+            .iter()
+            .min_by_key(|(_, f)| f.info.last_seen_us)
+            .map(|(key, _)| *key)
+        {
+            debug!("TCP 连接跟踪数量达到上限，移除最旧的连接: {} -> {}", oldest.src, oldest.dst);
+            self.flows.remove(&oldest);
+            self.dirty.remove(&oldest);
+        }
+    }
+}
+
+/// 保存连接快照到全局连接表
+pub fn store_flows(flows: &[FlowInfo]) {
+    let Ok(mut store) = FLOW_STORE.write() else {
+        debug!("连接表锁已损坏，跳过保存");
+        return;
+    };
+    for flow in flows {
+        store.insert(flow.id, flow.clone());
+    }
+    if store.len() > MAX_STORED_FLOWS {
+        // 淘汰最久未活动的十分之一
+        let mut by_age: Vec<(u64, u64)> = store.values().map(|f| (f.last_seen_us, f.id)).collect();
+        by_age.sort_unstable();
+        for (_, id) in by_age.into_iter().take(MAX_STORED_FLOWS / 10) {
+            store.remove(&id);
+        }
+    }
+}
+
+/// 分页查询连接
+pub fn list_flows(query: &FlowQuery) -> Result<FlowPage> {
+    let store = FLOW_STORE.read().map_err(|_| anyhow!("连接表锁已损坏"))?;
+    let limit = match query.limit {
+        0 => 100,
+        n => n.min(MAX_PAGE_SIZE),
+    };
+
+    let mut matched: Vec<&FlowInfo> = store
+        .values()
+        .filter(|f| query.session_id.as_deref().is_none_or(|s| f.session_id == s))
+        .filter(|f| query.ip.as_deref().is_none_or(|ip| f.client_ip == ip || f.server_ip == ip))
+        .filter(|f| query.port.is_none_or(|port| f.client_port == port || f.server_port == port))
+        .filter(|f| query.state.is_none_or(|state| f.state == state))
+        .filter(|f| !query.active_only || !matches!(f.state, TcpState::Closed | TcpState::Reset))
+        .collect();
+    matched.sort_by_key(|f| std::cmp::Reverse(f.last_seen_us));
+
+    Ok(FlowPage {
+        total: matched.len(),
+        offset: query.offset,
+        limit,
+        flows: matched
+            .into_iter()
+            .skip(query.offset)
+            .take(limit)
+            .cloned()
+            .collect(),
+    })
+}
+
+pub fn get_flow(id: u64) -> Option<FlowInfo> {
+    FLOW_STORE.read().ok()?.get(&id).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(seq: u32, syn: bool, fin: bool, payload: &[u8], timestamp_us: u64) -> TcpSegment<'_> {
+        TcpSegment {
+            seq,
+            syn,
+            fin,
+            rst: false,
+            payload,
+            timestamp_us,
+        }
+    }
+
+    #[test]
+    fn test_flow_lifecycle() {
+        let client = FlowKey::new("10.0.0.2:50000".parse().unwrap(), "10.0.0.1:80".parse().unwrap());
+        let server = client.reversed();
+        let resolve = |_: &IpAddr| Some("bi.example.com".to_string());
+        let mut tracker = FlowTracker::new("en0".to_string(), "en0".to_string());
+
+        tracker.observe(client, &segment(100, true, false, b"", 0), false, 65535, resolve);
+        tracker.observe(server, &segment(900, true, false, b"", 10), true, 65535, resolve);
+        tracker.observe(client, &segment(101, false, false, b"", 20), true, 65535, resolve);
+        let updates = tracker.take_updates(20, false);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].state, TcpState::Established);
+        assert_eq!(updates[0].server_name.as_deref(), Some("bi.example.com"));
+
+        tracker.observe(client, &segment(101, false, false, b"GET / HTTP/1.1\r\n\r\n", 30), true, 65535, resolve);
+        tracker.observe(client, &segment(101, false, false, b"GET / HTTP/1.1\r\n\r\n", 40), true, 65535, resolve);
+        tracker.observe(server, &segment(901, false, false, b"", 50), true, 0, resolve);
+        let request = HttpPacket {
+            id: 7,
+            packet_type: "request".to_string(),
+            method: Some("GET".to_string()),
+            ..Default::default()
+        };
+        tracker.on_http_packet(client, &request);
+        // 没有状态变化且未到推送间隔
+        assert!(tracker.take_updates(50, false).is_empty());
+
+        tracker.observe(client, &segment(119, false, true, b"", 60), true, 65535, resolve);
+        let flow = tracker.take_updates(60, false).remove(0);
+        assert_eq!(flow.state, TcpState::Closing);
+        assert_eq!(flow.client_to_server.bytes, 36);
+        assert_eq!(flow.client_to_server.retransmissions, 1);
+        assert_eq!(flow.server_to_client.zero_window, 1);
+        assert_eq!(flow.transactions[0].id, 7);
+        assert_eq!(flow.transactions[0].status_code, None);
+
+        store_flows(std::slice::from_ref(&flow));
+        assert_eq!(get_flow(flow.id).map(|f| f.state), Some(TcpState::Closing));
+        let page = list_flows(&FlowQuery {
+            session_id: Some("en0".to_string()),
+            port: Some(80),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(page.flows.iter().any(|f| f.id == flow.id));
+    }
+}
@@ -0,0 +1,646 @@
+use super::HttpPacket;
+use crate::service::redact;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+/// HAR 文件根对象
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    #[serde(default)]
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    /// 整个事务耗时（毫秒）
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    #[serde(default)]
+    pub cache: serde_json::Value,
+    pub timings: HarTimings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_ip_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<HarCookie>,
+    #[serde(default)]
+    pub headers: Vec<HarNameValue>,
+    #[serde(default)]
+    pub query_string: Vec<HarNameValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: i64,
+    pub status_text: String,
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<HarCookie>,
+    #[serde(default)]
+    pub headers: Vec<HarNameValue>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL", default)]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarNameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarCookie {
+    pub name: String,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_only: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secure: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPostData {
+    pub mime_type: String,
+    #[serde(default)]
+    pub params: Vec<HarNameValue>,
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    pub size: i64,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+/// 各阶段耗时（毫秒），-1 表示不可用
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarTimings {
+    #[serde(default = "unavailable")]
+    pub blocked: f64,
+    #[serde(default = "unavailable")]
+    pub dns: f64,
+    #[serde(default = "unavailable")]
+    pub connect: f64,
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+    #[serde(default = "unavailable")]
+    pub ssl: f64,
+}
+
+fn unavailable() -> f64 {
+    -1.0
+}
+
+/// 把抓到的数据包导出为 HAR 1.1 文件，返回写入的条目数
+pub fn export_har(packets: &[HttpPacket], file_path: &str) -> Result<usize> {
+    let har = build_har(packets);
+    let count = har.log.entries.len();
+    let json = serde_json::to_string_pretty(&har)?;
+    std::fs::write(file_path, json).map_err(|e| anyhow!("写入HAR文件失败: {}", e))?;
+    info!("已导出 {} 条HAR记录到 {}", count, file_path);
+    Ok(count)
+}
+
+/// 从 HAR 文件导入数据包，每个条目还原为一个请求和一个响应
+pub fn import_har(file_path: &str) -> Result<Vec<HttpPacket>> {
+    let content = std::fs::read_to_string(file_path).map_err(|e| anyhow!("读取HAR文件失败: {}", e))?;
+    let har: Har = serde_json::from_str(&content).map_err(|e| anyhow!("HAR文件格式错误: {}", e))?;
+
+    // 导入的数据包以文件名作为会话，可以单独解锁敏感信息
+    let session_id = Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_path.to_string());
+
+    let mut packets = Vec::with_capacity(har.log.entries.len() * 2);
+    for (index, entry) in har.log.entries.iter().enumerate() {
+        match entry_to_packets(entry) {
+            Ok((request, response)) => {
+                packets.push(request);
+                packets.extend(response);
+            }
+            Err(e) => warn!("跳过无法解析的HAR条目 #{}: {}", index, e),
+        }
+    }
+
+    // 导入的数据包同样进入数据包存储（保留原始数据），详情窗口可以按ID查询
+    for packet in packets.iter_mut() {
+        packet.session_id = session_id.clone();
+        super::store::store_packet(packet.clone());
+        redact::redact_packet(packet);
+    }
+
+    info!("从 {} 导入了 {} 个数据包", file_path, packets.len());
+    Ok(packets)
+}
+
+/// 把数据包按请求/响应配对后构建 HAR 对象
+pub fn build_har(packets: &[HttpPacket]) -> Har {
+    let entries = pair_packets(packets)
+        .into_iter()
+        .map(|(request, response)| build_entry(request, response))
+        .collect();
+
+    Har {
+        log: HarLog {
+            version: "1.1".to_string(),
+            creator: HarCreator {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            entries,
+        },
+    }
+}
+
+/// 配对请求和响应：优先使用响应上的 paired_id，否则按同一连接上的顺序配对
+fn pair_packets(packets: &[HttpPacket]) -> Vec<(&HttpPacket, Option<&HttpPacket>)> {
+    let mut sorted: Vec<&HttpPacket> = packets.iter().collect();
+    sorted.sort_by_key(|p| (p.timestamp_us, p.timestamp, p.id));
+
+    let mut pairs: Vec<(&HttpPacket, Option<&HttpPacket>)> = Vec::new();
+    let mut index_by_id = HashMap::new();
+    let mut pending: HashMap<(String, u16, String, u16), VecDeque<usize>> = HashMap::new();
+
+    for packet in sorted {
+        if packet.packet_type == "request" {
+            let index = pairs.len();
+            pairs.push((packet, None));
+            index_by_id.insert(packet.id, index);
+            pending
+                .entry((packet.src_ip.clone(), packet.src_port, packet.dst_ip.clone(), packet.dst_port))
+                .or_default()
+                .push_back(index);
+            continue;
+        }
+
+        let key = (packet.dst_ip.clone(), packet.dst_port, packet.src_ip.clone(), packet.src_port);
+        let index = match packet.paired_id.and_then(|id| index_by_id.get(&id).copied()) {
+            Some(index) => {
+                if let Some(queue) = pending.get_mut(&key) {
+                    queue.retain(|i| *i != index);
+                }
+                Some(index)
+            }
+            None => pending.get_mut(&key).and_then(|queue| queue.pop_front()),
+        };
+
+        match index {
+            Some(index) if pairs[index].1.is_none() => pairs[index].1 = Some(packet),
+            _ => warn!("响应 {} 没有找到对应的请求，HAR中将忽略", packet.id),
+        }
+    }
+
+    pairs
+}
+
+fn build_entry(request: &HttpPacket, response: Option<&HttpPacket>) -> HarEntry {
+    let url = packet_url(request);
+    let started = DateTime::<Utc>::from_timestamp_micros(request.timestamp_us as i64)
+        .filter(|_| request.timestamp_us > 0)
+        .or_else(|| DateTime::<Utc>::from_timestamp(request.timestamp as i64, 0))
+        .unwrap_or_default();
+    let wait_ms = response
+        .and_then(|r| r.round_trip_us)
+        .map(|us| us as f64 / 1000.0)
+        .unwrap_or(0.0);
+
+    let query_string = url::Url::parse(&url)
+        .map(|u| {
+            u.query_pairs()
+                .map(|(name, value)| HarNameValue { name: name.into_owned(), value: value.into_owned() })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let post_data = (!request.body.is_empty()).then(|| {
+        let params = if request.content_type.starts_with("application/x-www-form-urlencoded") {
+            url::form_urlencoded::parse(request.body.as_bytes())
+                .map(|(name, value)| HarNameValue { name: name.into_owned(), value: value.into_owned() })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        HarPostData {
+            mime_type: request.content_type.clone(),
+            params,
+            text: request.body.clone(),
+        }
+    });
+
+    let har_request = HarRequest {
+        method: request.method.clone().unwrap_or_else(|| "GET".to_string()),
+        url,
+        http_version: request.version.clone(),
+        cookies: request_cookies(request),
+        headers: har_headers(request),
+        query_string,
+        post_data,
+        headers_size: -1,
+        body_size: request.body.len() as i64,
+    };
+
+    let har_response = match response {
+        Some(response) => HarResponse {
+            status: i64::from(response.status_code.unwrap_or(0)),
+            status_text: response.status_text.clone().unwrap_or_default(),
+            http_version: response.version.clone(),
+            cookies: response_cookies(response),
+            headers: har_headers(response),
+            content: response_content(response),
+            redirect_url: header_value(response, "location").unwrap_or_default(),
+            headers_size: -1,
+            body_size: response.body.len() as i64,
+        },
+        // 没有响应的请求按 HAR 约定使用 status 0
+        None => HarResponse {
+            status: 0,
+            status_text: String::new(),
+            http_version: request.version.clone(),
+            cookies: Vec::new(),
+            headers: Vec::new(),
+            content: HarContent { size: 0, mime_type: String::new(), text: None, encoding: None },
+            redirect_url: String::new(),
+            headers_size: -1,
+            body_size: -1,
+        },
+    };
+
+    HarEntry {
+        started_date_time: started.to_rfc3339_opts(SecondsFormat::Micros, true),
+        time: wait_ms,
+        request: har_request,
+        response: har_response,
+        cache: serde_json::json!({}),
+        timings: HarTimings {
+            blocked: -1.0,
+            dns: -1.0,
+            connect: -1.0,
+            send: 0.0,
+            wait: wait_ms,
+            receive: 0.0,
+            ssl: -1.0,
+        },
+        server_ip_address: Some(request.dst_ip.clone()).filter(|ip| !ip.is_empty()),
+        connection: (request.src_port != 0).then(|| request.src_port.to_string()),
+    }
+}
+
+fn entry_to_packets(entry: &HarEntry) -> Result<(HttpPacket, Option<HttpPacket>)> {
+    let url = url::Url::parse(&entry.request.url).map_err(|e| anyhow!("URL无效: {}", e))?;
+    let timestamp_us = DateTime::parse_from_rfc3339(&entry.started_date_time)
+        .map(|t| t.timestamp_micros().max(0) as u64)
+        .unwrap_or(0);
+    let timestamp = timestamp_us / 1_000_000;
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let dst_ip = entry
+        .server_ip_address
+        .clone()
+        .unwrap_or_else(|| url.host_str().unwrap_or_default().to_string());
+    let dst_port = url.port_or_known_default().unwrap_or(80);
+    let src_port = entry
+        .connection
+        .as_deref()
+        .and_then(|c| c.parse::<u16>().ok())
+        .unwrap_or(0);
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    let request_headers: Vec<(String, String)> = entry
+        .request
+        .headers
+        .iter()
+        .map(|h| (h.name.clone(), h.value.clone()))
+        .collect();
+    let request_body = entry
+        .request
+        .post_data
+        .as_ref()
+        .map(|p| p.text.clone())
+        .unwrap_or_default();
+
+    let request_raw_body = BASE64.encode(request_body.as_bytes());
+
+    let request_id = super::next_packet_id();
+    let request = HttpPacket {
+        id: request_id,
+        timestamp,
+        timestamp_us,
+        src_port,
+        dst_ip: dst_ip.clone(),
+        dst_port,
+        packet_type: "request".to_string(),
+        method: Some(entry.request.method.clone()),
+        path: Some(path.clone()),
+        version: entry.request.http_version.clone(),
+        host: host.clone(),
+        content_type: find_header(&request_headers, "content-type")
+            .or_else(|| entry.request.post_data.as_ref().map(|p| p.mime_type.clone()))
+            .unwrap_or_default(),
+        content_length: find_header(&request_headers, "content-length").and_then(|v| v.parse().ok()),
+        cookies: super::cookie::parse_cookies(&request_headers),
+        headers: request_headers,
+        body: request_body,
+        raw_body: request_raw_body,
+        ..Default::default()
+    };
+
+    // status 为 0 表示该请求没有收到响应
+    if entry.response.status <= 0 {
+        return Ok((request, None));
+    }
+    let status = u16::try_from(entry.response.status)
+        .map_err(|_| anyhow!("响应状态码无效: {}", entry.response.status))?;
+    let body = content_bytes(&entry.response.content)?;
+
+    let response_headers: Vec<(String, String)> = entry
+        .response
+        .headers
+        .iter()
+        .map(|h| (h.name.clone(), h.value.clone()))
+        .collect();
+    let wait_us = (entry.timings.wait >= 0.0).then_some((entry.timings.wait * 1000.0) as u64);
+    let response_timestamp_us = timestamp_us + wait_us.unwrap_or(0);
+    let response = HttpPacket {
+        id: super::next_packet_id(),
+        timestamp: response_timestamp_us / 1_000_000,
+        timestamp_us: response_timestamp_us,
+        src_ip: dst_ip,
+        src_port: dst_port,
+        dst_port: src_port,
+        packet_type: "response".to_string(),
+        path: Some(path),
+        status_code: Some(status),
+        status_text: Some(entry.response.status_text.clone()),
+        version: entry.response.http_version.clone(),
+        host,
+        content_type: entry.response.content.mime_type.clone(),
+        content_length: find_header(&response_headers, "content-length").and_then(|v| v.parse().ok()),
+        set_cookies: super::cookie::parse_set_cookies(&response_headers),
+        headers: response_headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        raw_body: BASE64.encode(&body),
+        paired_id: Some(request_id),
+        round_trip_us: wait_us,
+        ..Default::default()
+    };
+
+    Ok((request, Some(response)))
+}
+
+/// 响应内容：文本按原样写入，非 UTF-8 的消息体按 HAR 约定以 base64 写入
+fn response_content(response: &HttpPacket) -> HarContent {
+    // 导入的数据包可能只有消息体文本
+    let data = if response.raw_body.is_empty() {
+        response.body.as_bytes().to_vec()
+    } else {
+        super::body_view::decoded_body(response)
+    };
+    let size = data.len() as i64;
+    let (text, encoding) = match String::from_utf8(data) {
+        Ok(text) => (text, None),
+        Err(e) => (BASE64.encode(e.as_bytes()), Some("base64".to_string())),
+    };
+    HarContent {
+        size,
+        mime_type: response.content_type.clone(),
+        text: Some(text),
+        encoding,
+    }
+}
+
+/// 响应内容的原始字节，`encoding` 为 base64 时先解码
+fn content_bytes(content: &HarContent) -> Result<Vec<u8>> {
+    let text = content.text.as_deref().unwrap_or_default();
+    match content.encoding.as_deref() {
+        Some(encoding) if encoding.eq_ignore_ascii_case("base64") => {
+            BASE64.decode(text.trim()).map_err(|e| anyhow!("响应内容 base64 解码失败: {}", e))
+        }
+        _ => Ok(text.as_bytes().to_vec()),
+    }
+}
+
+/// 还原请求的完整URL
+fn packet_url(packet: &HttpPacket) -> String {
+    let path = packet.path.clone().unwrap_or_else(|| "/".to_string());
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return path;
+    }
+    let scheme = if packet.dst_port == 443 { "https" } else { "http" };
+    let host = if packet.host.is_empty() {
+        format!("{}:{}", packet.dst_ip, packet.dst_port)
+    } else {
+        packet.host.clone()
+    };
+    format!("{}://{}{}", scheme, host, path)
+}
+
+fn har_headers(packet: &HttpPacket) -> Vec<HarNameValue> {
+    packet
+        .headers
+        .iter()
+        .map(|(name, value)| HarNameValue { name: name.clone(), value: value.clone() })
+        .collect()
+}
+
+fn find_header(headers: &[(String, String)], name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.clone())
+}
+
+fn header_value(packet: &HttpPacket, name: &str) -> Option<String> {
+    find_header(&packet.headers, name)
+}
+
+fn request_cookies(packet: &HttpPacket) -> Vec<HarCookie> {
+    packet
+        .cookies
+        .iter()
+        .map(|c| HarCookie { name: c.name.clone(), value: c.value.clone(), ..Default::default() })
+        .collect()
+}
+
+fn response_cookies(packet: &HttpPacket) -> Vec<HarCookie> {
+    packet
+        .set_cookies
+        .iter()
+        .map(|c| HarCookie {
+            name: c.name.clone(),
+            value: c.value.clone(),
+            path: c.path.clone(),
+            domain: c.domain.clone(),
+            expires: c.expires.clone(),
+            http_only: c.http_only.then_some(true),
+            secure: c.secure.then_some(true),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> HttpPacket {
+        HttpPacket {
+            id: 1,
+            timestamp: 1_700_000_000,
+            src_ip: "10.0.0.1".to_string(),
+            src_port: 50000,
+            dst_ip: "23.210.227.16".to_string(),
+            dst_port: 80,
+            packet_type: "request".to_string(),
+            method: Some("POST".to_string()),
+            path: Some("/api/v2/biPlatform/query/byQueryParam?a=1".to_string()),
+            version: "HTTP/1.1".to_string(),
+            host: "23.210.227.16".to_string(),
+            content_type: "application/x-www-form-urlencoded".to_string(),
+            headers: vec![("Cookie".to_string(), "x_login_pk=abc; qbi_locale=zh-CN".to_string())],
+            cookies: crate::service::capture::cookie::parse_cookie_header("x_login_pk=abc; qbi_locale=zh-CN"),
+            body: "olapQueryParam=%7B%7D".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn response() -> HttpPacket {
+        HttpPacket {
+            id: 2,
+            timestamp: 1_700_000_000,
+            src_ip: "23.210.227.16".to_string(),
+            src_port: 80,
+            dst_ip: "10.0.0.1".to_string(),
+            dst_port: 50000,
+            packet_type: "response".to_string(),
+            status_code: Some(200),
+            status_text: Some("OK".to_string()),
+            version: "HTTP/1.1".to_string(),
+            content_type: "application/json".to_string(),
+            body: "{}".to_string(),
+            paired_id: Some(1),
+            round_trip_us: Some(12_000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_har_pairs_and_parses_fields() {
+        let har = build_har(&[request(), response()]);
+        assert_eq!(har.log.entries.len(), 1);
+
+        let entry = &har.log.entries[0];
+        assert_eq!(entry.time, 12.0);
+        assert_eq!(entry.request.query_string[0].name, "a");
+        assert_eq!(entry.request.cookies.len(), 2);
+        assert_eq!(entry.request.post_data.as_ref().unwrap().params[0].value, "{}");
+        assert_eq!(entry.response.status, 200);
+    }
+
+    #[test]
+    fn test_har_round_trip() {
+        let har = build_har(&[request(), response()]);
+        let json = serde_json::to_string(&har).unwrap();
+        let parsed: Har = serde_json::from_str(&json).unwrap();
+
+        let (req, resp) = entry_to_packets(&parsed.log.entries[0]).unwrap();
+        let resp = resp.unwrap();
+        assert_eq!(resp.paired_id, Some(req.id));
+        assert!(resp.id > req.id);
+        assert_eq!(req.method.as_deref(), Some("POST"));
+        assert_eq!(req.path.as_deref(), Some("/api/v2/biPlatform/query/byQueryParam?a=1"));
+        assert_eq!(resp.status_code, Some(200));
+    }
+
+    #[test]
+    fn test_binary_response_uses_base64() {
+        let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0xff, 0x00];
+        let binary = HttpPacket {
+            content_type: "image/png".to_string(),
+            body: String::from_utf8_lossy(&png).into_owned(),
+            raw_body: BASE64.encode(png),
+            ..response()
+        };
+        let har = build_har(&[request(), binary]);
+        let content = &har.log.entries[0].response.content;
+        assert_eq!(content.encoding.as_deref(), Some("base64"));
+        assert_eq!(content.text.as_deref(), Some(BASE64.encode(png).as_str()));
+        assert_eq!(content.size, png.len() as i64);
+
+        // 导入时还原出原始字节
+        let (_, resp) = entry_to_packets(&har.log.entries[0]).unwrap();
+        let resp = resp.unwrap();
+        assert_eq!(BASE64.decode(&resp.raw_body).unwrap(), png);
+
+        // 文本消息体不使用 base64
+        let text = build_har(&[request(), response()]);
+        assert_eq!(text.log.entries[0].response.content.encoding, None);
+        assert_eq!(text.log.entries[0].response.content.text.as_deref(), Some("{}"));
+    }
+
+    #[test]
+    fn test_import_rejects_invalid_status() {
+        let har = build_har(&[request(), response()]);
+        let json = serde_json::to_string(&har).unwrap().replace("\"status\":200", "\"status\":70000");
+        let parsed: Har = serde_json::from_str(&json).unwrap();
+        assert!(entry_to_packets(&parsed.log.entries[0]).is_err());
+    }
+}
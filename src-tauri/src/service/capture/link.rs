@@ -0,0 +1,163 @@
+use etherparse::err::packet::SliceError;
+use etherparse::{EtherType, SlicedPacket};
+use pcap::Linktype;
+use std::fmt;
+
+// 链路层类型编号（参见 https://www.tcpdump.org/linktypes.html）
+const LINKTYPE_NULL: i32 = 0;
+const LINKTYPE_ETHERNET: i32 = 1;
+/// OpenBSD 等平台上 DLT_RAW 的取值
+const LINKTYPE_RAW_BSD: i32 = 12;
+const LINKTYPE_RAW_BSD_ALT: i32 = 14;
+const LINKTYPE_RAW: i32 = 101;
+const LINKTYPE_LOOP: i32 = 108;
+const LINKTYPE_LINUX_SLL: i32 = 113;
+const LINKTYPE_IPV4: i32 = 228;
+const LINKTYPE_IPV6: i32 = 229;
+const LINKTYPE_LINUX_SLL2: i32 = 276;
+
+/// Linux cooked v2 头部长度
+const SLL2_HEADER_LEN: usize = 20;
+/// BSD loopback 头部长度（协议族）
+const NULL_HEADER_LEN: usize = 4;
+
+/// 链路层解析错误
+#[derive(Debug)]
+pub enum FrameError {
+    /// 帧长度不足以容纳链路层头部
+    Truncated,
+    /// 不支持的链路层类型
+    UnsupportedLinktype(i32),
+    /// 链路层承载了不支持的网络层协议
+    UnsupportedProtocol(u32),
+    Slice(SliceError),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Truncated => write!(f, "帧长度不足"),
+            FrameError::UnsupportedLinktype(linktype) => write!(f, "不支持的链路层类型: {}", linktype),
+            FrameError::UnsupportedProtocol(protocol) => write!(f, "不支持的网络层协议: 0x{:x}", protocol),
+            FrameError::Slice(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<SliceError> for FrameError {
+    fn from(e: SliceError) -> Self {
+        FrameError::Slice(e)
+    }
+}
+
+/// 是否支持该链路层类型
+pub fn is_supported_linktype(linktype: Linktype) -> bool {
+    matches!(
+        linktype.0,
+        LINKTYPE_NULL
+            | LINKTYPE_ETHERNET
+            | LINKTYPE_RAW_BSD
+            | LINKTYPE_RAW_BSD_ALT
+            | LINKTYPE_RAW
+            | LINKTYPE_LOOP
+            | LINKTYPE_LINUX_SLL
+            | LINKTYPE_IPV4
+            | LINKTYPE_IPV6
+            | LINKTYPE_LINUX_SLL2
+    )
+}
+
+/// 链路层类型的可读名称，用于日志和状态信息
+pub fn linktype_name(linktype: Linktype) -> String {
+    linktype
+        .get_name()
+        .unwrap_or_else(|_| format!("LINKTYPE_{}", linktype.0))
+}
+
+/// 按捕获句柄的链路层类型切分一帧数据
+///
+/// 以太网帧（包括 VLAN 标签）、Linux cooked (SLL/SLL2)、BSD loopback 和
+/// 原始 IPv4/IPv6 都会被解析到网络层和传输层。
+pub fn slice_frame(linktype: Linktype, data: &[u8]) -> Result<SlicedPacket<'_>, FrameError> {
+    match linktype.0 {
+        // from_ethernet 会自动跳过 VLAN 标签
+        LINKTYPE_ETHERNET => Ok(SlicedPacket::from_ethernet(data)?),
+        LINKTYPE_LINUX_SLL => Ok(SlicedPacket::from_linux_sll(data)?),
+        LINKTYPE_LINUX_SLL2 => {
+            if data.len() < SLL2_HEADER_LEN {
+                return Err(FrameError::Truncated);
+            }
+            // SLL2 头部的前两个字节是以太网类型
+            let ether_type = EtherType(u16::from_be_bytes([data[0], data[1]]));
+            Ok(SlicedPacket::from_ether_type(ether_type, &data[SLL2_HEADER_LEN..])?)
+        }
+        LINKTYPE_NULL | LINKTYPE_LOOP => {
+            if data.len() < NULL_HEADER_LEN {
+                return Err(FrameError::Truncated);
+            }
+            // NULL 的协议族按捕获主机的字节序存储，LOOP 固定为网络字节序
+            let header = [data[0], data[1], data[2], data[3]];
+            let family = if linktype.0 == LINKTYPE_LOOP {
+                u32::from_be_bytes(header)
+            } else {
+                let le = u32::from_le_bytes(header);
+                let be = u32::from_be_bytes(header);
+                // 离线文件可能来自不同字节序的主机，取看起来合理的值
+                if le <= 0xffff { le } else { be }
+            };
+            match family {
+                // AF_INET
+                2 => Ok(SlicedPacket::from_ip(&data[NULL_HEADER_LEN..])?),
+                // AF_INET6 在不同系统上的取值（BSD/Linux/macOS 等）
+                10 | 24 | 28 | 30 => Ok(SlicedPacket::from_ip(&data[NULL_HEADER_LEN..])?),
+                other => Err(FrameError::UnsupportedProtocol(other)),
+            }
+        }
+        LINKTYPE_RAW | LINKTYPE_RAW_BSD | LINKTYPE_RAW_BSD_ALT | LINKTYPE_IPV4 | LINKTYPE_IPV6 => {
+            Ok(SlicedPacket::from_ip(data)?)
+        }
+        other => Err(FrameError::UnsupportedLinktype(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use etherparse::{NetSlice, PacketBuilder, TransportSlice};
+
+    fn ipv4_tcp(payload: &[u8]) -> Vec<u8> {
+        let builder = PacketBuilder::ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64).tcp(50000, 80, 1, 65535);
+        let mut packet = Vec::with_capacity(builder.size(payload.len()));
+        builder.write(&mut packet, payload).unwrap();
+        packet
+    }
+
+    fn assert_tcp(sliced: SlicedPacket, payload: &[u8]) {
+        assert!(matches!(sliced.net, Some(NetSlice::Ipv4(_))));
+        match sliced.transport {
+            Some(TransportSlice::Tcp(tcp)) => assert_eq!(tcp.payload(), payload),
+            other => panic!("expected tcp, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_raw_null_and_sll2_frames() {
+        let payload = b"GET / HTTP/1.1\r\n\r\n";
+        let ip = ipv4_tcp(payload);
+
+        assert_tcp(slice_frame(Linktype(LINKTYPE_RAW), &ip).unwrap(), payload);
+
+        let mut null = 2u32.to_le_bytes().to_vec();
+        null.extend_from_slice(&ip);
+        assert_tcp(slice_frame(Linktype(LINKTYPE_NULL), &null).unwrap(), payload);
+
+        let mut sll2 = vec![0x08, 0x00, 0, 0, 0, 0, 0, 2, 0, 1, 0, 6, 1, 2, 3, 4, 5, 6, 0, 0];
+        sll2.extend_from_slice(&ip);
+        assert_tcp(slice_frame(Linktype(LINKTYPE_LINUX_SLL2), &sll2).unwrap(), payload);
+
+        assert!(matches!(
+            slice_frame(Linktype(147), &ip),
+            Err(FrameError::UnsupportedLinktype(147))
+        ));
+    }
+}
@@ -0,0 +1,1515 @@
+pub mod auth_queue;
+pub mod body;
+pub mod body_view;
+pub mod ca;
+pub mod cookie;
+pub mod defrag;
+pub mod devices;
+pub mod dns;
+pub mod flows;
+pub mod har;
+pub mod link;
+pub mod offline;
+pub mod prerequisites;
+pub mod proxy;
+pub mod reassembly;
+pub mod session;
+pub mod stats;
+pub mod stop;
+pub mod store;
+pub mod tls;
+pub mod tls_decrypt;
+pub mod transaction;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use etherparse::{Ipv6ExtensionSlice, NetSlice, SlicedPacket, TcpSlice, TransportSlice, UdpSlice};
+use log::{debug, error, info};
+use once_cell::sync::{Lazy, OnceCell};
+use pcap::Capture;
+use serde::{Deserialize, Serialize};
+use reassembly::{FlowKey, TcpReassembler, TcpSegment};
+use cookie::{Cookie, SetCookie};
+use defrag::{Fragment, FragmentKey, IpDefragmenter};
+use devices::DeviceChangeEvent;
+use dns::{DnsEvent, DnsTracker, DNS_PORT};
+use flows::{FlowTracker, FlowUpdateEvent};
+pub use devices::NetworkDevice;
+use session::CaptureSession;
+use stats::{CaptureStats, RateMeter};
+use stop::{CaptureSummary, StopConditions, StopReason, StopTracker};
+use tls::{TlsConnectionEvent, TlsTracker};
+use tls_decrypt::{KeyLog, TlsDecryptor};
+use transaction::{HttpTransaction, TransactionTracker};
+use crate::service::redact;
+use crate::service::subscription::{AllEvents, EventFilter, SubscriptionRegistry};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tauri::ipc::Channel;
+
+// 全局句柄与通道（各捕获会话共享）
+static APP_HANDLE: OnceCell<tauri::AppHandle> = OnceCell::new();
+// 状态和 HTTP 数据包可以被多个窗口同时订阅
+static STATUS_SUBSCRIBERS: Lazy<SubscriptionRegistry<CaptureStatus, StatusFilter>> =
+    Lazy::new(|| SubscriptionRegistry::new("捕获状态"));
+static HTTP_SUBSCRIBERS: Lazy<SubscriptionRegistry<HttpPacket, HttpPacketFilter>> =
+    Lazy::new(|| SubscriptionRegistry::new("HTTP 数据包"));
+// 其余事件同样按窗口订阅，主窗口和数据包窗口可以同时接收
+static TRANSACTION_SUBSCRIBERS: Lazy<SubscriptionRegistry<HttpTransaction, SessionFilter>> =
+    Lazy::new(|| SubscriptionRegistry::new("HTTP 事务"));
+static TLS_SUBSCRIBERS: Lazy<SubscriptionRegistry<TlsConnectionEvent, AllEvents>> =
+    Lazy::new(|| SubscriptionRegistry::new("TLS 连接事件"));
+static SUMMARY_SUBSCRIBERS: Lazy<SubscriptionRegistry<CaptureSummary, SessionFilter>> =
+    Lazy::new(|| SubscriptionRegistry::new("捕获摘要"));
+static DEVICE_SUBSCRIBERS: Lazy<SubscriptionRegistry<DeviceChangeEvent, AllEvents>> =
+    Lazy::new(|| SubscriptionRegistry::new("网络设备变化"));
+static DNS_SUBSCRIBERS: Lazy<SubscriptionRegistry<DnsEvent, SessionFilter>> =
+    Lazy::new(|| SubscriptionRegistry::new("DNS 查询日志"));
+static FLOW_SUBSCRIBERS: Lazy<SubscriptionRegistry<FlowUpdateEvent, SessionFilter>> =
+    Lazy::new(|| SubscriptionRegistry::new("TCP 连接更新"));
+
+// 进程内唯一、单调递增的数据包ID
+static NEXT_PACKET_ID: AtomicU64 = AtomicU64::new(1);
+
+// 捕获状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureStatus {
+    pub session_id: String,
+    pub running: bool,
+    pub message: String,
+    pub device_name: String,
+    pub start_time: u64,
+    pub options: CaptureOptions, // 当前（或最近一次）捕获使用的选项
+    pub filter: String, // 实际生效的 BPF 过滤表达式
+    pub stats: CaptureStats, // 运行统计，捕获期间每秒更新一次
+}
+
+/// 默认捕获的 HTTP 端口
+const DEFAULT_HTTP_PORTS: [u16; 3] = [80, 8080, 443];
+
+// 捕获选项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureOptions {
+    /// 自定义 BPF 过滤表达式，设置后替代默认的端口过滤
+    pub bpf_filter: Option<String>,
+    /// 在默认端口之外额外捕获的 TCP 端口
+    pub extra_ports: Vec<u16>,
+    /// 每个数据包的最大捕获长度
+    pub snaplen: i32,
+    /// 内核缓冲区大小（字节），None 使用 libpcap 默认值
+    pub buffer_size: Option<i32>,
+    /// 是否启用混杂模式
+    pub promisc: bool,
+    /// 是否启用立即模式（数据包到达即交付）
+    pub immediate_mode: bool,
+    /// 读取超时（毫秒）
+    pub timeout_ms: i32,
+    /// 自动停止条件
+    pub stop: StopConditions,
+    /// SSLKEYLOGFILE 密钥日志路径，设置后解密能找到密钥的 TLS 连接
+    pub keylog_file: Option<String>,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            bpf_filter: None,
+            extra_ports: Vec::new(),
+            snaplen: 65535,
+            buffer_size: None,
+            promisc: true,
+            immediate_mode: true,
+            timeout_ms: 1000,
+            stop: StopConditions::default(),
+            keylog_file: None,
+        }
+    }
+}
+
+impl CaptureOptions {
+    /// 生成实际使用的 BPF 过滤表达式
+    pub fn filter_expression(&self) -> String {
+        if let Some(filter) = self.bpf_filter.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+            return filter.to_string();
+        }
+
+        let mut ports: Vec<u16> = DEFAULT_HTTP_PORTS.to_vec();
+        for port in &self.extra_ports {
+            if !ports.contains(port) {
+                ports.push(*port);
+            }
+        }
+        // DNS 用于把服务器地址解析为主机名
+        ports
+            .iter()
+            .map(|port| format!("tcp port {port}"))
+            .chain([format!("udp port {DNS_PORT}"), format!("tcp port {DNS_PORT}")])
+            .collect::<Vec<_>>()
+            .join(" or ")
+    }
+
+    /// 在启动捕获前校验选项，包括编译 BPF 表达式
+    pub fn validate(&self) -> Result<String> {
+        if self.extra_ports.contains(&0) {
+            return Err(anyhow!("端口号不能为 0"));
+        }
+        if self.snaplen <= 0 {
+            return Err(anyhow!("snaplen 必须大于 0"));
+        }
+        if matches!(self.buffer_size, Some(size) if size <= 0) {
+            return Err(anyhow!("缓冲区大小必须大于 0"));
+        }
+        // 超时为 0 时部分平台上读取会一直阻塞，捕获线程无法检查停止条件
+        if self.timeout_ms <= 0 {
+            return Err(anyhow!("读取超时必须大于 0"));
+        }
+        self.stop.validate()?;
+        if let Some(path) = self.keylog_file.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+            if std::path::Path::new(path).is_dir() {
+                return Err(anyhow!("密钥日志路径是目录: {}", path));
+            }
+        }
+
+        let filter = self.filter_expression();
+        validate_bpf_filter(&filter)?;
+        Ok(filter)
+    }
+}
+
+// 校验 BPF 过滤表达式（不需要打开真实设备）
+pub fn validate_bpf_filter(filter: &str) -> Result<()> {
+    let dead = Capture::dead(pcap::Linktype::ETHERNET)
+        .map_err(|e| anyhow!("创建过滤器校验句柄失败: {}", e))?;
+    dead.compile(filter, true)
+        .map_err(|e| anyhow!("BPF 过滤表达式无效: {} ({})", filter, e))?;
+    Ok(())
+}
+
+// HTTP 数据包结构（统一处理请求和响应）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpPacket {
+    pub id: u64,
+    pub timestamp: u64, // 捕获时间（秒）
+    pub timestamp_us: u64, // 捕获时间（微秒，来自 pcap 头部）
+    pub device_name: String, // 捕获该数据包的网络设备（离线分析时为文件名）
+    pub session_id: String, // 所属捕获会话（离线分析和 HAR 导入时为文件名）
+    pub src_ip: String,
+    pub src_port: u16,
+    pub dst_ip: String,
+    pub dst_port: u16,
+    pub src_name: Option<String>, // 捕获到的 DNS 解析出的源地址主机名
+    pub dst_name: Option<String>, // 捕获到的 DNS 解析出的目标地址主机名
+    pub packet_type: String, // "request" 或 "response"
+    
+    // 请求字段
+    pub method: Option<String>,
+    pub path: Option<String>,
+    
+    // 响应字段
+    pub status_code: Option<u16>,
+    pub status_text: Option<String>,
+    
+    // 通用字段
+    pub version: String,
+    pub host: String,
+    pub content_type: String,
+    pub content_length: Option<usize>,
+    pub headers: Vec<(String, String)>,
+    pub body: String, // 解码后的消息体文本
+
+    // 消息体编码字段
+    pub raw_body: String, // 线路上的原始消息体（base64）
+    pub transfer_encoding: Option<String>,
+    pub content_encoding: Option<String>,
+    pub body_truncated: bool, // 解码结果不完整（超过大小上限或压缩流中途结束）
+    pub body_decode_error: Option<String>,
+
+    // Cookie 字段
+    pub cookies: Vec<Cookie>, // 请求 Cookie 头解析出的键值对
+    pub set_cookies: Vec<SetCookie>, // 响应 Set-Cookie 头解析出的记录
+    pub redacted: bool, // 发往前端前是否隐藏了敏感内容
+
+    // 配对字段（仅响应）
+    pub paired_id: Option<u64>, // 对应请求的ID
+    pub round_trip_us: Option<u64>, // 请求到响应的往返时间（微秒）
+}
+
+impl HttpPacket {
+    // 按名称查找请求 Cookie 的值
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.value.as_str())
+    }
+}
+
+/// HTTP 数据包订阅过滤条件，所有条件之间为“且”关系，未设置的条件不过滤
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpPacketFilter {
+    pub session_id: Option<String>,
+    /// Host 头或 DNS 解析出的主机名包含该字符串（不区分大小写）
+    pub host: Option<String>,
+    /// "request" 或 "response"
+    pub packet_type: Option<String>,
+}
+
+impl EventFilter<HttpPacket> for HttpPacketFilter {
+    fn matches(&self, packet: &HttpPacket) -> bool {
+        let host_matches = |host: &str| {
+            let host = host.to_lowercase();
+            [Some(&packet.host), packet.src_name.as_ref(), packet.dst_name.as_ref()]
+                .into_iter()
+                .flatten()
+                .any(|name| name.to_lowercase().contains(&host))
+        };
+        self.session_id.as_deref().is_none_or(|s| packet.session_id == s)
+            && self.host.as_deref().is_none_or(host_matches)
+            && self.packet_type.as_deref().is_none_or(|t| packet.packet_type == t)
+    }
+}
+
+/// 捕获状态订阅过滤条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusFilter {
+    /// 只接收该捕获会话的状态
+    pub session_id: Option<String>,
+}
+
+impl EventFilter<CaptureStatus> for StatusFilter {
+    fn matches(&self, status: &CaptureStatus) -> bool {
+        self.session_id.as_deref().is_none_or(|s| status.session_id == s)
+    }
+}
+
+/// 按捕获会话过滤的订阅条件，用于 HTTP 事务、DNS 查询日志、连接更新和会话摘要
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionFilter {
+    /// 只接收该捕获会话的事件
+    pub session_id: Option<String>,
+}
+
+impl SessionFilter {
+    fn matches_session(&self, session_id: &str) -> bool {
+        self.session_id.as_deref().is_none_or(|s| s == session_id)
+    }
+}
+
+impl EventFilter<HttpTransaction> for SessionFilter {
+    fn matches(&self, transaction: &HttpTransaction) -> bool {
+        self.matches_session(&transaction.request.session_id)
+    }
+}
+
+impl EventFilter<DnsEvent> for SessionFilter {
+    fn matches(&self, event: &DnsEvent) -> bool {
+        self.matches_session(&event.session_id)
+    }
+}
+
+impl EventFilter<FlowUpdateEvent> for SessionFilter {
+    fn matches(&self, event: &FlowUpdateEvent) -> bool {
+        self.matches_session(&event.session_id)
+    }
+}
+
+impl EventFilter<CaptureSummary> for SessionFilter {
+    fn matches(&self, summary: &CaptureSummary) -> bool {
+        self.matches_session(&summary.session_id)
+    }
+}
+
+// 初始化 AppHandle 以便发送事件
+pub fn init_app_handle(app_handle: tauri::AppHandle) -> Result<()> {
+    APP_HANDLE
+        .set(app_handle)
+        .map_err(|_| anyhow!("已经初始化过 AppHandle"))?;
+    Ok(())
+}
+
+// 订阅捕获状态，返回订阅者ID；owner 为订阅所在的窗口标签
+pub fn subscribe_status(channel: Channel<CaptureStatus>, filter: StatusFilter, owner: &str) -> Result<u64> {
+    STATUS_SUBSCRIBERS.subscribe(channel, filter, owner)
+}
+
+pub fn unsubscribe_status(subscriber_id: u64) -> bool {
+    STATUS_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+// 订阅 HTTP 数据包，返回订阅者ID；owner 为订阅所在的窗口标签
+pub fn subscribe_http(channel: Channel<HttpPacket>, filter: HttpPacketFilter, owner: &str) -> Result<u64> {
+    HTTP_SUBSCRIBERS.subscribe(channel, filter, owner)
+}
+
+pub fn unsubscribe_http(subscriber_id: u64) -> bool {
+    HTTP_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+// 窗口销毁时移除它的全部订阅
+pub fn remove_window_subscriptions(owner: &str) {
+    STATUS_SUBSCRIBERS.remove_owner(owner);
+    HTTP_SUBSCRIBERS.remove_owner(owner);
+    TRANSACTION_SUBSCRIBERS.remove_owner(owner);
+    TLS_SUBSCRIBERS.remove_owner(owner);
+    SUMMARY_SUBSCRIBERS.remove_owner(owner);
+    DEVICE_SUBSCRIBERS.remove_owner(owner);
+    DNS_SUBSCRIBERS.remove_owner(owner);
+    FLOW_SUBSCRIBERS.remove_owner(owner);
+}
+
+// 订阅 HTTP 事务（请求/响应配对结果），返回订阅者ID
+pub fn subscribe_transactions(channel: Channel<HttpTransaction>, filter: SessionFilter, owner: &str) -> Result<u64> {
+    TRANSACTION_SUBSCRIBERS.subscribe(channel, filter, owner)
+}
+
+pub fn unsubscribe_transactions(subscriber_id: u64) -> bool {
+    TRANSACTION_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+// 订阅 TLS 连接事件，返回订阅者ID
+pub fn subscribe_tls_events(channel: Channel<TlsConnectionEvent>, owner: &str) -> Result<u64> {
+    TLS_SUBSCRIBERS.subscribe(channel, AllEvents, owner)
+}
+
+pub fn unsubscribe_tls_events(subscriber_id: u64) -> bool {
+    TLS_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+// 订阅捕获会话结束摘要，返回订阅者ID
+pub fn subscribe_summaries(channel: Channel<CaptureSummary>, filter: SessionFilter, owner: &str) -> Result<u64> {
+    SUMMARY_SUBSCRIBERS.subscribe(channel, filter, owner)
+}
+
+pub fn unsubscribe_summaries(subscriber_id: u64) -> bool {
+    SUMMARY_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+// 订阅网络设备变化，返回订阅者ID；第一次订阅时启动设备监听
+pub fn subscribe_device_changes(channel: Channel<DeviceChangeEvent>, owner: &str) -> Result<u64> {
+    let id = DEVICE_SUBSCRIBERS.subscribe(channel, AllEvents, owner)?;
+    devices::start_device_watch();
+    Ok(id)
+}
+
+pub fn unsubscribe_device_changes(subscriber_id: u64) -> bool {
+    DEVICE_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+// 订阅 DNS 查询日志，返回订阅者ID
+pub fn subscribe_dns_events(channel: Channel<DnsEvent>, filter: SessionFilter, owner: &str) -> Result<u64> {
+    DNS_SUBSCRIBERS.subscribe(channel, filter, owner)
+}
+
+pub fn unsubscribe_dns_events(subscriber_id: u64) -> bool {
+    DNS_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+// 订阅 TCP 连接更新，返回订阅者ID
+pub fn subscribe_flow_updates(channel: Channel<FlowUpdateEvent>, filter: SessionFilter, owner: &str) -> Result<u64> {
+    FLOW_SUBSCRIBERS.subscribe(channel, filter, owner)
+}
+
+pub fn unsubscribe_flow_updates(subscriber_id: u64) -> bool {
+    FLOW_SUBSCRIBERS.unsubscribe(subscriber_id)
+}
+
+// 一次性初始化全局状态，只在应用启动时调用一次
+pub fn init_capture_system() -> Result<()> {
+    info!("初始化捕获系统...");
+    
+    // 初始化捕获会话表
+    session::init_sessions();
+
+    // 启动认证队列消费者
+    auth_queue::start_auth_consumer();
+        
+    info!("捕获系统初始化完成");
+    Ok(())
+}
+
+// 启动数据包捕获，返回会话ID
+//
+// 每个会话独占一个捕获线程，不同设备可以同时捕获。
+// 未指定会话ID时使用设备名称作为会话ID。
+pub fn start_capture_with_device(device_name: String, options: CaptureOptions, session_id: Option<String>) -> Result<String> {
+    info!("启动数据包捕获，设备: {device_name}");
+    
+    // 检查设备名称
+    if device_name.trim().is_empty() {
+        return Err(anyhow!("未指定网络设备名称"));
+    }
+
+    // 启动前校验捕获选项，避免线程启动后才发现过滤表达式错误
+    let filter = options.validate()?;
+    info!("捕获过滤表达式: {filter}");
+
+    let session_id = session_id
+        .filter(|id| !id.trim().is_empty())
+        .unwrap_or_else(|| device_name.clone());
+    
+    // 先设置运行标志再登记，同名会话仍在运行时登记失败
+    let session = Arc::new(CaptureSession::new(session_id.clone(), device_name, options, filter));
+    session.set_running(true);
+    session::register_session(session.clone())?;
+
+    // 更新状态
+    session.update_status(Some(true), Some("正在启动...".to_string()));
+
+    // 启动捕获线程
+    let session_clone = session.clone();
+    let capture_thread = thread::spawn(move || {
+        if let Err(e) = run_capture_loop(session_clone.clone()) {
+            error!("捕获会话 [{}] 出错: {}", session_clone.id(), e);
+            session_clone.set_running(false);
+            let reason = StopReason::Error { message: e.to_string() };
+            session_clone.update_status(Some(false), Some(reason.message()));
+            send_capture_summary(session_clone.summary(reason));
+        }
+    });
+
+    // 保存线程句柄
+    session.set_thread(capture_thread);
+    info!("捕获会话 [{}] 线程已启动", session_id);
+
+    Ok(session_id)
+}
+
+fn run_capture_loop(session: Arc<CaptureSession>) -> Result<()> {
+    info!("开始初始化数据包捕获...");
+
+    let CaptureStatus { device_name, options, filter, .. } = session.status();
+    
+    // 更新状态
+    session.update_status(None, Some("正在初始化网络捕获...".to_string()));
+
+    // 获取可用的网络设备列表
+    let list = match pcap::Device::list() {
+        Ok(list) => list,
+        Err(e) => {
+            let err = anyhow!("获取网络设备列表失败: {}", e);
+            session.update_status(Some(false), Some(err.to_string()));
+            return Err(err);
+        }
+    };
+    
+    if list.is_empty() {
+        let err = anyhow!("没有找到可用的网络设备");
+        session.update_status(Some(false), Some(err.to_string()));
+        return Err(err);
+    }
+    
+    // 根据指定的设备名称查找设备
+    let device = if !device_name.is_empty() {
+        // 查找指定名称的设备
+        match list.iter().find(|d| d.name == device_name) {
+            Some(device) => {
+                info!("找到指定的网络设备: {}", device_name);
+                device
+            },
+            None => {
+                let err = anyhow!("未找到指定的网络设备: {}", device_name);
+                session.update_status(Some(false), Some(err.to_string()));
+                return Err(err);
+            }
+        }
+    } else {
+        // 如果没有指定设备名称，直接报错
+        let err = anyhow!("未指定网络设备名称，请选择一个网络设备");
+        session.update_status(Some(false), Some(err.to_string()));
+        return Err(err);
+    };
+    
+    info!("使用网络设备: {}", device.name);
+    
+    let mut cap = match Capture::from_device(device.clone()) {
+        Ok(cap) => {
+            let mut cap = cap
+                .promisc(options.promisc)
+                .timeout(options.timeout_ms)
+                .immediate_mode(options.immediate_mode)
+                .snaplen(options.snaplen);
+            if let Some(buffer_size) = options.buffer_size {
+                cap = cap.buffer_size(buffer_size);
+            }
+            match cap.open() {
+                Ok(cap) => cap,
+                Err(e) => {
+                    let err = anyhow!("打开网络设备失败: {}. 请确保已安装ChmodBPF", e);
+                    session.update_status(Some(false), Some(err.to_string()));
+                    return Err(err);
+                }
+            }
+        }
+        Err(e) => {
+            let err = anyhow!("创建捕获句柄失败: {}. 请确保已安装ChmodBPF", e);
+            session.update_status(Some(false), Some(err.to_string()));
+            return Err(err);
+        }
+    };
+
+    // 设置过滤器，默认只捕获 HTTP 流量
+    if let Err(e) = cap.filter(&filter, true) {
+        let err = anyhow!("设置过滤器失败: {}", e);
+        session.update_status(Some(false), Some(err.to_string()));
+        return Err(err);
+    }
+    
+    // 按链路层类型切分数据帧（以太网、Linux cooked、loopback、原始 IP 等）
+    let linktype = cap.get_datalink();
+    if !link::is_supported_linktype(linktype) {
+        let err = anyhow!("不支持的链路层类型: {}", link::linktype_name(linktype));
+        session.update_status(Some(false), Some(err.to_string()));
+        return Err(err);
+    }
+    info!("链路层类型: {}", link::linktype_name(linktype));
+
+    // 更新状态为运行中
+    session.update_status(Some(true), Some("正在捕获 HTTP 请求和响应...".to_string()));
+    
+    info!("开始捕获 HTTP 请求和响应数据包...");
+
+    // 数据包处理流水线，生命周期与本次捕获一致
+    let mut pipeline = CapturePipeline::new(device_name.clone(), session.id().to_string(), true);
+    pipeline.stop = Some(StopTracker::new(options.stop.clone())?);
+    pipeline.set_keylog_file(options.keylog_file.as_deref());
+    let mut rate = RateMeter::new();
+    let mut reason = StopReason::Manual;
+    let mut last_timestamp_us = 0;
+
+    // 简化的捕获循环
+    while session.is_running() {
+        // 定期把运行统计推送到状态流
+        if rate.is_due() {
+            session.update_stats(pipeline.snapshot_stats(&mut cap, &mut rate));
+        }
+
+        // 检查自动停止条件
+        if let Some(stop_reason) = pipeline.check_stop(&session) {
+            info!("捕获会话 [{}] {}", session.id(), stop_reason.message());
+            session.set_running(false);
+            reason = stop_reason;
+            break;
+        }
+
+        match cap.next_packet() {
+            Ok(packet) => {
+                //debug!("捕获到数据包: {} 字节", packet.data.len());
+                let timestamp_us = header_timestamp_us(packet.header);
+                last_timestamp_us = timestamp_us;
+                pipeline.stats.frames += 1;
+                pipeline.stats.bytes += packet.header.len as u64;
+                match link::slice_frame(linktype, packet.data) {
+                    Ok(sliced) => pipeline.process_packet(sliced, timestamp_us),
+                    Err(e) => {
+                        pipeline.stats.parse_errors += 1;
+                        debug!("解析数据包错误: {}", e)
+                    }
+                }
+            },
+            Err(pcap::Error::TimeoutExpired) => continue, // 超时是正常的
+            Err(e) => {
+                error!("捕获数据包错误: {:?}", e);
+                if !session.is_running() {
+                    break;
+                }
+                thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+
+    // 推送剩余的连接更新
+    pipeline.publish_flows(last_timestamp_us, true);
+
+    // 更新状态为已停止，附带最终统计和停止原因
+    session.update_stats(pipeline.snapshot_stats(&mut cap, &mut rate));
+    session.update_status(Some(false), Some(reason.message()));
+    send_capture_summary(session.summary(reason));
+
+    info!("捕获会话 [{}] 已停止", session.id());
+    Ok(())
+}
+
+/// 单次捕获会话内的数据包处理流水线
+///
+/// 持有跨数据包的状态（TCP 流重组、请求/响应配对），由捕获线程独占。
+/// 实时捕获和离线文件分析共用同一条流水线。
+struct CapturePipeline {
+    /// 数据包来源设备，写入每个 HttpPacket
+    device_name: String,
+    /// 所属捕获会话，用于按会话解锁敏感信息
+    session_id: String,
+    /// IP 分片重组，在 TCP 流重组之前进行
+    defrag: IpDefragmenter,
+    reassembler: TcpReassembler,
+    transactions: TransactionTracker,
+    /// HTTPS 连接的明文握手信息
+    tls: TlsTracker,
+    /// 按密钥日志解密 TLS 连接，None 表示未启用
+    tls_decryptor: Option<TlsDecryptor>,
+    /// DNS 查询日志和 IP 到主机名的缓存
+    dns: DnsTracker,
+    /// TCP 连接跟踪表
+    flows: FlowTracker,
+    /// 是否把 HTTP 数据包交给认证系统处理
+    feed_auth: bool,
+    /// 运行统计（不含 pcap 内核计数和速率）
+    stats: CaptureStats,
+    /// 自动停止条件（仅实时捕获）
+    stop: Option<StopTracker>,
+}
+
+impl CapturePipeline {
+    fn new(device_name: String, session_id: String, feed_auth: bool) -> Self {
+        Self {
+            flows: FlowTracker::new(session_id.clone(), device_name.clone()),
+            device_name,
+            session_id,
+            defrag: IpDefragmenter::new(),
+            reassembler: TcpReassembler::new(),
+            transactions: TransactionTracker::new(),
+            tls: TlsTracker::new(),
+            tls_decryptor: None,
+            dns: DnsTracker::new(),
+            feed_auth,
+            stats: CaptureStats::default(),
+            stop: None,
+        }
+    }
+
+    /// 启用 TLS 解密，路径为空时不启用
+    fn set_keylog_file(&mut self, path: Option<&str>) {
+        self.tls_decryptor = path.map(str::trim).filter(|p| !p.is_empty()).map(|path| {
+            info!("使用密钥日志解密 TLS 流量: {}", path);
+            TlsDecryptor::new(KeyLog::new(path))
+        });
+    }
+
+    /// 检查自动停止条件是否满足
+    fn check_stop(&self, session: &CaptureSession) -> Option<StopReason> {
+        let stop = self.stop.as_ref()?;
+        stop.check(&self.stats, |system_id| session.has_token(system_id))
+    }
+
+    /// 汇总流水线自身的统计（不含 pcap 内核计数和速率）
+    fn current_stats(&self) -> CaptureStats {
+        let mut stats = self.stats.clone();
+        stats.tls_connections = self.tls.connections();
+        stats.tls_decrypted = self.tls_decryptor.as_ref().map_or(0, |d| d.decrypted_connections());
+        stats.dns_messages = self.dns.messages();
+        stats.ip_fragments = self.defrag.fragments();
+        stats.ip_reassembled = self.defrag.reassembled();
+        stats.ip_reassembly_failures = self.defrag.failures();
+        stats
+    }
+
+    /// 汇总当前统计，附带 pcap 内核计数并更新速率
+    fn snapshot_stats(&mut self, cap: &mut Capture<pcap::Active>, rate: &mut RateMeter) -> CaptureStats {
+        let mut stats = self.current_stats();
+        match cap.stats() {
+            Ok(pcap_stats) => {
+                stats.pcap_received = pcap_stats.received as u64;
+                stats.pcap_dropped = pcap_stats.dropped as u64;
+                stats.pcap_if_dropped = pcap_stats.if_dropped as u64;
+            }
+            Err(e) => debug!("获取 pcap 统计失败: {}", e),
+        }
+        rate.update(&mut stats);
+        stats
+    }
+
+    fn process_packet(&mut self, sliced: SlicedPacket, timestamp_us: u64) {
+        // 提取 IP 地址信息
+        let (src_ip, dst_ip) = match &sliced.net {
+            Some(NetSlice::Ipv4(ipv4)) => (
+                IpAddr::V4(ipv4.header().source_addr()),
+                IpAddr::V4(ipv4.header().destination_addr()),
+            ),
+            Some(NetSlice::Ipv6(ipv6)) => (
+                IpAddr::V6(ipv6.header().source_addr()),
+                IpAddr::V6(ipv6.header().destination_addr()),
+            ),
+            _ => return,
+        };
+
+        // 分片的 IP 数据报先重组，完整后再解析传输层
+        if let Some((key, fragment)) = sliced.net.as_ref().and_then(|net| ip_fragment(net, timestamp_us)) {
+            if let Some(payload) = self.defrag.process_fragment(key, fragment) {
+                let transport = match key.protocol {
+                    6 => TcpSlice::from_slice(&payload).ok().map(TransportSlice::Tcp),
+                    17 => UdpSlice::from_slice(&payload).ok().map(TransportSlice::Udp),
+                    _ => None,
+                };
+                if let Some(transport) = transport {
+                    self.process_transport(src_ip, dst_ip, transport, timestamp_us);
+                }
+            }
+            return;
+        }
+
+        if let Some(transport) = sliced.transport {
+            self.process_transport(src_ip, dst_ip, transport, timestamp_us);
+        }
+    }
+
+    // 按传输层协议分发：TCP 经过流重组，UDP 按单个数据报处理，53 端口按 DNS 处理
+    fn process_transport(&mut self, src_ip: IpAddr, dst_ip: IpAddr, transport: TransportSlice, timestamp_us: u64) {
+        match transport {
+            TransportSlice::Tcp(tcp) => {
+                // TCP 数据先经过流重组，得到完整的 HTTP 消息后再解析
+                let key = FlowKey::new(
+                    SocketAddr::new(src_ip, tcp.source_port()),
+                    SocketAddr::new(dst_ip, tcp.destination_port()),
+                );
+                let segment = TcpSegment {
+                    seq: tcp.sequence_number(),
+                    syn: tcp.syn(),
+                    fin: tcp.fin(),
+                    rst: tcp.rst(),
+                    payload: tcp.payload(),
+                    timestamp_us,
+                };
+                // 连接跟踪在流重组之前进行，包含 DNS over TCP 等非 HTTP 连接
+                self.flows
+                    .observe(key, &segment, tcp.ack(), tcp.window_size(), |ip| self.dns.lookup(ip, timestamp_us));
+                self.publish_flows(timestamp_us, false);
+                if key.src.port() == DNS_PORT || key.dst.port() == DNS_PORT {
+                    for message in self.dns.process_tcp_segment(key, &segment) {
+                        self.process_dns_message(key, "tcp", &message, timestamp_us);
+                    }
+                    return;
+                }
+                for mut event in self.tls.process_segment(key, &segment) {
+                    event.device_name = self.device_name.clone();
+                    debug!("TLS 连接 [{}] {}:{} -> {}:{} SNI: {:?}", event.stage, event.client_ip, event.client_port, event.server_ip, event.server_port, event.sni);
+                    send_tls_event(event);
+                }
+                if let Some(decryptor) = self.tls_decryptor.as_mut() {
+                    for message in decryptor.process_segment(key, &segment) {
+                        self.process_http_message(message.key, &message.data, message.timestamp_us);
+                    }
+                }
+                for message in self.reassembler.process_segment(key, segment) {
+                    if message.truncated {
+                        debug!("HTTP 消息不完整: {} -> {}，{} 字节", message.key.src, message.key.dst, message.data.len());
+                    }
+                    self.process_http_message(message.key, &message.data, message.timestamp_us);
+                }
+            }
+            TransportSlice::Udp(udp) => {
+                let key = FlowKey::new(
+                    SocketAddr::new(src_ip, udp.source_port()),
+                    SocketAddr::new(dst_ip, udp.destination_port()),
+                );
+                if key.src.port() == DNS_PORT || key.dst.port() == DNS_PORT {
+                    self.process_dns_message(key, "udp", udp.payload(), timestamp_us);
+                    return;
+                }
+                self.process_http_message(key, udp.payload(), timestamp_us);
+            }
+            _ => {}
+        }
+    }
+
+    // 输入结束时（抓包文件读完）输出仍缓冲在流中的消息，如消息体持续到连接关闭的响应
+    fn flush(&mut self) {
+        let mut messages = self.tls_decryptor.as_mut().map(TlsDecryptor::flush).unwrap_or_default();
+        messages.extend(self.reassembler.flush());
+        for message in messages {
+            if message.truncated {
+                debug!("HTTP 消息不完整: {} -> {}，{} 字节", message.key.src, message.key.dst, message.data.len());
+            }
+            self.process_http_message(message.key, &message.data, message.timestamp_us);
+        }
+    }
+
+    // 解析一条 DNS 消息，更新地址缓存并把响应发送到 DNS 查询日志
+    fn process_dns_message(&mut self, key: FlowKey, transport: &str, payload: &[u8], timestamp_us: u64) {
+        if let Some(mut event) = self.dns.process_message(key.src, key.dst, transport, payload, timestamp_us) {
+            event.device_name = self.device_name.clone();
+            event.session_id = self.session_id.clone();
+            debug!("DNS 响应 {} {} -> {:?}", event.query_type, event.query_name, event.answers.iter().map(|a| &a.data).collect::<Vec<_>>());
+            send_dns_event(event);
+        }
+    }
+
+    // 推送有变化的连接并保存到连接表，force 用于捕获结束时推送剩余的更新
+    fn publish_flows(&mut self, now_us: u64, force: bool) {
+        let flows = self.flows.take_updates(now_us, force);
+        if flows.is_empty() {
+            return;
+        }
+        flows::store_flows(&flows);
+        send_flow_update(FlowUpdateEvent {
+            session_id: self.session_id.clone(),
+            flows,
+        });
+    }
+
+    // 解析一条完整的 HTTP 消息并分发到认证系统和前端
+    fn process_http_message(&mut self, key: FlowKey, payload: &[u8], timestamp_us: u64) {
+        let (src_ip, src_port) = (key.src.ip(), key.src.port());
+        let (dst_ip, dst_port) = (key.dst.ip(), key.dst.port());
+
+        // 只处理有效载荷
+        if payload.is_empty() {
+            return;
+        }
+
+        // 检查是否是 HTTP 数据包（请求或响应）
+        let Some(packet_type) = detect_http_packet_type(payload) else {
+            return;
+        };
+
+        // 根据类型解析 HTTP 数据包
+        let http_packet = match packet_type.as_str() {
+            "request" => parse_http_request(payload),
+            "response" => parse_http_response(payload),
+            _ => None,
+        };
+        let Some(mut packet) = http_packet else {
+            return;
+        };
+
+        // 添加网络信息
+        packet.timestamp = timestamp_us / 1_000_000;
+        packet.timestamp_us = timestamp_us;
+        packet.device_name = self.device_name.clone();
+        packet.session_id = self.session_id.clone();
+        packet.src_ip = src_ip.to_string();
+        packet.src_port = src_port;
+        packet.dst_ip = dst_ip.to_string();
+        packet.dst_port = dst_port;
+        packet.src_name = self.dns.lookup(&src_ip, timestamp_us);
+        packet.dst_name = self.dns.lookup(&dst_ip, timestamp_us);
+        packet.packet_type = packet_type.clone();
+
+        // 生成唯一ID
+        packet.id = next_packet_id();
+        if packet_type == "request" {
+            self.stats.http_requests += 1;
+        } else {
+            self.stats.http_responses += 1;
+        }
+        if let Some(stop) = self.stop.as_mut() {
+            stop.observe(&packet);
+        }
+
+        // 请求进入配对队列，响应与队首请求配对
+        let mut transactions = Vec::new();
+        match packet_type.as_str() {
+            "request" => {
+                transactions.extend(self.transactions.on_request(key, &packet, timestamp_us));
+            }
+            "response" => {
+                transactions.extend(self.transactions.on_response(key, &mut packet, timestamp_us));
+            }
+            _ => {}
+        }
+        self.flows.on_http_packet(key, &packet);
+
+        // 输出格式化的 HTTP 信息到日志
+        match packet_type.as_str() {
+            "request" => {
+                info!("捕获 HTTP 请求: {}:{} -> {}:{}", src_ip, src_port, dst_ip, dst_port);
+                if let Some(method) = &packet.method {
+                    info!("请求方法: {}", method);
+                }
+                if let Some(path) = &packet.path {
+                    info!("请求路径: {}", path);
+                }
+            }
+            "response" => {
+                info!("捕获 HTTP 响应: {}:{} -> {}:{}", src_ip, src_port, dst_ip, dst_port);
+                if let Some(status_code) = packet.status_code {
+                    info!("响应状态码: {}", status_code);
+                }
+                if let Some(status_text) = &packet.status_text {
+                    info!("响应状态: {}", status_text);
+                }
+                if let Some(round_trip_us) = packet.round_trip_us {
+                    info!("往返时间: {:.1} ms", round_trip_us as f64 / 1000.0);
+                }
+            }
+            _ => {}
+        }
+
+        // 交给认证队列，由应用运行时上的消费者按顺序处理
+        if self.feed_auth {
+            auth_queue::submit_packet(packet.clone());
+        }
+
+        // 保存到数据包存储，并发送到前端
+        store::store_packet(packet.clone());
+        send_http_packet(packet);
+
+        for transaction in transactions {
+            send_http_transaction(transaction);
+        }
+    }
+}
+
+// 提取 IP 分片信息，未分片的数据报返回 None
+fn ip_fragment<'a>(net: &NetSlice<'a>, timestamp_us: u64) -> Option<(FragmentKey, Fragment<'a>)> {
+    match net {
+        NetSlice::Ipv4(ipv4) if ipv4.payload().fragmented => {
+            let header = ipv4.header();
+            Some((
+                FragmentKey {
+                    src: IpAddr::V4(header.source_addr()),
+                    dst: IpAddr::V4(header.destination_addr()),
+                    protocol: ipv4.payload().ip_number.0,
+                    identification: header.identification() as u32,
+                },
+                Fragment {
+                    offset: header.fragments_offset().value() as usize * 8,
+                    more_fragments: header.more_fragments(),
+                    data: ipv4.payload().payload,
+                    timestamp_us,
+                },
+            ))
+        }
+        NetSlice::Ipv6(ipv6) if ipv6.payload().fragmented => {
+            let fragment_header = ipv6.extensions().clone().into_iter().find_map(|ext| match ext {
+                Ipv6ExtensionSlice::Fragment(fragment) => Some(fragment),
+                _ => None,
+            })?;
+            let header = ipv6.header();
+            Some((
+                FragmentKey {
+                    src: IpAddr::V6(header.source_addr()),
+                    dst: IpAddr::V6(header.destination_addr()),
+                    protocol: ipv6.payload().ip_number.0,
+                    identification: fragment_header.identification(),
+                },
+                Fragment {
+                    offset: fragment_header.fragment_offset().value() as usize * 8,
+                    more_fragments: fragment_header.more_fragments(),
+                    data: ipv6.payload().payload,
+                    timestamp_us,
+                },
+            ))
+        }
+        _ => None,
+    }
+}
+
+// 分配下一个数据包ID
+pub fn next_packet_id() -> u64 {
+    NEXT_PACKET_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// pcap 头部中的捕获时间（微秒）
+fn header_timestamp_us(header: &pcap::PacketHeader) -> u64 {
+    header.ts.tv_sec as u64 * 1_000_000 + header.ts.tv_usec as u64
+}
+
+// 检测 HTTP 数据包类型（请求或响应）
+fn detect_http_packet_type(data: &[u8]) -> Option<String> {
+    if data.len() < 4 {
+        return None;
+    }
+
+    // 检查是否是 HTTP 请求
+    if data.starts_with(b"GET ")
+        || data.starts_with(b"POST ")
+        || data.starts_with(b"PUT ")
+        || data.starts_with(b"DELETE ")
+        || data.starts_with(b"HEAD ")
+        || data.starts_with(b"OPTIONS ")
+        || data.starts_with(b"PATCH ")
+        || data.starts_with(b"TRACE ")
+        || data.starts_with(b"CONNECT ")
+    {
+        return Some("request".to_string());
+    }
+
+    // 检查是否是 HTTP 响应
+    if data.starts_with(b"HTTP/1.0 ")
+        || data.starts_with(b"HTTP/1.1 ")
+        || data.starts_with(b"HTTP/2.0 ")
+        || data.starts_with(b"HTTP/3.0 ")
+    {
+        return Some("response".to_string());
+    }
+
+    None
+}
+
+// 在头部结束处拆分 HTTP 消息，返回 (头部, 原始消息体)
+fn split_http_message(data: &[u8]) -> (&[u8], &[u8]) {
+    match reassembly::find_subslice(data, b"\r\n\r\n") {
+        Some(pos) => (&data[..pos], &data[pos + 4..]),
+        None => (data, &[]),
+    }
+}
+
+// 解析 HTTP 请求
+fn parse_http_request(data: &[u8]) -> Option<HttpPacket> {
+    // 头部按文本解析，消息体保留原始字节
+    let (head, raw_body) = split_http_message(data);
+    let http_text = String::from_utf8_lossy(head);
+    let lines: Vec<&str> = http_text.split("\r\n").collect();
+    
+    if lines.is_empty() {
+        return None;
+    }
+    
+    // 解析请求行
+    let request_line_parts: Vec<&str> = lines[0].split_whitespace().collect();
+    if request_line_parts.len() < 3 {
+        return None;
+    }
+    
+    let method = request_line_parts[0].to_string();
+    let path = request_line_parts[1].to_string();
+    let version = request_line_parts[2].to_string();
+    
+    let mut host = String::new();
+    let mut content_type = String::new();
+    let mut content_length = None;
+    let mut headers = Vec::new();
+    
+    // 逐行处理头部
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            break;
+        }
+        
+        // 解析请求头
+        if i > 0 {
+            let parts: Vec<&str> = line.splitn(2, ": ").collect();
+            if parts.len() == 2 {
+                let header_name = parts[0].to_string();
+                let header_value = parts[1].to_string();
+                
+                // 提取特定的头信息
+                match header_name.to_lowercase().as_str() {
+                    "host" => host = header_value.clone(),
+                    "content-type" => content_type = header_value.clone(),
+                    "content-length" => {
+                        if let Ok(len) = header_value.parse::<usize>() {
+                            content_length = Some(len);
+                        }
+                    }
+                    _ => {}
+                }
+                
+                headers.push((header_name, header_value));
+            }
+        }
+    }
+    
+    // 解码请求体（分块传输编码、gzip/deflate/br 压缩）
+    let decoded = body::decode_body(&headers, raw_body);
+    let cookies = cookie::parse_cookies(&headers);
+    let set_cookies = cookie::parse_set_cookies(&headers);
+    let body = String::from_utf8_lossy(&decoded.data).into_owned();
+    
+    Some(HttpPacket {
+        id: 0, // 将在 process_packet 中设置
+        timestamp: 0, // 将在 process_packet 中设置
+        timestamp_us: 0, // 将在 process_packet 中设置
+        device_name: String::new(), // 将在 process_packet 中设置
+        session_id: String::new(), // 将在 process_packet 中设置
+        src_ip: String::new(), // 将在 process_packet 中设置
+        src_port: 0, // 将在 process_packet 中设置
+        dst_ip: String::new(), // 将在 process_packet 中设置
+        dst_port: 0, // 将在 process_packet 中设置
+        src_name: None, // 将在 process_packet 中设置
+        dst_name: None, // 将在 process_packet 中设置
+        packet_type: "request".to_string(),
+        method: Some(method),
+        path: Some(path),
+        status_code: None,
+        status_text: None,
+        version,
+        host,
+        content_type,
+        content_length,
+        headers,
+        body,
+        raw_body: BASE64.encode(raw_body),
+        transfer_encoding: decoded.transfer_encoding,
+        content_encoding: decoded.content_encoding,
+        body_truncated: decoded.truncated,
+        body_decode_error: decoded.error,
+        cookies,
+        set_cookies,
+        redacted: false,
+        paired_id: None,
+        round_trip_us: None,
+    })
+}
+
+// 解析 HTTP 响应
+fn parse_http_response(data: &[u8]) -> Option<HttpPacket> {
+    // 头部按文本解析，消息体保留原始字节
+    let (head, raw_body) = split_http_message(data);
+    let http_text = String::from_utf8_lossy(head);
+    let lines: Vec<&str> = http_text.split("\r\n").collect();
+    
+    if lines.is_empty() {
+        return None;
+    }
+    
+    // 解析状态行
+    let status_line_parts: Vec<&str> = lines[0].splitn(3, ' ').collect();
+    if status_line_parts.len() < 3 {
+        return None;
+    }
+    
+    let version = status_line_parts[0].to_string();
+    let status_code = match status_line_parts[1].parse::<u16>() {
+        Ok(code) => code,
+        Err(_) => return None,
+    };
+    let status_text = status_line_parts[2].to_string();
+    
+    let host = String::new();
+    let mut content_type = String::new();
+    let mut content_length = None;
+    let mut headers = Vec::new();
+    
+    // 逐行处理头部
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            break;
+        }
+        
+        // 解析响应头
+        if i > 0 {
+            let parts: Vec<&str> = line.splitn(2, ": ").collect();
+            if parts.len() == 2 {
+                let header_name = parts[0].to_string();
+                let header_value = parts[1].to_string();
+                
+                // 提取特定的头信息
+                match header_name.to_lowercase().as_str() {
+                    "content-type" => content_type = header_value.clone(),
+                    "content-length" => {
+                        if let Ok(len) = header_value.parse::<usize>() {
+                            content_length = Some(len);
+                        }
+                    }
+                    _ => {}
+                }
+                
+                headers.push((header_name, header_value));
+            }
+        }
+    }
+    
+    // 解码响应体（分块传输编码、gzip/deflate/br 压缩）
+    let decoded = body::decode_body(&headers, raw_body);
+    let cookies = cookie::parse_cookies(&headers);
+    let set_cookies = cookie::parse_set_cookies(&headers);
+    let body = String::from_utf8_lossy(&decoded.data).into_owned();
+    
+    Some(HttpPacket {
+        id: 0, // 将在 process_packet 中设置
+        timestamp: 0, // 将在 process_packet 中设置
+        timestamp_us: 0, // 将在 process_packet 中设置
+        device_name: String::new(), // 将在 process_packet 中设置
+        session_id: String::new(), // 将在 process_packet 中设置
+        src_ip: String::new(), // 将在 process_packet 中设置
+        src_port: 0, // 将在 process_packet 中设置
+        dst_ip: String::new(), // 将在 process_packet 中设置
+        dst_port: 0, // 将在 process_packet 中设置
+        src_name: None, // 将在 process_packet 中设置
+        dst_name: None, // 将在 process_packet 中设置
+        packet_type: "response".to_string(),
+        method: None,
+        path: None,
+        status_code: Some(status_code),
+        status_text: Some(status_text),
+        version,
+        host,
+        content_type,
+        content_length,
+        headers,
+        body,
+        raw_body: BASE64.encode(raw_body),
+        transfer_encoding: decoded.transfer_encoding,
+        content_encoding: decoded.content_encoding,
+        body_truncated: decoded.truncated,
+        body_decode_error: decoded.error,
+        cookies,
+        set_cookies,
+        redacted: false,
+        paired_id: None,
+        round_trip_us: None,
+    })
+}
+
+// 停止捕获会话，未指定会话ID时停止所有会话
+pub fn stop_capture(session_id: Option<&str>) -> Result<()> {
+    info!("正在停止数据包捕获...");
+
+    let targets: Vec<Arc<CaptureSession>> = match session_id {
+        Some(id) => vec![session::get_session(id).ok_or_else(|| anyhow!("未找到捕获会话: {}", id))?],
+        None => session::sessions().iter().map(|entry| entry.value().clone()).collect(),
+    };
+
+    // 已经自行停止的会话保留原有的停止原因
+    for session in targets.into_iter().filter(|s| s.is_running()) {
+        // 设置运行标志为 false 并等待线程结束
+        session.stop();
+        session.update_status(Some(false), Some("数据包捕获已停止".to_string()));
+    }
+    
+    info!("数据包捕获停止完成");
+    Ok(())
+}
+
+// 获取所有捕获会话的状态
+pub fn get_capture_status() -> Vec<CaptureStatus> {
+    session::all_statuses()
+}
+
+// 获取捕获统计，未指定会话ID时返回全部会话
+pub fn get_capture_stats(session_id: Option<&str>) -> Result<Vec<CaptureStats>> {
+    if let Some(id) = session_id {
+        if session::get_session(id).is_none() {
+            return Err(anyhow!("捕获会话不存在: {}", id));
+        }
+    }
+    Ok(session::session_stats(session_id))
+}
+
+// 发送状态更新给所有订阅者
+fn send_status_update(status: CaptureStatus) {
+    debug!("发送状态更新: {:?}", status);
+    STATUS_SUBSCRIBERS.send(&status);
+}
+
+// 发送 HTTP 数据包给所有订阅者（发送前脱敏）
+fn send_http_packet(mut packet: HttpPacket) {
+    if HTTP_SUBSCRIBERS.subscriber_count() == 0 {
+        return;
+    }
+    // 先脱敏再记录日志，路径中的敏感参数不会写入日志
+    redact::redact_packet(&mut packet);
+    info!("发送 HTTP {}: {:?}",
+        if packet.packet_type == "request" { "请求" } else { "响应" },
+        packet.path);
+    HTTP_SUBSCRIBERS.send(&packet);
+}
+
+// 发送 HTTP 事务给所有订阅者（发送前脱敏）
+fn send_http_transaction(mut transaction: HttpTransaction) {
+    if TRANSACTION_SUBSCRIBERS.subscriber_count() == 0 {
+        return;
+    }
+    redact::redact_packet(&mut transaction.request);
+    if let Some(response) = transaction.response.as_mut() {
+        redact::redact_packet(response);
+    }
+    debug!("发送 HTTP 事务: {} {:?}", transaction.id, transaction.request.path);
+    TRANSACTION_SUBSCRIBERS.send(&transaction);
+}
+
+// 发送捕获会话结束摘要给所有订阅者
+fn send_capture_summary(summary: CaptureSummary) {
+    SUMMARY_SUBSCRIBERS.send(&summary);
+}
+
+// 发送网络设备变化事件给所有订阅者
+fn send_device_event(event: DeviceChangeEvent) {
+    DEVICE_SUBSCRIBERS.send(&event);
+}
+
+// 发送 TLS 连接事件给所有订阅者
+fn send_tls_event(event: TlsConnectionEvent) {
+    TLS_SUBSCRIBERS.send(&event);
+}
+
+// 发送 DNS 查询日志事件给所有订阅者
+fn send_dns_event(event: DnsEvent) {
+    DNS_SUBSCRIBERS.send(&event);
+}
+
+// 发送 TCP 连接更新事件给所有订阅者
+fn send_flow_update(event: FlowUpdateEvent) {
+    FLOW_SUBSCRIBERS.send(&event);
+}
+
+// 获取网络设备列表
+pub fn get_network_devices() -> Result<Vec<NetworkDevice>> {
+    let devices = devices::list_devices()?;
+    info!("找到 {} 个网络设备", devices.len());
+    Ok(devices)
+}
+
+/// 检查是否安装了抓包所需的系统组件（macOS上是ChmodBPF）
+#[cfg(target_os = "macos")]
+pub fn has_capture_prerequisites() -> bool {
+    use log::{info};
+    use std::path::Path;
+
+    // 检查ChmodBPF服务是否存在
+    info!("检查ChmodBPF服务是否已安装...");
+    let chmodbpf_path = Path::new("/Library/LaunchDaemons/org.wireshark.ChmodBPF.plist");
+    if !chmodbpf_path.exists() {
+        return false;
+    }
+
+    // 打印ChmodBPF服务状态日志
+    info!("ChmodBPF服务文件存在，检查服务状态...");
+    
+    
+    return true;
+        
+}
+
+/// 在Windows上检查是否安装了Npcap
+#[cfg(target_os = "windows")]
+pub fn has_capture_prerequisites() -> bool {
+    use log::{info};
+    use std::path::Path;
+    
+    // 检查Npcap是否已安装
+    info!("检查Windows上Npcap是否已安装...");
+    
+    // 检查Npcap安装路径
+    let npcap_path = Path::new("C:\\Windows\\System32\\Npcap");
+    if npcap_path.exists() {
+        info!("检测到Npcap安装目录存在");
+        return true;
+    }
+    
+    return false;
+}
+
+/// 在 Linux 等其他平台上，以是否有设备可以打开捕获为准
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn has_capture_prerequisites() -> bool {
+    prerequisites::check_capture_prerequisites().ready
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use etherparse::PacketBuilder;
+
+    type Endpoint = ([u8; 4], u16);
+
+    fn tcp_frame(src: Endpoint, dst: Endpoint, payload: &[u8]) -> Vec<u8> {
+        let builder = PacketBuilder::ethernet2([1; 6], [2; 6])
+            .ipv4(src.0, dst.0, 64)
+            .tcp(src.1, dst.1, 1, 65535)
+            .ack(1)
+            .psh();
+        let mut frame = Vec::with_capacity(builder.size(payload.len()));
+        builder.write(&mut frame, payload).unwrap();
+        frame
+    }
+
+    fn udp_frame(src: Endpoint, dst: Endpoint, payload: &[u8]) -> Vec<u8> {
+        let builder = PacketBuilder::ethernet2([1; 6], [2; 6]).ipv4(src.0, dst.0, 64).udp(src.1, dst.1);
+        let mut frame = Vec::with_capacity(builder.size(payload.len()));
+        builder.write(&mut frame, payload).unwrap();
+        frame
+    }
+
+    #[test]
+    fn test_filter_expression() {
+        let mut options = CaptureOptions::default();
+        assert_eq!(
+            options.filter_expression(),
+            "tcp port 80 or tcp port 8080 or tcp port 443 or udp port 53 or tcp port 53"
+        );
+
+        // 额外端口去重后追加在默认端口之后
+        options.extra_ports = vec![8080, 3000, 3000];
+        assert_eq!(
+            options.filter_expression(),
+            "tcp port 80 or tcp port 8080 or tcp port 443 or tcp port 3000 or udp port 53 or tcp port 53"
+        );
+
+        // 自定义表达式替代端口过滤，空白表达式视为未设置
+        options.bpf_filter = Some("  host 10.0.0.1  ".to_string());
+        assert_eq!(options.filter_expression(), "host 10.0.0.1");
+        options.bpf_filter = Some("   ".to_string());
+        assert!(options.filter_expression().starts_with("tcp port 80 or"));
+    }
+
+    #[test]
+    fn test_validate_options() {
+        let options = CaptureOptions::default();
+        assert_eq!(options.validate().unwrap(), options.filter_expression());
+
+        let invalid: [fn(&mut CaptureOptions); 7] = [
+            |o| o.extra_ports = vec![0],
+            |o| o.snaplen = 0,
+            |o| o.buffer_size = Some(0),
+            |o| o.timeout_ms = 0,
+            |o| o.timeout_ms = -1,
+            |o| o.stop.max_duration_secs = Some(0),
+            |o| o.bpf_filter = Some("tcp port".to_string()),
+        ];
+        for (i, modify) in invalid.iter().enumerate() {
+            let mut options = CaptureOptions::default();
+            modify(&mut options);
+            assert!(options.validate().is_err(), "第 {} 个选项应校验失败", i);
+        }
+
+        let mut options = CaptureOptions {
+            keylog_file: Some(std::env::temp_dir().to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        assert!(options.validate().is_err());
+        // 空白的密钥日志路径视为未设置
+        options.keylog_file = Some("  ".to_string());
+        options.bpf_filter = Some("tcp port 8443".to_string());
+        assert_eq!(options.validate().unwrap(), "tcp port 8443");
+    }
+
+    #[test]
+    fn test_session_filter() {
+        let event = FlowUpdateEvent {
+            session_id: "s1".to_string(),
+            flows: Vec::new(),
+        };
+        assert!(SessionFilter::default().matches(&event));
+        let filter: SessionFilter = serde_json::from_str(r#"{"session_id":"s1"}"#).unwrap();
+        assert!(filter.matches(&event));
+        let other = SessionFilter {
+            session_id: Some("s2".to_string()),
+        };
+        assert!(!other.matches(&event));
+    }
+
+    #[test]
+    fn test_pipeline_accumulates_counters() {
+        let mut pipeline = CapturePipeline::new("eth0".to_string(), "pipeline-test".to_string(), false);
+        let server = ([10, 0, 0, 2], 80);
+        // abc.com 的 A 记录查询
+        let dns_query = [
+            0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, 3, b'a', b'b', b'c', 3, b'c', b'o', b'm', 0, 0, 1, 0, 1,
+        ];
+        let mut frames = vec![udp_frame(([10, 0, 0, 1], 53000), ([10, 0, 0, 53], DNS_PORT), &dns_query)];
+        // 两个连接各一对请求和响应，计数在连接之间累加
+        for port in [50000, 50001] {
+            let client = ([10, 0, 0, 1], port);
+            frames.push(tcp_frame(client, server, b"GET / HTTP/1.1\r\nHost: abc.com\r\n\r\n"));
+            frames.push(tcp_frame(server, client, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"));
+        }
+
+        for (i, frame) in frames.iter().enumerate() {
+            pipeline.process_packet(SlicedPacket::from_ethernet(frame).unwrap(), 1_000_000 + i as u64);
+        }
+
+        let stats = pipeline.current_stats();
+        assert_eq!(stats.http_requests, 2);
+        assert_eq!(stats.http_responses, 2);
+        assert_eq!(stats.dns_messages, 1);
+        assert_eq!(stats.tls_connections, 0);
+        assert_eq!(stats.ip_fragments, 0);
+    }
+}
@@ -0,0 +1,184 @@
+use super::{link, CapturePipeline};
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use pcap::Capture;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 离线分析结果摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OfflineAnalysisSummary {
+    pub file_path: String,
+    /// 文件中读取到的帧数
+    pub frames: u64,
+    /// 无法解析的帧数
+    pub parse_errors: u64,
+    /// 解析出的 HTTP 消息数
+    pub http_messages: u64,
+    /// 识别出的 TLS 连接数
+    pub tls_connections: u64,
+    /// 使用密钥日志解密的 TLS 连接数
+    pub tls_decrypted: u64,
+    /// 第一帧和最后一帧的捕获时间（微秒）
+    pub first_timestamp_us: u64,
+    pub last_timestamp_us: u64,
+}
+
+/// 分析 pcap/pcapng 文件
+///
+/// 文件中的每一帧都经过与实时捕获相同的处理流水线，解析出的 HTTP 数据包
+/// 通过 HTTP 数据包通道发送给前端；`feed_auth` 为 true 时同时交给认证系统，
+/// 从而可以从录制的流量中恢复 token。指定 `keylog_file` 时同时解密其中能找到密钥的 TLS 连接。
+pub fn analyze_capture_file(file_path: &str, feed_auth: bool, keylog_file: Option<&str>) -> Result<OfflineAnalysisSummary> {
+    info!("开始离线分析抓包文件: {file_path}");
+
+    if !Path::new(file_path).is_file() {
+        return Err(anyhow!("抓包文件不存在: {}", file_path));
+    }
+
+    let mut cap = Capture::from_file(file_path)
+        .map_err(|e| anyhow!("打开抓包文件失败: {}", e))?;
+
+    let linktype = cap.get_datalink();
+    if !link::is_supported_linktype(linktype) {
+        return Err(anyhow!("不支持的链路层类型: {}", link::linktype_name(linktype)));
+    }
+
+    // 离线数据包以文件名作为来源设备
+    let source = Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file_path.to_string());
+    let mut pipeline = CapturePipeline::new(source.clone(), source, feed_auth);
+    pipeline.set_keylog_file(keylog_file);
+    let mut summary = OfflineAnalysisSummary {
+        file_path: file_path.to_string(),
+        frames: 0,
+        parse_errors: 0,
+        http_messages: 0,
+        tls_connections: 0,
+        tls_decrypted: 0,
+        first_timestamp_us: 0,
+        last_timestamp_us: 0,
+    };
+
+    loop {
+        let packet = match cap.next_packet() {
+            Ok(packet) => packet,
+            Err(pcap::Error::NoMorePackets) => break,
+            Err(e) => return Err(anyhow!("读取抓包文件失败: {}", e)),
+        };
+
+        let timestamp_us = super::header_timestamp_us(packet.header);
+        if summary.frames == 0 {
+            summary.first_timestamp_us = timestamp_us;
+        }
+        summary.last_timestamp_us = timestamp_us;
+        summary.frames += 1;
+
+        match link::slice_frame(linktype, packet.data) {
+            Ok(sliced) => pipeline.process_packet(sliced, timestamp_us),
+            Err(e) => {
+                summary.parse_errors += 1;
+                debug!("解析数据包错误: {}", e);
+            }
+        }
+    }
+
+    // 文件结束相当于所有连接关闭，输出仍在缓冲中的消息
+    pipeline.flush();
+    pipeline.publish_flows(summary.last_timestamp_us, true);
+    summary.http_messages = pipeline.stats.http_requests + pipeline.stats.http_responses;
+    summary.tls_connections = pipeline.tls.connections();
+    summary.tls_decrypted = pipeline.current_stats().tls_decrypted;
+    info!(
+        "离线分析完成: {} 帧，{} 条 HTTP 消息，{} 个解析错误",
+        summary.frames, summary.http_messages, summary.parse_errors
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use etherparse::PacketBuilder;
+
+    fn tcp_frame(src_port: u16, dst_port: u16, seq: u32, payload: &[u8]) -> Vec<u8> {
+        let (src, dst) = if src_port == 80 {
+            ([10, 0, 0, 2], [10, 0, 0, 1])
+        } else {
+            ([10, 0, 0, 1], [10, 0, 0, 2])
+        };
+        let builder = PacketBuilder::ethernet2([1; 6], [2; 6])
+            .ipv4(src, dst, 64)
+            .tcp(src_port, dst_port, seq, 65535)
+            .ack(1)
+            .psh();
+        let mut frame = Vec::with_capacity(builder.size(payload.len()));
+        builder.write(&mut frame, payload).unwrap();
+        frame
+    }
+
+    fn write_pcap(path: &Path, frames: &[Vec<u8>]) {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&4u16.to_le_bytes());
+        data.extend_from_slice(&0i32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&65535u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes()); // LINKTYPE_ETHERNET
+
+        for (i, frame) in frames.iter().enumerate() {
+            data.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+            data.extend_from_slice(&(i as u32 * 1000).to_le_bytes());
+            data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            data.extend_from_slice(frame);
+        }
+        std::fs::write(path, data).unwrap();
+    }
+
+    fn temp_pcap(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("offline_analysis_{}_test-{}.pcap", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_analyze_split_request_from_file() {
+        let first = b"POST /api/v2/biPlatform/query/byQueryParam HTTP/1.1\r\nHost: 23.210.227.16\r\nContent-Length: 4\r\n\r\nab";
+        let second = b"cd";
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let frames = vec![
+            tcp_frame(50000, 80, 1, first),
+            tcp_frame(50000, 80, 1 + first.len() as u32, second),
+            tcp_frame(80, 50000, 1, response),
+        ];
+
+        let path = temp_pcap("split");
+        write_pcap(&path, &frames);
+
+        let summary = analyze_capture_file(path.to_str().unwrap(), false, None).unwrap();
+        assert_eq!(summary.frames, 3);
+        assert_eq!(summary.parse_errors, 0);
+        assert_eq!(summary.http_messages, 2);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_flush_close_delimited_response_at_end_of_file() {
+        let request = b"GET /download HTTP/1.1\r\nHost: 23.210.227.16\r\n\r\n";
+        let response = b"HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\npartial body";
+        let frames = vec![tcp_frame(50000, 80, 1, request), tcp_frame(80, 50000, 1, response)];
+
+        let path = temp_pcap("eof");
+        write_pcap(&path, &frames);
+
+        // 文件在连接关闭之前结束，没有长度的响应仍然应当输出
+        let summary = analyze_capture_file(path.to_str().unwrap(), false, None).unwrap();
+        assert_eq!(summary.frames, 2);
+        assert_eq!(summary.http_messages, 2);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
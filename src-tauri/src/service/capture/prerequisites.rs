@@ -0,0 +1,186 @@
+use log::{debug, info};
+use pcap::Capture;
+use serde::{Deserialize, Serialize};
+
+/// 单个网络设备的打开测试结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceAccess {
+    pub name: String,
+    pub can_open: bool,
+    pub error: Option<String>,
+}
+
+/// 捕获前置条件诊断报告
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CapturePrerequisiteReport {
+    /// 当前操作系统（linux / macos / windows）
+    pub platform: String,
+    /// 是否至少有一个设备可以打开捕获
+    pub ready: bool,
+    /// 平台抓包组件是否就绪（macOS 为 ChmodBPF，Windows 为 Npcap）
+    pub driver_installed: bool,
+    /// 进程是否以 root 身份运行（仅 Linux）
+    pub is_root: bool,
+    /// 进程当前有效的抓包相关能力，如 CAP_NET_RAW（仅 Linux）
+    pub effective_capabilities: Vec<String>,
+    /// 缺少的抓包相关能力（仅 Linux）
+    pub missing_capabilities: Vec<String>,
+    /// 当前可执行文件路径
+    pub binary_path: Option<String>,
+    /// getcap 报告的可执行文件能力（仅 Linux，未安装 getcap 时为空）
+    pub binary_capabilities: Option<String>,
+    pub devices: Vec<DeviceAccess>,
+    /// 修复权限问题的命令
+    pub fix_command: Option<String>,
+    /// 发现的问题说明
+    pub issues: Vec<String>,
+}
+
+/// Linux 抓包所需的能力（名称, 能力编号）
+#[cfg(target_os = "linux")]
+const CAPTURE_CAPABILITIES: [(&str, u32); 2] = [("CAP_NET_ADMIN", 12), ("CAP_NET_RAW", 13)];
+
+/// 生成捕获前置条件诊断报告
+///
+/// 会逐个尝试打开网络设备，设备较多时可能需要一两秒。
+pub fn check_capture_prerequisites() -> CapturePrerequisiteReport {
+    let mut report = CapturePrerequisiteReport {
+        platform: std::env::consts::OS.to_string(),
+        binary_path: std::env::current_exe()
+            .ok()
+            .map(|path| path.to_string_lossy().into_owned()),
+        ..Default::default()
+    };
+
+    check_platform(&mut report);
+
+    match pcap::Device::list() {
+        Ok(devices) => {
+            report.devices = devices.into_iter().map(test_device).collect();
+            if report.devices.is_empty() {
+                report.issues.push("未发现任何网络设备".to_string());
+            }
+        }
+        Err(e) => report.issues.push(format!("获取网络设备列表失败: {}", e)),
+    }
+
+    report.ready = report.devices.iter().any(|d| d.can_open);
+    if !report.ready && !report.devices.is_empty() {
+        report.issues.push("所有网络设备都无法打开捕获".to_string());
+    }
+
+    info!(
+        "捕获前置条件检查完成: ready={}, {}/{} 个设备可用",
+        report.ready,
+        report.devices.iter().filter(|d| d.can_open).count(),
+        report.devices.len()
+    );
+    report
+}
+
+/// 尝试以最小配置打开设备，立即关闭
+fn test_device(device: pcap::Device) -> DeviceAccess {
+    let name = device.name.clone();
+    let result = Capture::from_device(device).and_then(|cap| cap.promisc(false).snaplen(64).timeout(100).open());
+    match result {
+        Ok(_) => DeviceAccess { name, can_open: true, error: None },
+        Err(e) => {
+            debug!("设备 {} 无法打开: {}", name, e);
+            DeviceAccess { name, can_open: false, error: Some(e.to_string()) }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check_platform(report: &mut CapturePrerequisiteReport) {
+    // Linux 上 libpcap 直接使用内核接口，无需额外驱动
+    report.driver_installed = true;
+
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    report.is_root = parse_effective_uid(&status) == Some(0);
+
+    let cap_eff = parse_effective_capabilities(&status).unwrap_or(0);
+    for (name, bit) in CAPTURE_CAPABILITIES {
+        if cap_eff & (1u64 << bit) != 0 {
+            report.effective_capabilities.push(name.to_string());
+        } else {
+            report.missing_capabilities.push(name.to_string());
+        }
+    }
+
+    if let Some(path) = &report.binary_path {
+        report.binary_capabilities = std::process::Command::new("getcap")
+            .arg(path)
+            .output()
+            .ok()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .filter(|caps| !caps.is_empty());
+    }
+
+    // root 默认拥有全部能力，只有非 root 且缺少能力时才需要 setcap
+    if !report.missing_capabilities.is_empty() {
+        report.issues.push(format!(
+            "当前进程缺少 {}，非 root 用户无法抓包",
+            report.missing_capabilities.join("、")
+        ));
+        if let Some(path) = &report.binary_path {
+            report.fix_command = Some(format!("sudo setcap cap_net_raw,cap_net_admin=eip '{}'", path));
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn check_platform(report: &mut CapturePrerequisiteReport) {
+    report.driver_installed = super::has_capture_prerequisites();
+    if !report.driver_installed {
+        report.issues.push("未安装 ChmodBPF，无法访问 /dev/bpf*".to_string());
+        report.fix_command = Some("brew install --cask wireshark-chmodbpf".to_string());
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn check_platform(report: &mut CapturePrerequisiteReport) {
+    report.driver_installed = super::has_capture_prerequisites();
+    if !report.driver_installed {
+        report.issues.push("未安装 Npcap，请从 https://npcap.com 下载安装".to_string());
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn check_platform(report: &mut CapturePrerequisiteReport) {
+    // 其他平台只依据设备打开测试判断
+    report.driver_installed = true;
+}
+
+/// 从 /proc/self/status 中解析有效用户ID
+#[cfg(target_os = "linux")]
+fn parse_effective_uid(status: &str) -> Option<u32> {
+    // 格式: "Uid:\t真实\t有效\t保存\t文件系统"
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+/// 从 /proc/self/status 中解析有效能力集
+#[cfg(target_os = "linux")]
+fn parse_effective_capabilities(status: &str) -> Option<u64> {
+    let hex = status.lines().find_map(|line| line.strip_prefix("CapEff:"))?.trim();
+    u64::from_str_radix(hex, 16).ok()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_status() {
+        let status = "Name:\tproxy\nUid:\t1000\t0\t0\t0\nCapInh:\t0000000000000000\nCapEff:\t0000000000003000\n";
+        assert_eq!(parse_effective_uid(status), Some(0));
+        let cap_eff = parse_effective_capabilities(status).unwrap();
+        assert!(CAPTURE_CAPABILITIES.iter().all(|(_, bit)| cap_eff & (1u64 << bit) != 0));
+    }
+}
@@ -0,0 +1,1040 @@
+use super::ca::LocalCa;
+use super::reassembly::FlowKey;
+use super::session::{self, CaptureSession};
+use super::stats::{RateMeter, STATS_INTERVAL};
+use super::stop::{StopConditions, StopReason, StopTracker};
+use super::{send_capture_summary, CaptureOptions, CapturePipeline};
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use once_cell::sync::OnceCell;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// 请求头或响应头的最大字节数
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+/// 每条消息最多保留用于解析的字节数，超出部分照常转发但不解析
+const MAX_CAPTURED_MESSAGE: usize = 16 * 1024 * 1024;
+/// 等待流水线处理的消息数量上限，队列满时连接任务等待
+const PIPELINE_QUEUE_SIZE: usize = 256;
+
+/// 本地根证书，首次启用 HTTPS 解密时加载
+static LOCAL_CA: OnceCell<Arc<LocalCa>> = OnceCell::new();
+
+/// 代理捕获选项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyOptions {
+    /// 监听地址，默认只接受本机连接
+    pub bind_address: String,
+    pub port: u16,
+    /// 是否解密 HTTPS（CONNECT 隧道），关闭时只转发不解析
+    pub intercept_tls: bool,
+    /// 是否校验上游服务器证书，内网自签名证书的站点需要关闭
+    pub verify_upstream: bool,
+    /// 自动停止条件
+    pub stop: StopConditions,
+}
+
+impl Default for ProxyOptions {
+    fn default() -> Self {
+        Self {
+            bind_address: "127.0.0.1".to_string(),
+            port: 8899,
+            intercept_tls: true,
+            verify_upstream: true,
+            stop: StopConditions::default(),
+        }
+    }
+}
+
+/// 代理启动结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyInfo {
+    pub session_id: String,
+    pub listen_addr: String,
+    /// 根证书信息，需要导入系统或浏览器的受信任根证书后才能解密 HTTPS
+    pub ca: Option<ProxyCaInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyCaInfo {
+    pub cert_path: String,
+    pub cert_pem: String,
+}
+
+/// 代理转发的一条完整 HTTP 消息
+struct ProxyMessage {
+    key: FlowKey,
+    data: Vec<u8>,
+    wire_len: u64,
+    timestamp_us: u64,
+}
+
+/// 代理连接共享的上下文
+struct ProxyContext {
+    session: Arc<CaptureSession>,
+    /// 发往流水线线程的消息队列
+    messages: mpsc::Sender<ProxyMessage>,
+    ca: Option<Arc<LocalCa>>,
+    upstream_tls: Arc<ClientConfig>,
+}
+
+impl ProxyContext {
+    /// 把一条完整的 HTTP 消息交给流水线，与 pcap 捕获的数据包走同一条处理路径
+    async fn record(&self, key: FlowKey, data: Vec<u8>, wire_len: u64) {
+        if data.is_empty() {
+            return;
+        }
+        let message = ProxyMessage {
+            key,
+            data,
+            wire_len,
+            timestamp_us: now_us(),
+        };
+        if self.messages.send(message).await.is_err() {
+            debug!("代理流水线已停止，跳过消息");
+        }
+    }
+}
+
+/// 启动流水线线程
+///
+/// 流水线处理会进入认证队列，Block 策略下可能阻塞数秒，因此放在独立线程中，
+/// 不占用异步运行时的工作线程。所有发送端释放后线程结束。
+fn start_pipeline_worker(pipeline: Arc<Mutex<CapturePipeline>>) -> (mpsc::Sender<ProxyMessage>, thread::JoinHandle<()>) {
+    let (sender, mut receiver) = mpsc::channel::<ProxyMessage>(PIPELINE_QUEUE_SIZE);
+    let worker = thread::spawn(move || {
+        while let Some(message) = receiver.blocking_recv() {
+            match pipeline.lock() {
+                Ok(mut pipeline) => {
+                    // 代理模式下 frames 统计的是 HTTP 消息数
+                    pipeline.stats.frames += 1;
+                    pipeline.stats.bytes += message.wire_len;
+                    pipeline.process_http_message(message.key, &message.data, message.timestamp_us);
+                }
+                Err(_) => debug!("代理流水线锁已损坏，跳过消息"),
+            }
+        }
+    });
+    (sender, worker)
+}
+
+/// 获取本地根证书，不存在时生成
+pub fn local_ca() -> Result<Arc<LocalCa>> {
+    LOCAL_CA
+        .get_or_try_init(|| LocalCa::load_or_create().map(Arc::new))
+        .cloned()
+}
+
+/// 根证书信息，用于在界面上提示用户导入
+pub fn ca_info() -> Result<ProxyCaInfo> {
+    let ca = local_ca()?;
+    Ok(ProxyCaInfo {
+        cert_path: ca.cert_path().to_string_lossy().into_owned(),
+        cert_pem: ca.cert_pem().to_string(),
+    })
+}
+
+/// 启动代理捕获，返回会话信息
+///
+/// 代理会话与 pcap 会话共用会话表，可以用 stop_capture 停止，状态和统计也通过同一状态流推送。
+pub async fn start_proxy(options: ProxyOptions, session_id: Option<String>) -> Result<ProxyInfo> {
+    options.stop.validate()?;
+    let listen_addr = format!("{}:{}", options.bind_address, options.port);
+    let session_id = session_id
+        .filter(|id| !id.trim().is_empty())
+        .unwrap_or_else(|| format!("proxy:{}", options.port));
+
+    if let Some(existing) = session::get_session(&session_id) {
+        if existing.is_running() {
+            return Err(anyhow!("捕获会话 [{}] 已经在运行中，请先停止", session_id));
+        }
+    }
+
+    let ca = if options.intercept_tls { Some(local_ca()?) } else { None };
+    let upstream_tls = upstream_client_config(options.verify_upstream)?;
+    let listener = TcpListener::bind(&listen_addr)
+        .await
+        .map_err(|e| anyhow!("代理监听 {} 失败: {}", listen_addr, e))?;
+
+    let capture_options = CaptureOptions {
+        stop: options.stop.clone(),
+        ..Default::default()
+    };
+    let device_name = format!("proxy:{}", listen_addr);
+    let session = Arc::new(CaptureSession::new(session_id.clone(), device_name.clone(), capture_options, String::new()));
+    session.set_running(true);
+    session::register_session(session.clone())?;
+    session.update_status(Some(true), Some(format!("代理已启动，监听 {}", listen_addr)));
+
+    let mut pipeline = CapturePipeline::new(device_name, session_id.clone(), true);
+    pipeline.stop = Some(StopTracker::new(options.stop)?);
+    let pipeline = Arc::new(Mutex::new(pipeline));
+    // 线程在会话停止、所有连接关闭后自行结束
+    let (messages, _worker) = start_pipeline_worker(pipeline.clone());
+    let context = Arc::new(ProxyContext {
+        session: session.clone(),
+        messages,
+        ca: ca.clone(),
+        upstream_tls,
+    });
+
+    tauri::async_runtime::spawn(run_proxy(listener, context, pipeline));
+    info!("代理捕获会话 [{}] 已启动，监听 {}", session_id, listen_addr);
+
+    Ok(ProxyInfo {
+        session_id,
+        listen_addr,
+        ca: ca.map(|ca| ProxyCaInfo {
+            cert_path: ca.cert_path().to_string_lossy().into_owned(),
+            cert_pem: ca.cert_pem().to_string(),
+        }),
+    })
+}
+
+/// 接受连接，并按统计周期推送统计、检查停止条件
+async fn run_proxy(listener: TcpListener, context: Arc<ProxyContext>, pipeline: Arc<Mutex<CapturePipeline>>) {
+    let session = context.session.clone();
+    let mut rate = RateMeter::new();
+    let mut ticker = tokio::time::interval(STATS_INTERVAL);
+    let mut stop_signal = session.stop_signal();
+
+    let reason = loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, client_addr)) => {
+                    let context = context.clone();
+                    // 会话停止时关闭连接，包括正在转发的 CONNECT 隧道和协议升级后的连接
+                    let mut connection_stop = session.stop_signal();
+                    tauri::async_runtime::spawn(async move {
+                        tokio::select! {
+                            result = handle_client(stream, client_addr, context) => {
+                                if let Err(e) = result {
+                                    debug!("代理连接 {} 结束: {}", client_addr, e);
+                                }
+                            }
+                            _ = session::wait_stopped(&mut connection_stop) => {
+                                debug!("代理连接 {} 随会话停止而关闭", client_addr);
+                            }
+                        }
+                    });
+                }
+                Err(e) => warn!("代理接受连接失败: {}", e),
+            },
+            // stop_capture 清除运行标志后立即停止监听
+            _ = session::wait_stopped(&mut stop_signal) => break StopReason::Manual,
+            _ = ticker.tick() => {
+                if !session.is_running() {
+                    break StopReason::Manual;
+                }
+                // 流水线线程可能正阻塞在认证队列上，不在异步任务中等待锁
+                let (stats, stop_reason) = match pipeline.try_lock() {
+                    Ok(pipeline) => (pipeline.current_stats(), pipeline.check_stop(&session)),
+                    Err(_) => {
+                        debug!("代理流水线正忙，跳过此次统计");
+                        continue;
+                    }
+                };
+                let mut stats = stats;
+                rate.update(&mut stats);
+                session.update_stats(stats);
+                if let Some(stop_reason) = stop_reason {
+                    info!("捕获会话 [{}] {}", session.id(), stop_reason.message());
+                    session.set_running(false);
+                    break stop_reason;
+                }
+            }
+        }
+    };
+    drop(listener);
+
+    let final_stats = tokio::task::spawn_blocking(move || pipeline.lock().ok().map(|p| p.current_stats())).await;
+    if let Ok(Some(mut stats)) = final_stats {
+        rate.update(&mut stats);
+        session.update_stats(stats);
+    }
+    session.update_status(Some(false), Some(reason.message()));
+    send_capture_summary(session.summary(reason));
+    info!("代理捕获会话 [{}] 已停止", session.id());
+}
+
+/// 处理一个客户端连接：CONNECT 隧道或普通 HTTP 代理请求
+async fn handle_client<S>(stream: S, client_addr: SocketAddr, context: Arc<ProxyContext>) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut client = BufReader::new(stream);
+    let Some(head) = read_head(&mut client).await? else {
+        return Ok(());
+    };
+    let (start_line, _) = parse_head(&head)?;
+    let mut parts = start_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    if !method.eq_ignore_ascii_case("CONNECT") {
+        return relay_plain(client, head, client_addr, &context).await;
+    }
+
+    let (host, port) = split_authority(&target, 443)?;
+    let upstream = TcpStream::connect((host.as_str(), port))
+        .await
+        .map_err(|e| anyhow!("连接上游 {} 失败: {}", target, e))?;
+    let upstream_addr = upstream.peer_addr()?;
+    client.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n").await?;
+    client.flush().await?;
+
+    // 客户端在收到 200 之后才发送 ClientHello，缓冲区中不应有数据
+    if !client.buffer().is_empty() {
+        return Err(anyhow!("CONNECT 之后收到了意外的数据"));
+    }
+    let mut client = client.into_inner();
+
+    let Some(ca) = &context.ca else {
+        let mut upstream = upstream;
+        tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+        return Ok(());
+    };
+
+    let client_tls = TlsAcceptor::from(ca.server_config(&host)?)
+        .accept(client)
+        .await
+        .map_err(|e| anyhow!("与客户端的 TLS 握手失败（是否已信任代理根证书？）{}: {}", host, e))?;
+    let server_name = ServerName::try_from(host.clone()).map_err(|e| anyhow!("无效的主机名 {}: {}", host, e))?;
+    let upstream_tls = TlsConnector::from(context.upstream_tls.clone())
+        .connect(server_name, upstream)
+        .await
+        .map_err(|e| anyhow!("与上游 {} 的 TLS 握手失败: {}", host, e))?;
+
+    let mut client = BufReader::new(client_tls);
+    let mut upstream = BufReader::new(upstream_tls);
+    let key = FlowKey::new(client_addr, upstream_addr);
+    while context.session.is_running() {
+        let Some(head) = read_head(&mut client).await? else {
+            break;
+        };
+        if !exchange(&mut client, &mut upstream, head, key, &context).await? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// 普通 HTTP 代理：请求行使用绝对 URI，目标主机变化时重新连接上游
+async fn relay_plain<S>(
+    mut client: BufReader<S>,
+    mut head: Vec<u8>,
+    client_addr: SocketAddr,
+    context: &ProxyContext,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut upstream: Option<(String, BufReader<TcpStream>, SocketAddr)> = None;
+
+    loop {
+        let (start_line, _) = parse_head(&head)?;
+        let target = start_line.split_whitespace().nth(1).unwrap_or_default().to_string();
+        let (authority, path) = split_absolute_uri(&target)?;
+
+        if upstream.as_ref().is_none_or(|(current, _, _)| *current != authority) {
+            let (host, port) = split_authority(&authority, 80)?;
+            let stream = TcpStream::connect((host.as_str(), port))
+                .await
+                .map_err(|e| anyhow!("连接上游 {} 失败: {}", authority, e))?;
+            let addr = stream.peer_addr()?;
+            upstream = Some((authority.clone(), BufReader::new(stream), addr));
+        }
+        let Some((_, upstream_stream, upstream_addr)) = upstream.as_mut() else {
+            break;
+        };
+
+        let head_out = rewrite_request_target(&head, &path)?;
+        let key = FlowKey::new(client_addr, *upstream_addr);
+        if !exchange(&mut client, upstream_stream, head_out, key, context).await? {
+            break;
+        }
+        if !context.session.is_running() {
+            break;
+        }
+        head = match read_head(&mut client).await? {
+            Some(head) => head,
+            None => break,
+        };
+    }
+    Ok(())
+}
+
+/// 转发一次请求/响应并记录，返回连接是否可以继续复用
+async fn exchange<C, U>(
+    client: &mut BufReader<C>,
+    upstream: &mut BufReader<U>,
+    request_head: Vec<u8>,
+    key: FlowKey,
+    context: &ProxyContext,
+) -> Result<bool>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: AsyncRead + AsyncWrite + Unpin,
+{
+    let (start_line, headers) = parse_head(&request_head)?;
+    let method = start_line.split_whitespace().next().unwrap_or_default().to_string();
+    let request_close = has_token(&headers, "connection", "close");
+
+    // 转发请求
+    let mut request = Vec::new();
+    capture(&mut request, &request_head);
+    upstream.write_all(&request_head).await?;
+    let request_framing = request_framing(&headers);
+    let request_len = relay_body(client, upstream, request_framing, &mut request).await?;
+    upstream.flush().await?;
+    context.record(key, request, (request_head.len() as u64) + request_len).await;
+
+    // 读取响应，1xx 中间响应直接转发
+    let (response_head, status, headers) = loop {
+        let head = read_head(upstream)
+            .await?
+            .ok_or_else(|| anyhow!("上游在响应之前关闭了连接"))?;
+        let (status_line, headers) = parse_head(&head)?;
+        let status: u16 = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| anyhow!("无效的响应状态行: {}", status_line))?;
+        if (100..200).contains(&status) && status != 101 {
+            client.write_all(&head).await?;
+            continue;
+        }
+        break (head, status, headers);
+    };
+    let http10 = String::from_utf8_lossy(&response_head).starts_with("HTTP/1.0");
+
+    let mut response = Vec::new();
+    capture(&mut response, &response_head);
+    client.write_all(&response_head).await?;
+
+    // 协议升级（例如 WebSocket）之后不再按 HTTP 解析，直接双向转发
+    if status == 101 {
+        client.flush().await?;
+        context.record(key.reversed(), response, response_head.len() as u64).await;
+        tokio::io::copy_bidirectional(client, upstream).await?;
+        return Ok(false);
+    }
+
+    let framing = response_framing(&method, status, &headers);
+    let response_len = relay_body(upstream, client, framing, &mut response).await?;
+    client.flush().await?;
+    context.record(key.reversed(), response, (response_head.len() as u64) + response_len).await;
+
+    let response_close = has_token(&headers, "connection", "close")
+        || (http10 && !has_token(&headers, "connection", "keep-alive"));
+    Ok(!request_close && !response_close && framing != BodyFraming::UntilClose)
+}
+
+/// 消息体的分帧方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyFraming {
+    None,
+    Length(u64),
+    Chunked,
+    /// 读到连接关闭为止（仅响应）
+    UntilClose,
+}
+
+fn request_framing(headers: &[(String, String)]) -> BodyFraming {
+    if has_token(headers, "transfer-encoding", "chunked") {
+        return BodyFraming::Chunked;
+    }
+    match header(headers, "content-length").and_then(|v| v.parse().ok()) {
+        Some(0) | None => BodyFraming::None,
+        Some(len) => BodyFraming::Length(len),
+    }
+}
+
+fn response_framing(method: &str, status: u16, headers: &[(String, String)]) -> BodyFraming {
+    if method.eq_ignore_ascii_case("HEAD") || status == 204 || status == 304 {
+        return BodyFraming::None;
+    }
+    if has_token(headers, "transfer-encoding", "chunked") {
+        return BodyFraming::Chunked;
+    }
+    match header(headers, "content-length").and_then(|v| v.parse().ok()) {
+        Some(0) => BodyFraming::None,
+        Some(len) => BodyFraming::Length(len),
+        None => BodyFraming::UntilClose,
+    }
+}
+
+/// 按分帧方式转发消息体，同时保留一份用于解析，返回转发的字节数
+async fn relay_body<R, W>(reader: &mut R, writer: &mut W, framing: BodyFraming, captured: &mut Vec<u8>) -> io::Result<u64>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    match framing {
+        BodyFraming::None => Ok(0),
+        BodyFraming::Length(len) => relay_exact(reader, writer, len, captured).await,
+        BodyFraming::UntilClose => {
+            let mut total = 0;
+            let mut buf = vec![0u8; 16 * 1024];
+            loop {
+                let n = reader.read(&mut buf).await?;
+                if n == 0 {
+                    return Ok(total);
+                }
+                writer.write_all(&buf[..n]).await?;
+                capture(captured, &buf[..n]);
+                total += n as u64;
+            }
+        }
+        BodyFraming::Chunked => {
+            let mut total = 0;
+            loop {
+                let line = read_line(reader).await?;
+                writer.write_all(&line).await?;
+                capture(captured, &line);
+                total += line.len() as u64;
+
+                let size_text = String::from_utf8_lossy(&line);
+                let size_text = size_text.split(';').next().unwrap_or_default().trim();
+                let size = u64::from_str_radix(size_text, 16)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "无效的分块长度"))?;
+                if size == 0 {
+                    // 尾部头部，以空行结束
+                    loop {
+                        let trailer = read_line(reader).await?;
+                        writer.write_all(&trailer).await?;
+                        capture(captured, &trailer);
+                        total += trailer.len() as u64;
+                        if trailer == b"\r\n" || trailer == b"\n" {
+                            return Ok(total);
+                        }
+                    }
+                }
+                // 分块数据及其后的 CRLF
+                total += relay_exact(reader, writer, size + 2, captured).await?;
+            }
+        }
+    }
+}
+
+async fn relay_exact<R, W>(reader: &mut R, writer: &mut W, len: u64, captured: &mut Vec<u8>) -> io::Result<u64>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut remaining = len;
+    let mut buf = vec![0u8; 16 * 1024];
+    while remaining > 0 {
+        let want = remaining.min(buf.len() as u64) as usize;
+        let n = reader.read(&mut buf[..want]).await?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "消息体不完整"));
+        }
+        writer.write_all(&buf[..n]).await?;
+        capture(captured, &buf[..n]);
+        remaining -= n as u64;
+    }
+    Ok(len)
+}
+
+/// 读取一行（包含换行符），连接关闭时返回错误
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut line = Vec::new();
+    let n = (&mut *reader).take(MAX_HEAD_SIZE as u64).read_until(b'\n', &mut line).await?;
+    if n == 0 || !line.ends_with(b"\n") {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "连接意外关闭"));
+    }
+    Ok(line)
+}
+
+/// 读取请求头或响应头（到空行为止），连接在消息之间关闭时返回 None
+async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut head = Vec::new();
+    loop {
+        let mut line = Vec::new();
+        let n = (&mut *reader).take(MAX_HEAD_SIZE as u64).read_until(b'\n', &mut line).await?;
+        if n == 0 {
+            return if head.is_empty() {
+                Ok(None)
+            } else {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "消息头不完整"))
+            };
+        }
+        // 忽略消息之间多余的空行
+        if head.is_empty() && (line == b"\r\n" || line == b"\n") {
+            continue;
+        }
+        head.extend_from_slice(&line);
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "消息头过大"));
+        }
+        if line == b"\r\n" || line == b"\n" {
+            return Ok(Some(head));
+        }
+    }
+}
+
+/// 解析消息头，返回起始行和头部列表
+fn parse_head(head: &[u8]) -> Result<(String, Vec<(String, String)>)> {
+    let text = String::from_utf8_lossy(head);
+    let mut lines = text.lines();
+    let start_line = lines
+        .next()
+        .filter(|line| !line.trim().is_empty())
+        .ok_or_else(|| anyhow!("空的消息头"))?
+        .trim()
+        .to_string();
+    let headers = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    Ok((start_line, headers))
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// 头部值（逗号分隔）中是否包含指定令牌
+fn has_token(headers: &[(String, String)], name: &str, token: &str) -> bool {
+    headers
+        .iter()
+        .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+        .any(|(_, v)| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+}
+
+fn capture(captured: &mut Vec<u8>, data: &[u8]) {
+    let room = MAX_CAPTURED_MESSAGE.saturating_sub(captured.len());
+    captured.extend_from_slice(&data[..data.len().min(room)]);
+}
+
+/// 拆分 "host:port"，支持 "[::1]:443" 形式的 IPv6 地址
+fn split_authority(authority: &str, default_port: u16) -> Result<(String, u16)> {
+    let invalid = || anyhow!("无效的目标地址: {}", authority);
+    if let Some(rest) = authority.strip_prefix('[') {
+        let (host, port) = rest.split_once(']').ok_or_else(invalid)?;
+        let port = match port.strip_prefix(':') {
+            Some(port) => port.parse().map_err(|_| invalid())?,
+            None => default_port,
+        };
+        return Ok((host.to_string(), port));
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Ok((host.to_string(), port.parse().map_err(|_| invalid())?)),
+        None if !authority.is_empty() => Ok((authority.to_string(), default_port)),
+        None => Err(invalid()),
+    }
+}
+
+/// 拆分代理请求中的绝对 URI，返回 (主机[:端口], 路径)
+fn split_absolute_uri(target: &str) -> Result<(String, String)> {
+    let rest = target
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow!("代理只接受 http:// 绝对地址或 CONNECT 请求: {}", target))?;
+    let (authority, path) = match rest.find('/') {
+        Some(pos) => (&rest[..pos], &rest[pos..]),
+        None => (rest, "/"),
+    };
+    Ok((authority.to_string(), path.to_string()))
+}
+
+/// 把请求行中的绝对 URI 改为路径，并去掉发给代理的 Proxy-* 头部
+fn rewrite_request_target(head: &[u8], path: &str) -> Result<Vec<u8>> {
+    let text = String::from_utf8_lossy(head);
+    let mut lines = text.split_inclusive('\n');
+    let start_line = lines.next().ok_or_else(|| anyhow!("空的消息头"))?;
+    let mut parts = start_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let version = parts.nth(1).unwrap_or("HTTP/1.1");
+
+    let mut out = format!("{} {} {}\r\n", method, path, version);
+    for line in lines {
+        if line.to_ascii_lowercase().starts_with("proxy-") {
+            continue;
+        }
+        out.push_str(line);
+    }
+    Ok(out.into_bytes())
+}
+
+fn now_us() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+/// 连接上游服务器使用的 TLS 配置
+fn upstream_client_config(verify: bool) -> Result<Arc<ClientConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| anyhow!("TLS 配置失败: {}", e))?;
+    let mut config = if verify {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        builder.with_root_certificates(roots).with_no_client_auth()
+    } else {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyServerCert(provider)))
+            .with_no_client_auth()
+    };
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// 不校验上游证书（仍校验握手签名），用于内网自签名证书的站点
+#[derive(Debug)]
+struct AcceptAnyServerCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyServerCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::DuplexStream;
+
+    /// 测试用的代理上下文，流水线不把数据包交给认证系统
+    fn test_context(ca: Option<Arc<LocalCa>>) -> (Arc<ProxyContext>, Arc<Mutex<CapturePipeline>>, thread::JoinHandle<()>) {
+        let session = Arc::new(CaptureSession::new(
+            "proxy-test".to_string(),
+            "proxy:test".to_string(),
+            CaptureOptions::default(),
+            String::new(),
+        ));
+        session.set_running(true);
+        let pipeline = Arc::new(Mutex::new(CapturePipeline::new(
+            "proxy:test".to_string(),
+            "proxy-test".to_string(),
+            false,
+        )));
+        let (messages, worker) = start_pipeline_worker(pipeline.clone());
+        let context = Arc::new(ProxyContext {
+            session,
+            messages,
+            ca,
+            upstream_tls: upstream_client_config(false).unwrap(),
+        });
+        (context, pipeline, worker)
+    }
+
+    /// 本地上游：依次读取请求（含 Content-Length 请求体）并返回预设的响应，之后关闭连接
+    async fn spawn_upstream(responses: Vec<&'static [u8]>) -> (SocketAddr, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut requests = Vec::new();
+            for response in responses {
+                let Some(mut request) = read_head(&mut stream).await.unwrap() else {
+                    break;
+                };
+                let (_, headers) = parse_head(&request).unwrap();
+                if let BodyFraming::Length(len) = request_framing(&headers) {
+                    let mut body = vec![0; len as usize];
+                    stream.read_exact(&mut body).await.unwrap();
+                    request.extend_from_slice(&body);
+                }
+                requests.push(String::from_utf8(request).unwrap());
+                stream.write_all(response).await.unwrap();
+                stream.flush().await.unwrap();
+            }
+            requests
+        });
+        (addr, handle)
+    }
+
+    /// 在内存管道上运行 handle_client，返回客户端一端
+    fn spawn_proxy(context: &Arc<ProxyContext>) -> (DuplexStream, tokio::task::JoinHandle<Result<()>>) {
+        let (client, proxy_side) = tokio::io::duplex(1 << 20);
+        let context = context.clone();
+        let handle = tokio::spawn(async move { handle_client(proxy_side, "127.0.0.1:40000".parse().unwrap(), context).await });
+        (client, handle)
+    }
+
+    /// 等待连接任务结束、流水线处理完剩余消息，返回 (请求数, 响应数)
+    async fn finish(context: Arc<ProxyContext>, pipeline: Arc<Mutex<CapturePipeline>>, worker: thread::JoinHandle<()>) -> (u64, u64) {
+        drop(context);
+        tokio::task::spawn_blocking(move || worker.join().unwrap()).await.unwrap();
+        let pipeline = pipeline.lock().unwrap();
+        (pipeline.stats.http_requests, pipeline.stats.http_responses)
+    }
+
+    #[tokio::test]
+    async fn test_plain_keep_alive_and_response_framing() {
+        let responses: Vec<&'static [u8]> = vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+            // HEAD 和 204/304 没有消息体，即使带有 Content-Length
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n",
+            b"HTTP/1.1 204 No Content\r\n\r\n",
+            b"HTTP/1.1 304 Not Modified\r\nContent-Length: 5\r\n\r\n",
+            // 1xx 中间响应转发后继续等待最终响应
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+            // 没有长度的响应读到连接关闭为止，之后连接不再复用
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nuntil close",
+        ];
+        let expected: Vec<u8> = responses.concat();
+        let (upstream_addr, upstream) = spawn_upstream(responses).await;
+        let (context, pipeline, worker) = test_context(None);
+        let (mut client, proxy) = spawn_proxy(&context);
+
+        let base = format!("http://{}", upstream_addr);
+        let requests = format!(
+            "GET {base}/a HTTP/1.1\r\nHost: bi.example.com\r\nProxy-Connection: keep-alive\r\n\r\n\
+             HEAD {base}/b HTTP/1.1\r\nHost: bi.example.com\r\n\r\n\
+             DELETE {base}/c HTTP/1.1\r\nHost: bi.example.com\r\n\r\n\
+             GET {base}/d HTTP/1.1\r\nHost: bi.example.com\r\nIf-None-Match: \"v1\"\r\n\r\n\
+             POST {base}/e HTTP/1.1\r\nHost: bi.example.com\r\nExpect: 100-continue\r\nContent-Length: 4\r\n\r\ndata\
+             GET {base}/f HTTP/1.1\r\nHost: bi.example.com\r\n\r\n"
+        );
+        client.write_all(requests.as_bytes()).await.unwrap();
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&received), String::from_utf8_lossy(&expected));
+        proxy.await.unwrap().unwrap();
+
+        let forwarded = upstream.await.unwrap();
+        assert_eq!(forwarded.len(), 6);
+        assert!(forwarded[0].starts_with("GET /a HTTP/1.1\r\n"));
+        assert!(!forwarded[0].contains("Proxy-Connection"));
+        assert!(forwarded[4].ends_with("\r\n\r\ndata"));
+        assert_eq!(finish(context, pipeline, worker).await, (6, 6));
+    }
+
+    #[tokio::test]
+    async fn test_connection_close_ends_keep_alive() {
+        let (upstream_addr, upstream) = spawn_upstream(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        ])
+        .await;
+        let (context, pipeline, worker) = test_context(None);
+        let (mut client, proxy) = spawn_proxy(&context);
+
+        // 客户端连接保持打开，代理在 Connection: close 的事务之后结束
+        let requests = format!(
+            "GET http://{0}/a HTTP/1.1\r\nConnection: close\r\n\r\nGET http://{0}/b HTTP/1.1\r\n\r\n",
+            upstream_addr
+        );
+        client.write_all(requests.as_bytes()).await.unwrap();
+        proxy.await.unwrap().unwrap();
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+        assert_eq!(upstream.await.unwrap().len(), 1);
+        assert_eq!(finish(context, pipeline, worker).await, (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_switches_to_raw_relay() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = listener.local_addr().unwrap();
+        let upstream = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            read_head(&mut stream).await.unwrap().unwrap();
+            stream
+                .write_all(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n")
+                .await
+                .unwrap();
+            stream.flush().await.unwrap();
+            let mut ping = [0u8; 4];
+            stream.read_exact(&mut ping).await.unwrap();
+            assert_eq!(&ping, b"ping");
+            stream.write_all(b"pong").await.unwrap();
+            stream.flush().await.unwrap();
+            // 等客户端关闭后再断开，代理两个方向都正常结束
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).await.unwrap();
+            assert!(rest.is_empty());
+        });
+        let (context, pipeline, worker) = test_context(None);
+        let (mut client, proxy) = spawn_proxy(&context);
+
+        let request = format!(
+            "GET http://{}/ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n",
+            upstream_addr
+        );
+        client.write_all(request.as_bytes()).await.unwrap();
+        let switching = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n";
+        let mut head = vec![0u8; switching.len()];
+        client.read_exact(&mut head).await.unwrap();
+        assert_eq!(head, switching);
+
+        client.write_all(b"ping").await.unwrap();
+        let mut pong = [0u8; 4];
+        client.read_exact(&mut pong).await.unwrap();
+        assert_eq!(&pong, b"pong");
+        client.shutdown().await.unwrap();
+
+        upstream.await.unwrap();
+        proxy.await.unwrap().unwrap();
+        assert_eq!(finish(context, pipeline, worker).await, (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_connect_intercepts_tls() {
+        let dir = std::env::temp_dir().join(format!("proxy-mitm-test-{}", std::process::id()));
+        let ca = Arc::new(LocalCa::load_or_create_in(&dir).unwrap());
+
+        // 上游 HTTPS 服务器，证书由同一根证书签发（代理不校验上游证书）
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = listener.local_addr().unwrap();
+        let acceptor = TlsAcceptor::from(ca.server_config("127.0.0.1").unwrap());
+        let upstream = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(acceptor.accept(stream).await.unwrap());
+            let request = read_head(&mut stream).await.unwrap().unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+            stream.flush().await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let (context, pipeline, worker) = test_context(Some(ca));
+        let (mut client, proxy) = spawn_proxy(&context);
+        let connect = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", upstream_addr);
+        client.write_all(connect.as_bytes()).await.unwrap();
+        let established = b"HTTP/1.1 200 Connection Established\r\n\r\n";
+        let mut reply = vec![0u8; established.len()];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, established);
+
+        // 客户端与代理签发的站点证书握手
+        let server_name = ServerName::try_from("127.0.0.1").unwrap();
+        let mut tls = TlsConnector::from(upstream_client_config(false).unwrap())
+            .connect(server_name, client)
+            .await
+            .unwrap();
+        tls.write_all(b"GET /secure HTTP/1.1\r\nHost: portal.example.com\r\n\r\n").await.unwrap();
+        tls.flush().await.unwrap();
+        let expected = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+        let mut response = vec![0u8; expected.len()];
+        tls.read_exact(&mut response).await.unwrap();
+        assert_eq!(response, expected);
+
+        assert!(upstream.await.unwrap().starts_with("GET /secure HTTP/1.1\r\n"));
+        proxy.await.unwrap().unwrap();
+        assert_eq!(finish(context, pipeline, worker).await, (1, 1));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_read_head_limits() {
+        // 连接在消息之间关闭
+        let mut reader = BufReader::new(&b"\r\n"[..]);
+        assert!(read_head(&mut reader).await.unwrap().is_none());
+
+        let mut reader = BufReader::new(&b"GET / HTTP/1.1\r\nHost: a"[..]);
+        assert_eq!(read_head(&mut reader).await.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // 单行过长和头部总长过大都被拒绝
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEAD_SIZE + 1));
+        let mut reader = BufReader::new(long_line.as_bytes());
+        assert_eq!(read_head(&mut reader).await.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X-Pad: 0123456789abcdef\r\n".repeat(MAX_HEAD_SIZE / 16));
+        let mut reader = BufReader::new(many_headers.as_bytes());
+        assert_eq!(read_head(&mut reader).await.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // 过大的请求头使代理直接关闭连接
+        let (context, pipeline, worker) = test_context(None);
+        let (mut client, proxy) = spawn_proxy(&context);
+        client.write_all(many_headers.as_bytes()).await.unwrap();
+        assert!(proxy.await.unwrap().is_err());
+        assert_eq!(finish(context, pipeline, worker).await, (0, 0));
+    }
+
+    #[test]
+    fn test_response_framing() {
+        let length = vec![("Content-Length".to_string(), "5".to_string())];
+        let chunked = vec![("Transfer-Encoding".to_string(), "gzip, chunked".to_string())];
+        assert_eq!(response_framing("GET", 200, &length), BodyFraming::Length(5));
+        assert_eq!(response_framing("HEAD", 200, &length), BodyFraming::None);
+        assert_eq!(response_framing("GET", 204, &length), BodyFraming::None);
+        assert_eq!(response_framing("GET", 304, &chunked), BodyFraming::None);
+        assert_eq!(response_framing("GET", 200, &chunked), BodyFraming::Chunked);
+        assert_eq!(response_framing("GET", 200, &[]), BodyFraming::UntilClose);
+        assert_eq!(request_framing(&[]), BodyFraming::None);
+        assert_eq!(request_framing(&length), BodyFraming::Length(5));
+    }
+
+    #[tokio::test]
+    async fn test_relay_chunked_body_and_rewrite() {
+        let body = b"4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\nX-Trailer: 1\r\n\r\nGET /next";
+        let mut reader = BufReader::new(&body[..]);
+        let mut forwarded = Vec::new();
+        let mut captured = Vec::new();
+        relay_body(&mut reader, &mut forwarded, BodyFraming::Chunked, &mut captured).await.unwrap();
+        assert_eq!(forwarded, b"4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\nX-Trailer: 1\r\n\r\n");
+        assert_eq!(captured, forwarded);
+
+        let head = b"GET http://portal.example.com:8080/api/login?x=1 HTTP/1.1\r\nHost: portal.example.com:8080\r\nProxy-Connection: keep-alive\r\n\r\n";
+        let (authority, path) = split_absolute_uri("http://portal.example.com:8080/api/login?x=1").unwrap();
+        assert_eq!(split_authority(&authority, 80).unwrap(), ("portal.example.com".to_string(), 8080));
+        let rewritten = rewrite_request_target(head, &path).unwrap();
+        assert_eq!(
+            rewritten,
+            b"GET /api/login?x=1 HTTP/1.1\r\nHost: portal.example.com:8080\r\n\r\n".to_vec()
+        );
+        assert_eq!(split_authority("[::1]:443", 80).unwrap(), ("::1".to_string(), 443));
+    }
+}
@@ -0,0 +1,729 @@
+use log::{debug, warn};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+/// 单个方向流的最大缓冲字节数（超过后强制输出并重置）
+const MAX_STREAM_BUFFER: usize = 16 * 1024 * 1024;
+/// 单个方向流乱序段的最大缓存字节数
+const MAX_OUT_OF_ORDER_BYTES: usize = 4 * 1024 * 1024;
+/// 同时跟踪的最大流数量
+const MAX_FLOWS: usize = 4096;
+/// 所有流缓冲（按序数据和乱序段）的总字节上限，超过后移除最久未活动的流
+const MAX_TOTAL_BUFFERED: usize = 256 * 1024 * 1024;
+/// 流空闲超时时间（微秒）
+const FLOW_IDLE_TIMEOUT_US: u64 = 120 * 1_000_000;
+/// 每个连接最多记录的待响应请求数量（HTTP/1.1 管线化）
+const MAX_PENDING_REQUESTS: usize = 64;
+
+/// 单向流标识（源地址 -> 目的地址）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlowKey {
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+}
+
+impl FlowKey {
+    pub fn new(src: SocketAddr, dst: SocketAddr) -> Self {
+        Self { src, dst }
+    }
+
+    /// 反方向的流标识
+    pub fn reversed(&self) -> Self {
+        Self { src: self.dst, dst: self.src }
+    }
+}
+
+/// TCP 段中与重组相关的信息
+#[derive(Debug, Clone, Copy)]
+pub struct TcpSegment<'a> {
+    pub seq: u32,
+    pub syn: bool,
+    pub fin: bool,
+    pub rst: bool,
+    pub payload: &'a [u8],
+    /// 捕获时间戳（微秒）
+    pub timestamp_us: u64,
+}
+
+/// 重组完成的完整 HTTP 消息
+#[derive(Debug, Clone)]
+pub struct ReassembledMessage {
+    pub key: FlowKey,
+    pub data: Vec<u8>,
+    /// 消息第一个字节到达的时间戳（微秒）
+    pub timestamp_us: u64,
+    /// 消息是否因为连接关闭或缓冲上限而被截断
+    pub truncated: bool,
+}
+
+/// HTTP 消息长度判定结果
+#[derive(Debug, PartialEq, Eq)]
+enum MessageLength {
+    /// 头部或消息体尚未接收完整
+    Incomplete,
+    /// 完整消息的总字节数
+    Complete(usize),
+    /// 消息体持续到连接关闭
+    UntilClose,
+}
+
+/// 单个方向的流状态
+#[derive(Debug)]
+pub(super) struct StreamState {
+    /// 下一个期望的序列号，None 表示尚未同步
+    next_seq: Option<u32>,
+    /// 已按序接收但尚未组成完整消息的数据
+    pub(super) buffer: Vec<u8>,
+    /// 缓冲区首字节的到达时间
+    buffer_timestamp_us: u64,
+    /// 乱序到达的段：(序列号, 数据)
+    out_of_order: Vec<(u32, Vec<u8>)>,
+    out_of_order_bytes: usize,
+    last_seen_us: u64,
+    closed: bool,
+}
+
+impl StreamState {
+    pub(super) fn new(now_us: u64) -> Self {
+        Self {
+            next_seq: None,
+            buffer: Vec::new(),
+            buffer_timestamp_us: now_us,
+            out_of_order: Vec::new(),
+            out_of_order_bytes: 0,
+            last_seen_us: now_us,
+            closed: false,
+        }
+    }
+
+    /// 当前占用的缓冲字节数
+    fn buffered_bytes(&self) -> usize {
+        self.buffer.len() + self.out_of_order_bytes
+    }
+
+    /// 追加按序数据
+    fn append(&mut self, data: &[u8], timestamp_us: u64) {
+        if data.is_empty() {
+            return;
+        }
+        if self.buffer.is_empty() {
+            self.buffer_timestamp_us = timestamp_us;
+        }
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// 接收一个 TCP 段，返回该段是否为重复的重传段
+    pub(super) fn accept(&mut self, segment: &TcpSegment) -> bool {
+        let mut seq = segment.seq;
+        if segment.syn {
+            // SYN 占用一个序列号
+            seq = seq.wrapping_add(1);
+            self.next_seq = Some(seq);
+        }
+
+        let payload = segment.payload;
+        if payload.is_empty() {
+            return false;
+        }
+
+        let next_seq = match self.next_seq {
+            Some(next) => next,
+            None => {
+                // 中途加入的连接，以第一个数据段作为同步点
+                self.next_seq = Some(seq);
+                seq
+            }
+        };
+
+        let offset = seq.wrapping_sub(next_seq) as i32;
+        if offset < 0 {
+            // 与已接收数据重叠（重传）
+            let overlap = offset.unsigned_abs() as usize;
+            if overlap >= payload.len() {
+                return true;
+            }
+            self.append(&payload[overlap..], segment.timestamp_us);
+            self.next_seq = Some(next_seq.wrapping_add((payload.len() - overlap) as u32));
+            self.drain_out_of_order();
+            return false;
+        }
+
+        if offset == 0 {
+            self.append(payload, segment.timestamp_us);
+            self.next_seq = Some(next_seq.wrapping_add(payload.len() as u32));
+            self.drain_out_of_order();
+            return false;
+        }
+
+        // 未来的段，先缓存起来
+        if self.out_of_order.iter().any(|(s, d)| *s == seq && d.len() >= payload.len()) {
+            return true;
+        }
+        if self.out_of_order_bytes + payload.len() > MAX_OUT_OF_ORDER_BYTES {
+            warn!("乱序缓存超过上限，跳过缺失的数据");
+            self.skip_gap();
+        }
+        self.out_of_order_bytes += payload.len();
+        self.out_of_order.push((seq, payload.to_vec()));
+        self.drain_out_of_order();
+        false
+    }
+
+    /// 把已经可以衔接上的乱序段移入缓冲区
+    fn drain_out_of_order(&mut self) {
+        while let Some(next_seq) = self.next_seq {
+            // 丢弃已被完全覆盖的段
+            self.out_of_order.retain(|(seq, data)| {
+                let end = seq.wrapping_add(data.len() as u32);
+                (end.wrapping_sub(next_seq) as i32) > 0
+            });
+
+            let Some(index) = self
+                .out_of_order
+                .iter()
+                .position(|(seq, _)| (seq.wrapping_sub(next_seq) as i32) <= 0)
+            else {
+                break;
+            };
+
+            let (seq, data) = self.out_of_order.swap_remove(index);
+            let overlap = next_seq.wrapping_sub(seq) as usize;
+            let timestamp_us = self.last_seen_us;
+            self.append(&data[overlap..], timestamp_us);
+            self.next_seq = Some(next_seq.wrapping_add((data.len() - overlap) as u32));
+        }
+        self.out_of_order_bytes = self.out_of_order.iter().map(|(_, d)| d.len()).sum();
+    }
+
+    /// 放弃等待缺失的数据，跳到最早的乱序段继续
+    fn skip_gap(&mut self) {
+        let Some(next_seq) = self.next_seq else { return };
+        if let Some(earliest) = self
+            .out_of_order
+            .iter()
+            .map(|(seq, _)| *seq)
+            .min_by_key(|seq| seq.wrapping_sub(next_seq))
+        {
+            // 缺口之前的缓冲数据已经无法组成完整消息
+            self.buffer.clear();
+            self.next_seq = Some(earliest);
+            self.drain_out_of_order();
+        }
+    }
+}
+
+/// TCP 流重组器
+///
+/// 按四元组分别跟踪两个方向的序列号，处理乱序、重传以及 FIN/RST，
+/// 在缓冲数据构成完整的 HTTP 消息时输出。
+#[derive(Debug)]
+pub struct TcpReassembler {
+    streams: HashMap<FlowKey, StreamState>,
+    /// 以响应方向为键，按顺序记录待响应的请求是否为 HEAD（HEAD 响应没有消息体）
+    pending_head: HashMap<FlowKey, VecDeque<bool>>,
+    retransmissions: u64,
+    /// 所有流当前占用的缓冲字节数
+    buffered_bytes: usize,
+    /// 总缓冲字节上限
+    buffer_budget: usize,
+}
+
+impl Default for TcpReassembler {
+    fn default() -> Self {
+        Self {
+            streams: HashMap::new(),
+            pending_head: HashMap::new(),
+            retransmissions: 0,
+            buffered_bytes: 0,
+            buffer_budget: MAX_TOTAL_BUFFERED,
+        }
+    }
+}
+
+impl TcpReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 所有流当前占用的缓冲字节数
+    #[cfg(test)]
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    /// 当前跟踪的单向流数量
+    #[cfg(test)]
+    pub fn flow_count(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// 累计丢弃的重传段数量
+    #[cfg(test)]
+    pub fn retransmissions(&self) -> u64 {
+        self.retransmissions
+    }
+
+    /// 处理一个 TCP 段，返回因此而完整的 HTTP 消息
+    pub fn process_segment(&mut self, key: FlowKey, segment: TcpSegment) -> Vec<ReassembledMessage> {
+        let now_us = segment.timestamp_us;
+        self.evict_idle(now_us);
+
+        if !self.streams.contains_key(&key) && self.streams.len() >= MAX_FLOWS {
+            self.evict_oldest();
+        }
+
+        let stream = self
+            .streams
+            .entry(key)
+            .or_insert_with(|| StreamState::new(now_us));
+        stream.last_seen_us = now_us;
+        let buffered_before = stream.buffered_bytes();
+
+        if stream.accept(&segment) {
+            self.retransmissions += 1;
+        }
+
+        let mut messages = Vec::new();
+        let closing = segment.fin || segment.rst;
+        if let Some(stream) = self.streams.get_mut(&key) {
+            if closing {
+                stream.closed = true;
+            }
+            extract_messages(key, stream, &mut self.pending_head, &mut messages);
+            self.buffered_bytes = (self.buffered_bytes + stream.buffered_bytes()).saturating_sub(buffered_before);
+        }
+
+        if segment.rst {
+            // RST 会同时终止两个方向
+            for k in [key, key.reversed()] {
+                if let Some(mut stream) = self.remove_stream(&k) {
+                    stream.closed = true;
+                    extract_messages(k, &mut stream, &mut self.pending_head, &mut messages);
+                }
+                self.pending_head.remove(&k);
+            }
+        } else if closing {
+            let both_closed = self
+                .streams
+                .get(&key.reversed())
+                .map(|s| s.closed)
+                .unwrap_or(true);
+            if both_closed {
+                for k in [key, key.reversed()] {
+                    self.remove_stream(&k);
+                    self.pending_head.remove(&k);
+                }
+            }
+        }
+
+        self.enforce_buffer_budget();
+        messages
+    }
+
+    /// 输入结束时输出所有流中剩余的消息，并清空全部状态
+    ///
+    /// 与连接关闭一样处理：消息体持续到连接关闭的响应视为完整，其余不完整的消息标记为截断。
+    pub fn flush(&mut self) -> Vec<ReassembledMessage> {
+        let mut streams: Vec<_> = self.streams.drain().collect();
+        // 按缓冲数据的到达顺序输出，使请求先于对应的响应
+        streams.sort_by_key(|(_, s)| s.buffer_timestamp_us);
+        let mut messages = Vec::new();
+        for (key, mut stream) in streams {
+            stream.closed = true;
+            extract_messages(key, &mut stream, &mut self.pending_head, &mut messages);
+        }
+        self.pending_head.clear();
+        self.buffered_bytes = 0;
+        messages
+    }
+
+    fn remove_stream(&mut self, key: &FlowKey) -> Option<StreamState> {
+        let stream = self.streams.remove(key)?;
+        self.buffered_bytes = self.buffered_bytes.saturating_sub(stream.buffered_bytes());
+        Some(stream)
+    }
+
+    /// 总缓冲超过上限时，按最久未活动的顺序移除有缓冲数据的流
+    fn enforce_buffer_budget(&mut self) {
+        let mut evicted = 0;
+        while self.buffered_bytes > self.buffer_budget {
+            let Some(oldest) = self
+                .streams
+                .iter()
+                .filter(|(_, s)| s.buffered_bytes() > 0)
+                .min_by_key(|(_, s)| s.last_seen_us)
+                .map(|(k, _)| *k)
+            else {
+                break;
+            };
+            self.remove_stream(&oldest);
+            evicted += 1;
+        }
+        if evicted > 0 {
+            warn!("TCP流缓冲总量超过上限 {} 字节，移除了 {} 个最旧的流", self.buffer_budget, evicted);
+            self.retain_pending_head();
+        }
+    }
+
+    /// 清理空闲超时的流
+    fn evict_idle(&mut self, now_us: u64) {
+        let before = self.streams.len();
+        let buffered_bytes = &mut self.buffered_bytes;
+        self.streams.retain(|_, s| {
+            let keep = now_us.saturating_sub(s.last_seen_us) < FLOW_IDLE_TIMEOUT_US;
+            if !keep {
+                *buffered_bytes = buffered_bytes.saturating_sub(s.buffered_bytes());
+            }
+            keep
+        });
+        let evicted = before - self.streams.len();
+        if evicted > 0 {
+            debug!("清理了 {} 个空闲的TCP流", evicted);
+            self.retain_pending_head();
+        }
+    }
+
+    /// 移除两个方向的流都已不存在的连接的请求记录
+    fn retain_pending_head(&mut self) {
+        let streams = &self.streams;
+        self.pending_head
+            .retain(|k, _| streams.contains_key(k) || streams.contains_key(&k.reversed()));
+    }
+
+    /// 流数量达到上限时移除最久未活动的流
+    fn evict_oldest(&mut self) {
+        if let Some(oldest) = self
+            .streams
+            .iter()
+            .min_by_key(|(_, s)| s.last_seen_us)
+            .map(|(k, _)| *k)
+        {
+            warn!("TCP流数量达到上限 {}，移除最旧的流", MAX_FLOWS);
+            self.remove_stream(&oldest);
+            self.retain_pending_head();
+        }
+    }
+}
+
+/// 从流缓冲区中取出所有完整的 HTTP 消息
+///
+/// `pending_head` 用于判断响应是否对应 HEAD 请求，同时记录本方向取出的请求。
+fn extract_messages(
+    key: FlowKey,
+    stream: &mut StreamState,
+    pending_head: &mut HashMap<FlowKey, VecDeque<bool>>,
+    out: &mut Vec<ReassembledMessage>,
+) {
+    loop {
+        if stream.buffer.is_empty() {
+            return;
+        }
+
+        // 缓冲区不是以 HTTP 起始行开头时，尝试重新同步
+        if !resync_to_message_start(&mut stream.buffer) {
+            return;
+        }
+
+        let head_response = pending_head
+            .get(&key)
+            .and_then(|queue| queue.front())
+            .copied()
+            .unwrap_or(false);
+        match message_length(&stream.buffer, head_response) {
+            MessageLength::Complete(len) => {
+                let rest = stream.buffer.split_off(len);
+                let data = std::mem::replace(&mut stream.buffer, rest);
+                track_pending_head(key, &data, pending_head);
+                out.push(ReassembledMessage {
+                    key,
+                    data,
+                    timestamp_us: stream.buffer_timestamp_us,
+                    truncated: false,
+                });
+                stream.buffer_timestamp_us = stream.last_seen_us;
+            }
+            MessageLength::UntilClose | MessageLength::Incomplete
+                if stream.closed || stream.buffer.len() > MAX_STREAM_BUFFER =>
+            {
+                let truncated = !(stream.closed
+                    && message_length(&stream.buffer, head_response) == MessageLength::UntilClose);
+                if stream.buffer.len() > MAX_STREAM_BUFFER {
+                    warn!("TCP流缓冲超过上限 {} 字节，强制输出", MAX_STREAM_BUFFER);
+                }
+                let data = std::mem::take(&mut stream.buffer);
+                track_pending_head(key, &data, pending_head);
+                out.push(ReassembledMessage {
+                    key,
+                    data,
+                    timestamp_us: stream.buffer_timestamp_us,
+                    truncated,
+                });
+                return;
+            }
+            _ => return,
+        }
+    }
+}
+
+/// 请求记录到反方向的队列中；最终响应（非 1xx，或 101）结束队首的请求
+fn track_pending_head(key: FlowKey, message: &[u8], pending_head: &mut HashMap<FlowKey, VecDeque<bool>>) {
+    if message.starts_with(b"HTTP/") {
+        let status = response_status(message);
+        if !(100..200).contains(&status) || status == 101 {
+            if let Some(queue) = pending_head.get_mut(&key) {
+                queue.pop_front();
+            }
+        }
+        return;
+    }
+
+    let queue = pending_head.entry(key.reversed()).or_default();
+    if queue.len() >= MAX_PENDING_REQUESTS {
+        queue.pop_front();
+    }
+    queue.push_back(message.starts_with(b"HEAD "));
+}
+
+/// 响应起始行中的状态码，无法解析时为 0
+fn response_status(message: &[u8]) -> u16 {
+    let line_end = find_subslice(message, b"\r\n").unwrap_or(message.len());
+    String::from_utf8_lossy(&message[..line_end])
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .unwrap_or(0)
+}
+
+/// 丢弃缓冲区开头不属于 HTTP 消息的字节，返回是否找到消息起点
+fn resync_to_message_start(buffer: &mut Vec<u8>) -> bool {
+    if super::detect_http_packet_type(buffer).is_some() {
+        return true;
+    }
+
+    // 在行首寻找下一个 HTTP 起始行
+    let mut search_from = 0;
+    while let Some(pos) = find_subslice(&buffer[search_from..], b"\r\n") {
+        let start = search_from + pos + 2;
+        if super::detect_http_packet_type(&buffer[start..]).is_some() {
+            debug!("TCP流重新同步，丢弃 {} 字节", start);
+            buffer.drain(..start);
+            return true;
+        }
+        search_from = start;
+    }
+
+    // 保留末尾可能是起始行前缀的少量数据
+    if buffer.len() > 16 {
+        let keep_from = buffer.len() - 16;
+        buffer.drain(..keep_from);
+    }
+    false
+}
+
+/// 根据 HTTP 头部判断完整消息的长度，`head_response` 表示这是 HEAD 请求的响应
+fn message_length(buffer: &[u8], head_response: bool) -> MessageLength {
+    let Some(header_end) = find_subslice(buffer, b"\r\n\r\n") else {
+        return MessageLength::Incomplete;
+    };
+    let body_start = header_end + 4;
+    let head = String::from_utf8_lossy(&buffer[..header_end]);
+    let mut lines = head.split("\r\n");
+    let start_line = lines.next().unwrap_or_default();
+    let is_response = start_line.starts_with("HTTP/");
+
+    let mut content_length = None;
+    let mut chunked = false;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<usize>().ok();
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.to_ascii_lowercase().contains("chunked");
+            }
+        }
+    }
+
+    if is_response {
+        let status = start_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse::<u16>().ok())
+            .unwrap_or(0);
+        // HEAD 响应的 Content-Length 描述的是 GET 时的消息体，实际没有消息体
+        if (100..200).contains(&status) || status == 204 || status == 304 || head_response {
+            return MessageLength::Complete(body_start);
+        }
+    }
+
+    if chunked {
+        return match chunked_body_length(&buffer[body_start..]) {
+            Some(len) => MessageLength::Complete(body_start + len),
+            None => MessageLength::Incomplete,
+        };
+    }
+
+    match content_length {
+        Some(len) if buffer.len() >= body_start + len => MessageLength::Complete(body_start + len),
+        Some(_) => MessageLength::Incomplete,
+        None if is_response => MessageLength::UntilClose,
+        None => MessageLength::Complete(body_start),
+    }
+}
+
+/// 计算分块编码消息体（包括结尾的 trailer）的总长度
+fn chunked_body_length(body: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    loop {
+        let line_end = pos + find_subslice(&body[pos..], b"\r\n")?;
+        let size_line = String::from_utf8_lossy(&body[pos..line_end]);
+        let size_str = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_str, 16).ok()?;
+        pos = line_end + 2;
+
+        if size == 0 {
+            // 跳过 trailer，直到空行
+            loop {
+                let end = pos + find_subslice(&body[pos..], b"\r\n")?;
+                if end == pos {
+                    return Some(pos + 2);
+                }
+                pos = end + 2;
+            }
+        }
+
+        if body.len() < pos + size + 2 {
+            return None;
+        }
+        pos += size + 2;
+    }
+}
+
+pub(super) fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> FlowKey {
+        FlowKey::new("10.0.0.1:50000".parse().unwrap(), "10.0.0.2:80".parse().unwrap())
+    }
+
+    fn segment(seq: u32, payload: &[u8]) -> TcpSegment<'_> {
+        TcpSegment { seq, syn: false, fin: false, rst: false, payload, timestamp_us: 1 }
+    }
+
+    #[test]
+    fn test_request_split_across_segments() {
+        let mut reassembler = TcpReassembler::new();
+        let first = b"POST /api HTTP/1.1\r\nContent-Length: 10\r\n\r\n01234";
+        let second = b"56789";
+
+        assert!(reassembler.process_segment(key(), segment(1000, first)).is_empty());
+        let messages = reassembler.process_segment(key(), segment(1000 + first.len() as u32, second));
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].data.ends_with(b"0123456789"));
+        assert!(!messages[0].truncated);
+    }
+
+    #[test]
+    fn test_out_of_order_and_retransmission() {
+        let mut reassembler = TcpReassembler::new();
+        let first = b"GET /a HTTP/1.1\r\nHost: x\r\n";
+        let second = b"\r\n";
+
+        let mut syn = segment(999, b"");
+        syn.syn = true;
+        assert!(reassembler.process_segment(key(), syn).is_empty());
+
+        let later = 1000 + first.len() as u32;
+        assert!(reassembler.process_segment(key(), segment(later, second)).is_empty());
+        let messages = reassembler.process_segment(key(), segment(1000, first));
+        assert_eq!(messages.len(), 1);
+
+        // 重传的段不会产生新的消息
+        assert!(reassembler.process_segment(key(), segment(1000, first)).is_empty());
+        assert_eq!(reassembler.retransmissions(), 1);
+    }
+
+    #[test]
+    fn test_chunked_and_close_delimited_responses() {
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        assert_eq!(message_length(chunked, false), MessageLength::Complete(chunked.len()));
+
+        let close = b"HTTP/1.0 200 OK\r\n\r\nbody";
+        assert_eq!(message_length(close, false), MessageLength::UntilClose);
+
+        let mut reassembler = TcpReassembler::new();
+        let mut fin = segment(1, close);
+        fin.fin = true;
+        let messages = reassembler.process_segment(key(), fin);
+        assert_eq!(messages.len(), 1);
+        assert!(!messages[0].truncated);
+    }
+
+    #[test]
+    fn test_head_response_has_no_body() {
+        let mut reassembler = TcpReassembler::new();
+        let requests = b"HEAD /file HTTP/1.1\r\nHost: x\r\n\r\nGET /file HTTP/1.1\r\nHost: x\r\n\r\n";
+        assert_eq!(reassembler.process_segment(key(), segment(1, requests)).len(), 2);
+
+        // HEAD 的响应带有 Content-Length 但没有消息体，不会阻塞后面 GET 的响应
+        let responses = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let messages = reassembler.process_segment(key().reversed(), segment(1, responses));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].data, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n");
+        assert!(messages[1].data.ends_with(b"hello"));
+        assert!(reassembler.pending_head.get(&key().reversed()).is_none_or(|q| q.is_empty()));
+    }
+
+    #[test]
+    fn test_total_buffer_budget_evicts_oldest_flows() {
+        let mut reassembler = TcpReassembler::new();
+        reassembler.buffer_budget = 100;
+        let partial = b"POST /upload HTTP/1.1\r\nContent-Length: 1000\r\n\r\n0123456789";
+
+        let flows: Vec<FlowKey> = (0..3)
+            .map(|i| FlowKey::new(format!("10.0.0.1:{}", 50000 + i).parse().unwrap(), "10.0.0.2:80".parse().unwrap()))
+            .collect();
+        for (i, flow) in flows.iter().enumerate() {
+            let mut segment = segment(1, partial);
+            segment.timestamp_us = i as u64;
+            assert!(reassembler.process_segment(*flow, segment).is_empty());
+        }
+
+        // 任意两个流的缓冲之和都超过上限，每加入一个新流就移除最旧的流
+        assert_eq!(reassembler.flow_count(), 1);
+        assert_eq!(reassembler.buffered_bytes(), partial.len());
+        let mut rest = segment(1 + partial.len() as u32, b"");
+        rest.fin = true;
+        rest.timestamp_us = 3;
+        assert_eq!(reassembler.process_segment(flows[2], rest).len(), 1);
+        assert_eq!(reassembler.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_flush_outputs_buffered_messages() {
+        let mut reassembler = TcpReassembler::new();
+        let request = b"POST /upload HTTP/1.1\r\nContent-Length: 10\r\n\r\n01234";
+        let response = b"HTTP/1.0 200 OK\r\n\r\nbody";
+        let mut first = segment(1, request);
+        first.timestamp_us = 1;
+        let mut second = segment(1, response);
+        second.timestamp_us = 2;
+        assert!(reassembler.process_segment(key(), first).is_empty());
+        assert!(reassembler.process_segment(key().reversed(), second).is_empty());
+
+        // 消息体持续到连接关闭的响应是完整的，缺少消息体的请求被标记为截断
+        let messages = reassembler.flush();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].key, key());
+        assert!(messages[0].truncated);
+        assert_eq!(messages[1].data, response);
+        assert!(!messages[1].truncated);
+        assert_eq!(reassembler.flow_count(), 0);
+        assert_eq!(reassembler.buffered_bytes(), 0);
+    }
+}
@@ -0,0 +1,393 @@
+use super::stats::CaptureStats;
+use super::stop::{CaptureSummary, StopReason};
+use super::{send_status_update, CaptureOptions, CaptureStatus};
+use anyhow::{anyhow, Result};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use log::{debug, info};
+use once_cell::sync::OnceCell;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+/// 所有捕获会话，以会话ID为键
+static SESSIONS: OnceCell<DashMap<String, Arc<CaptureSession>>> = OnceCell::new();
+
+/// 已结束的会话最多保留的数量，保留最近的会话以便查询最终状态和统计
+const MAX_FINISHED_SESSIONS: usize = 16;
+
+/// 单个捕获会话：一个网络设备对应一个捕获线程
+pub struct CaptureSession {
+    id: String,
+    running: Arc<AtomicBool>,
+    /// 停止信号，供异步任务（代理）在停止时立即退出，不必轮询运行标志
+    stopped: watch::Sender<bool>,
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+    status: Mutex<CaptureStatus>,
+    /// 本次捕获期间已获取到 Token 的系统
+    acquired_tokens: Mutex<HashSet<String>>,
+}
+
+impl CaptureSession {
+    pub fn new(id: String, device_name: String, options: CaptureOptions, filter: String) -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            stopped: watch::Sender::new(false),
+            thread: Mutex::new(None),
+            status: Mutex::new(CaptureStatus {
+                session_id: id.clone(),
+                running: false,
+                message: "捕获会话已创建".to_string(),
+                device_name,
+                start_time: 0,
+                options,
+                filter,
+                stats: CaptureStats {
+                    session_id: id.clone(),
+                    ..Default::default()
+                },
+            }),
+            acquired_tokens: Mutex::new(HashSet::new()),
+            id,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 捕获线程是否应继续运行
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    pub fn set_running(&self, running: bool) {
+        self.running.store(running, Ordering::Relaxed);
+        self.stopped.send_replace(!running);
+    }
+
+    /// 订阅停止信号，配合 `wait_stopped` 使用
+    pub fn stop_signal(&self) -> watch::Receiver<bool> {
+        self.stopped.subscribe()
+    }
+
+    /// 捕获线程是否已经结束
+    pub fn is_finished(&self) -> bool {
+        match self.thread.lock() {
+            Ok(guard) => guard.as_ref().map(|t| t.is_finished()).unwrap_or(true),
+            Err(_) => false,
+        }
+    }
+
+    pub fn set_thread(&self, handle: thread::JoinHandle<()>) {
+        if let Ok(mut guard) = self.thread.lock() {
+            *guard = Some(handle);
+        }
+    }
+
+    /// 获取会话状态快照
+    pub fn status(&self) -> CaptureStatus {
+        match self.status.lock() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// 更新会话状态并推送到前端
+    pub fn update_status(&self, running: Option<bool>, message: Option<String>) {
+        let snapshot = {
+            let Ok(mut status_guard) = self.status.lock() else {
+                debug!("会话 [{}] 状态锁已损坏，跳过此次更新", self.id);
+                return;
+            };
+
+            if let Some(running_val) = running {
+                status_guard.running = running_val;
+            }
+
+            if let Some(message_val) = message {
+                status_guard.message = message_val;
+            }
+
+            // 如果停止运行，不更新开始时间；如果开始运行，更新开始时间
+            if let Some(true) = running {
+                status_guard.start_time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+            }
+
+            status_guard.clone()
+        };
+
+        // 统计每秒推送一次，只在状态变化时记录日志
+        info!("会话 [{}] 状态更新: 运行中={}, {}", self.id, snapshot.running, snapshot.message);
+        send_status_update(snapshot);
+    }
+
+    /// 更新运行统计并推送到前端
+    pub fn update_stats(&self, stats: CaptureStats) {
+        let snapshot = {
+            let Ok(mut status_guard) = self.status.lock() else {
+                debug!("会话 [{}] 状态锁已损坏，跳过此次统计更新", self.id);
+                return;
+            };
+            status_guard.stats = CaptureStats {
+                session_id: self.id.clone(),
+                ..stats
+            };
+            status_guard.clone()
+        };
+
+        send_status_update(snapshot);
+    }
+
+    /// 记录系统已获取到 Token
+    pub fn mark_token_acquired(&self, system_id: &str) {
+        if let Ok(mut tokens) = self.acquired_tokens.lock() {
+            tokens.insert(system_id.to_string());
+        }
+    }
+
+    /// 本次捕获期间指定系统是否已获取到 Token
+    pub fn has_token(&self, system_id: &str) -> bool {
+        self.acquired_tokens
+            .lock()
+            .map(|tokens| tokens.contains(system_id))
+            .unwrap_or(false)
+    }
+
+    /// 生成会话结束摘要
+    pub fn summary(&self, reason: StopReason) -> CaptureSummary {
+        let status = self.status();
+        let end_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        CaptureSummary {
+            session_id: self.id.clone(),
+            device_name: status.device_name,
+            message: reason.message(),
+            reason,
+            start_time: status.start_time,
+            end_time,
+            duration_secs: end_time.saturating_sub(status.start_time),
+            stats: status.stats,
+        }
+    }
+
+    /// 通知捕获线程停止，并等待其结束（最多3秒）
+    pub fn stop(&self) {
+        self.set_running(false);
+        info!("已向捕获会话 [{}] 发送停止信号", self.id);
+
+        let thread = match self.thread.lock() {
+            Ok(mut guard) => guard.take(),
+            Err(_) => None,
+        };
+
+        if let Some(thread) = thread {
+            let mut attempts = 0;
+            while !thread.is_finished() && attempts < 30 {
+                thread::sleep(Duration::from_millis(100));
+                attempts += 1;
+            }
+
+            if thread.is_finished() {
+                let _ = thread.join();
+                info!("捕获会话 [{}] 线程已正常结束", self.id);
+            } else {
+                info!("等待捕获会话 [{}] 线程结束超时，强制继续", self.id);
+                // 注意：这里不调用 join，因为线程可能还在运行
+            }
+        }
+    }
+}
+
+/// 等待会话停止（运行标志被清除）
+pub async fn wait_stopped(signal: &mut watch::Receiver<bool>) {
+    // 发送端随会话一起释放时同样视为已停止
+    let _ = signal.wait_for(|stopped| *stopped).await;
+}
+
+/// 初始化会话表，只在应用启动时调用一次
+pub fn init_sessions() {
+    SESSIONS.get_or_init(DashMap::new);
+}
+
+pub fn sessions() -> &'static DashMap<String, Arc<CaptureSession>> {
+    SESSIONS.get_or_init(DashMap::new)
+}
+
+/// 登记新会话，同名会话仍在运行时返回错误，已结束的同名会话被替换
+///
+/// 检查和插入在同一个表项锁内完成，避免并发启动同名会话。调用前应先设置运行标志，
+/// 使其他线程在捕获线程启动之前也能看到该会话正在运行。
+pub fn register_session(session: Arc<CaptureSession>) -> Result<()> {
+    // 遍历会话表需要在获取表项锁之前完成
+    prune_finished(sessions(), MAX_FINISHED_SESSIONS);
+
+    match sessions().entry(session.id().to_string()) {
+        Entry::Occupied(mut entry) => {
+            let existing = entry.get();
+            if existing.is_running() || !existing.is_finished() {
+                return Err(anyhow!("捕获会话 [{}] 已经在运行中，请先停止", session.id()));
+            }
+            info!("清理了旧的捕获会话 [{}]", session.id());
+            entry.insert(session);
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(session);
+        }
+    }
+    Ok(())
+}
+
+/// 已结束的会话超过保留数量时，移除最早开始的会话
+fn prune_finished(sessions: &DashMap<String, Arc<CaptureSession>>, keep: usize) {
+    let mut finished: Vec<(String, u64)> = sessions
+        .iter()
+        .filter(|entry| !entry.value().is_running() && entry.value().is_finished())
+        .map(|entry| (entry.key().clone(), entry.value().status().start_time))
+        .collect();
+    if finished.len() <= keep {
+        return;
+    }
+
+    finished.sort_by_key(|(_, start_time)| *start_time);
+    let excess = finished.len() - keep;
+    for (id, _) in finished.into_iter().take(excess) {
+        // 收集之后会话可能已被重新启动，移除前再检查一次
+        if sessions
+            .remove_if(&id, |_, s| !s.is_running() && s.is_finished())
+            .is_some()
+        {
+            debug!("移除已结束的捕获会话 [{}]", id);
+        }
+    }
+}
+
+pub fn get_session(id: &str) -> Option<Arc<CaptureSession>> {
+    sessions().get(id).map(|entry| entry.value().clone())
+}
+
+/// 认证系统获取到 Token 时通知数据包所属的捕获会话
+pub fn notify_token_acquired(session_id: &str, system_id: &str) {
+    if let Some(session) = get_session(session_id) {
+        session.mark_token_acquired(system_id);
+    }
+}
+
+/// 会话的运行统计，未指定会话ID时返回全部会话
+pub fn session_stats(id: Option<&str>) -> Vec<CaptureStats> {
+    all_statuses()
+        .into_iter()
+        .filter(|status| id.is_none_or(|id| status.session_id == id))
+        .map(|status| status.stats)
+        .collect()
+}
+
+/// 所有会话的状态，按会话ID排序
+pub fn all_statuses() -> Vec<CaptureStatus> {
+    let mut statuses: Vec<CaptureStatus> = sessions()
+        .iter()
+        .map(|entry| entry.value().status())
+        .collect();
+    statuses.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+    statuses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_session(id: &str) -> Arc<CaptureSession> {
+        Arc::new(CaptureSession::new(id.to_string(), "eth0".to_string(), CaptureOptions::default(), String::new()))
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        let session = new_session("session-test-status");
+        let mut signal = session.stop_signal();
+        assert!(!session.is_running());
+        // 没有捕获线程的会话视为已结束
+        assert!(session.is_finished());
+
+        session.set_running(true);
+        session.update_status(Some(true), Some("正在捕获".to_string()));
+        let running = session.status();
+        assert!(running.running);
+        assert!(running.start_time > 0);
+        assert_eq!(running.message, "正在捕获");
+        assert!(!*signal.borrow_and_update());
+
+        session.update_stats(CaptureStats {
+            frames: 5,
+            ..Default::default()
+        });
+        assert_eq!(session.status().stats.frames, 5);
+        assert_eq!(session.status().stats.session_id, "session-test-status");
+
+        session.stop();
+        assert!(!session.is_running());
+        assert!(*signal.borrow_and_update());
+        session.update_status(Some(false), Some("已停止".to_string()));
+        let stopped = session.status();
+        assert!(!stopped.running);
+        // 停止时保留开始时间
+        assert_eq!(stopped.start_time, running.start_time);
+
+        let summary = session.summary(StopReason::Manual);
+        assert_eq!(summary.session_id, "session-test-status");
+        assert_eq!(summary.start_time, running.start_time);
+        assert_eq!(summary.stats.frames, 5);
+    }
+
+    #[test]
+    fn test_register_rejects_running_duplicate_and_reuses_stopped_id() {
+        let id = "session-test-register";
+        let first = new_session(id);
+        first.set_running(true);
+        register_session(first.clone()).unwrap();
+
+        // 同名会话仍在运行时拒绝启动，原会话不受影响
+        let second = new_session(id);
+        second.set_running(true);
+        assert!(register_session(second.clone()).is_err());
+        assert!(Arc::ptr_eq(&get_session(id).unwrap(), &first));
+
+        // 停止运行但线程尚未结束时同样拒绝
+        first.set_running(false);
+        first.set_thread(thread::spawn(|| thread::sleep(Duration::from_millis(300))));
+        assert!(register_session(second.clone()).is_err());
+
+        // 线程结束后可以复用同一个会话ID
+        first.stop();
+        register_session(second.clone()).unwrap();
+        assert!(Arc::ptr_eq(&get_session(id).unwrap(), &second));
+        sessions().remove(id);
+    }
+
+    #[test]
+    fn test_prune_finished_sessions() {
+        let sessions = DashMap::new();
+        for i in 0..4 {
+            let id = format!("finished-{}", i);
+            sessions.insert(id.clone(), new_session(&id));
+        }
+        let running = new_session("running");
+        running.set_running(true);
+        sessions.insert("running".to_string(), running);
+
+        prune_finished(&sessions, 4);
+        assert_eq!(sessions.len(), 5);
+
+        // 只移除已结束的会话，运行中的会话始终保留
+        prune_finished(&sessions, 1);
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.contains_key("running"));
+    }
+}
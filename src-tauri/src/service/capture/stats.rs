@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 统计推送间隔
+pub const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 捕获会话的运行统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CaptureStats {
+    pub session_id: String,
+    /// pcap 报告的内核统计：收到、因缓冲区满丢弃、被网卡/驱动丢弃
+    pub pcap_received: u64,
+    pub pcap_dropped: u64,
+    pub pcap_if_dropped: u64,
+    /// 捕获线程读到的帧数及其原始字节数
+    pub frames: u64,
+    pub bytes: u64,
+    /// 链路层/网络层解析失败的帧数
+    pub parse_errors: u64,
+    pub http_requests: u64,
+    pub http_responses: u64,
+    pub tls_connections: u64,
+    /// 使用密钥日志成功解密的 TLS 连接数
+    pub tls_decrypted: u64,
+    /// 解析的 DNS 消息数（查询与响应合计）
+    pub dns_messages: u64,
+    /// IP 分片：收到的分片数、重组成功的数据报数、重组失败（超时、重叠、超限）的数据报数
+    pub ip_fragments: u64,
+    pub ip_reassembled: u64,
+    pub ip_reassembly_failures: u64,
+    /// 最近一个统计周期内的速率
+    pub packets_per_second: f64,
+    pub bytes_per_second: f64,
+}
+
+/// 按统计周期计算速率
+#[derive(Debug)]
+pub struct RateMeter {
+    last_at: Instant,
+    last_frames: u64,
+    last_bytes: u64,
+}
+
+impl RateMeter {
+    pub fn new() -> Self {
+        Self {
+            last_at: Instant::now(),
+            last_frames: 0,
+            last_bytes: 0,
+        }
+    }
+
+    /// 距离上次计算是否已经超过统计周期
+    pub fn is_due(&self) -> bool {
+        self.last_at.elapsed() >= STATS_INTERVAL
+    }
+
+    /// 根据累计计数更新速率字段
+    pub fn update(&mut self, stats: &mut CaptureStats) {
+        let elapsed = self.last_at.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            stats.packets_per_second = stats.frames.saturating_sub(self.last_frames) as f64 / elapsed;
+            stats.bytes_per_second = stats.bytes.saturating_sub(self.last_bytes) as f64 / elapsed;
+        }
+        self.last_at = Instant::now();
+        self.last_frames = stats.frames;
+        self.last_bytes = stats.bytes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewind(meter: &mut RateMeter, secs: u64) {
+        meter.last_at = Instant::now() - Duration::from_secs(secs);
+    }
+
+    #[test]
+    fn test_rate_from_counter_deltas() {
+        let mut meter = RateMeter::new();
+        assert!(!meter.is_due());
+        rewind(&mut meter, 2);
+        assert!(meter.is_due());
+
+        let mut stats = CaptureStats {
+            frames: 100,
+            bytes: 10_000,
+            ..Default::default()
+        };
+        meter.update(&mut stats);
+        // 实际经过的时间略多于 2 秒
+        assert!((45.0..=50.0).contains(&stats.packets_per_second));
+        assert!((4_500.0..=5_000.0).contains(&stats.bytes_per_second));
+        assert!(!meter.is_due());
+
+        // 只统计上个周期以来新增的计数
+        rewind(&mut meter, 1);
+        stats.frames += 10;
+        stats.bytes += 1_000;
+        meter.update(&mut stats);
+        assert!((9.0..=10.0).contains(&stats.packets_per_second));
+        assert!((900.0..=1_000.0).contains(&stats.bytes_per_second));
+
+        // 计数比上次小（统计来源重置）时速率为 0
+        rewind(&mut meter, 1);
+        let mut reset = CaptureStats {
+            frames: 5,
+            bytes: 500,
+            ..Default::default()
+        };
+        meter.update(&mut reset);
+        assert_eq!(reset.packets_per_second, 0.0);
+        assert_eq!(reset.bytes_per_second, 0.0);
+    }
+}
@@ -0,0 +1,247 @@
+use super::stats::CaptureStats;
+use super::HttpPacket;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 捕获会话的自动停止条件，任意一个条件满足即停止
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StopConditions {
+    /// 最长捕获时间（秒）
+    pub max_duration_secs: Option<u64>,
+    /// 最多捕获的 HTTP 数据包（请求与响应合计）
+    pub max_http_packets: Option<u64>,
+    /// 最多读取的原始字节数
+    pub max_bytes: Option<u64>,
+    /// 指定系统获取到 Token 后停止
+    pub token_acquired: Option<String>,
+    /// 匹配的请求达到指定数量后停止
+    pub matching_requests: Option<RequestMatch>,
+}
+
+/// 请求匹配条件，所有条件之间为“且”关系
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestMatch {
+    /// Host 包含的子串（不区分大小写）
+    pub host: Option<String>,
+    pub method: Option<String>,
+    /// 路径正则表达式
+    pub path_regex: Option<String>,
+    /// 匹配数量，为 0 时按 1 处理
+    pub count: u64,
+}
+
+/// 捕获会话停止的原因
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StopReason {
+    /// 用户手动停止
+    Manual,
+    MaxDuration { secs: u64 },
+    MaxHttpPackets { count: u64 },
+    MaxBytes { bytes: u64 },
+    TokenAcquired { system_id: String },
+    MatchingRequests { count: u64 },
+    /// 捕获出错
+    Error { message: String },
+}
+
+impl StopReason {
+    /// 写入 CaptureStatus.message 的说明
+    pub fn message(&self) -> String {
+        match self {
+            StopReason::Manual => "数据包捕获已停止".to_string(),
+            StopReason::MaxDuration { secs } => format!("已达到最长捕获时间 {} 秒，自动停止", secs),
+            StopReason::MaxHttpPackets { count } => format!("已捕获 {} 个 HTTP 数据包，自动停止", count),
+            StopReason::MaxBytes { bytes } => format!("已读取 {} 字节，自动停止", bytes),
+            StopReason::TokenAcquired { system_id } => format!("系统 [{}] 已获取到 Token，自动停止", system_id),
+            StopReason::MatchingRequests { count } => format!("已捕获 {} 个匹配的请求，自动停止", count),
+            StopReason::Error { message } => format!("捕获失败: {}", message),
+        }
+    }
+}
+
+/// 捕获会话结束时发送的摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureSummary {
+    pub session_id: String,
+    pub device_name: String,
+    pub reason: StopReason,
+    pub message: String,
+    /// 开始和结束时间（Unix 时间戳，秒）
+    pub start_time: u64,
+    pub end_time: u64,
+    pub duration_secs: u64,
+    pub stats: CaptureStats,
+}
+
+impl StopConditions {
+    /// 校验停止条件，启动捕获前调用
+    pub fn validate(&self) -> Result<()> {
+        if self.max_duration_secs == Some(0) {
+            return Err(anyhow!("最长捕获时间必须大于 0"));
+        }
+        if self.max_http_packets == Some(0) || self.max_bytes == Some(0) {
+            return Err(anyhow!("数据包数量和字节数上限必须大于 0"));
+        }
+        if self.token_acquired.as_deref().is_some_and(|id| id.trim().is_empty()) {
+            return Err(anyhow!("Token 停止条件未指定系统ID"));
+        }
+        if let Some(matcher) = &self.matching_requests {
+            matcher.compile_path_regex()?;
+        }
+        Ok(())
+    }
+}
+
+impl RequestMatch {
+    fn compile_path_regex(&self) -> Result<Option<Regex>> {
+        self.path_regex
+            .as_deref()
+            .filter(|r| !r.is_empty())
+            .map(|r| Regex::new(r).map_err(|e| anyhow!("路径正则表达式无效: {}", e)))
+            .transpose()
+    }
+}
+
+/// 在捕获线程中跟踪停止条件
+#[derive(Debug)]
+pub struct StopTracker {
+    conditions: StopConditions,
+    started_at: Instant,
+    path_regex: Option<Regex>,
+    /// 已匹配的请求数量
+    matched: u64,
+}
+
+impl StopTracker {
+    pub fn new(conditions: StopConditions) -> Result<Self> {
+        conditions.validate()?;
+        let path_regex = match &conditions.matching_requests {
+            Some(matcher) => matcher.compile_path_regex()?,
+            None => None,
+        };
+        Ok(Self {
+            conditions,
+            started_at: Instant::now(),
+            path_regex,
+            matched: 0,
+        })
+    }
+
+    /// 统计匹配的请求
+    pub fn observe(&mut self, packet: &HttpPacket) {
+        let Some(matcher) = &self.conditions.matching_requests else {
+            return;
+        };
+        if packet.packet_type != "request" {
+            return;
+        }
+        let host_matches = matcher
+            .host
+            .as_deref()
+            .is_none_or(|h| packet.host.to_lowercase().contains(&h.to_lowercase()));
+        let method_matches = matcher
+            .method
+            .as_deref()
+            .is_none_or(|m| packet.method.as_deref().is_some_and(|pm| pm.eq_ignore_ascii_case(m)));
+        let path_matches = self
+            .path_regex
+            .as_ref()
+            .is_none_or(|re| packet.path.as_deref().is_some_and(|p| re.is_match(p)));
+        if host_matches && method_matches && path_matches {
+            self.matched += 1;
+        }
+    }
+
+    /// 检查是否满足停止条件，`has_token` 判断会话内指定系统是否已获取 Token
+    pub fn check(&self, stats: &CaptureStats, has_token: impl Fn(&str) -> bool) -> Option<StopReason> {
+        let conditions = &self.conditions;
+        if let Some(secs) = conditions.max_duration_secs {
+            if self.started_at.elapsed() >= Duration::from_secs(secs) {
+                return Some(StopReason::MaxDuration { secs });
+            }
+        }
+        if let Some(count) = conditions.max_http_packets {
+            if stats.http_requests + stats.http_responses >= count {
+                return Some(StopReason::MaxHttpPackets { count });
+            }
+        }
+        if let Some(bytes) = conditions.max_bytes {
+            if stats.bytes >= bytes {
+                return Some(StopReason::MaxBytes { bytes });
+            }
+        }
+        if let Some(system_id) = &conditions.token_acquired {
+            if has_token(system_id) {
+                return Some(StopReason::TokenAcquired { system_id: system_id.clone() });
+            }
+        }
+        if let Some(matcher) = &conditions.matching_requests {
+            let count = matcher.count.max(1);
+            if self.matched >= count {
+                return Some(StopReason::MatchingRequests { count });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_conditions() {
+        let mut tracker = StopTracker::new(StopConditions {
+            max_http_packets: Some(10),
+            token_acquired: Some("system_bi".to_string()),
+            matching_requests: Some(RequestMatch {
+                method: Some("post".to_string()),
+                path_regex: Some("^/api/query".to_string()),
+                count: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let request = HttpPacket {
+            packet_type: "request".to_string(),
+            method: Some("POST".to_string()),
+            path: Some("/api/query?page=1".to_string()),
+            ..Default::default()
+        };
+        let mut stats = CaptureStats::default();
+        tracker.observe(&request);
+        assert_eq!(tracker.check(&stats, |_| false), None);
+        tracker.observe(&request);
+        assert_eq!(tracker.check(&stats, |_| false), Some(StopReason::MatchingRequests { count: 2 }));
+
+        stats.http_requests = 10;
+        assert_eq!(tracker.check(&stats, |_| false), Some(StopReason::MaxHttpPackets { count: 10 }));
+        stats.http_requests = 0;
+        assert_eq!(
+            StopTracker::new(StopConditions {
+                token_acquired: Some("system_bi".to_string()),
+                ..Default::default()
+            })
+            .unwrap()
+            .check(&stats, |id| id == "system_bi"),
+            Some(StopReason::TokenAcquired { system_id: "system_bi".to_string() })
+        );
+
+        assert!(StopConditions {
+            matching_requests: Some(RequestMatch {
+                path_regex: Some("(".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use super::body_view::{view_for_packet, BodyView};
+use super::cookie::{Cookie, SetCookie};
+use super::HttpPacket;
+use crate::service::redact;
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+/// 默认最多保留的数据包数量
+const DEFAULT_MAX_PACKETS: usize = 20_000;
+/// 默认最多占用的内存（字节，估算值）
+const DEFAULT_MAX_BYTES: usize = 256 * 1024 * 1024;
+/// 单次查询最多返回的数据包数量
+const MAX_PAGE_SIZE: usize = 1000;
+
+static PACKET_STORE: Lazy<RwLock<PacketStore>> = Lazy::new(|| RwLock::new(PacketStore::default()));
+
+/// 数据包查询条件，所有条件之间为“且”关系
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PacketQuery {
+    /// "request" 或 "response"
+    pub packet_type: Option<String>,
+    /// Host 包含的子串（不区分大小写）
+    pub host: Option<String>,
+    pub method: Option<String>,
+    /// 状态码范围（闭区间）
+    pub status_min: Option<u16>,
+    pub status_max: Option<u16>,
+    /// 路径正则表达式
+    pub path_regex: Option<String>,
+    /// Content-Type 包含的子串（不区分大小写）
+    pub content_type: Option<String>,
+    pub device_name: Option<String>,
+    /// 时间窗口（与 HttpPacket.timestamp 同单位，闭区间）
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub offset: usize,
+    /// 为 0 时使用默认分页大小
+    pub limit: usize,
+}
+
+/// 分页查询结果，数据包按捕获时间倒序排列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketPage {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub packets: Vec<HttpPacket>,
+}
+
+/// 数据包详情：数据包本身加上结构化消息体视图
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketDetail {
+    #[serde(flatten)]
+    pub packet: HttpPacket,
+    pub body_view: BodyView,
+}
+
+/// 数据包存储的占用情况
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketStoreStats {
+    pub count: usize,
+    pub bytes: usize,
+    pub max_packets: usize,
+    pub max_bytes: usize,
+    /// 因超出上限被淘汰的数据包数量
+    pub evicted: u64,
+}
+
+/// 有界的内存数据包存储
+///
+/// 超过数量或字节上限时淘汰最早的数据包。
+#[derive(Debug)]
+pub struct PacketStore {
+    /// (数据包, 估算大小)，按插入顺序排列
+    packets: VecDeque<(HttpPacket, usize)>,
+    bytes: usize,
+    max_packets: usize,
+    max_bytes: usize,
+    evicted: u64,
+}
+
+impl Default for PacketStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PACKETS, DEFAULT_MAX_BYTES)
+    }
+}
+
+impl PacketStore {
+    pub fn new(max_packets: usize, max_bytes: usize) -> Self {
+        Self {
+            packets: VecDeque::new(),
+            bytes: 0,
+            max_packets,
+            max_bytes,
+            evicted: 0,
+        }
+    }
+
+    /// 保存数据包，ID 已存在时替换旧记录
+    pub fn insert(&mut self, packet: HttpPacket) {
+        // 数据包ID单调递增，只有不大于最新ID时才可能是已保存过的数据包
+        if self.packets.back().is_some_and(|(last, _)| packet.id <= last.id) {
+            self.remove(packet.id);
+        }
+
+        let size = estimate_size(&packet);
+        self.bytes += size;
+        self.packets.push_back((packet, size));
+        self.enforce_limits();
+    }
+
+    pub fn get(&self, id: u64) -> Option<HttpPacket> {
+        // 详情窗口通常查看最近的数据包，从尾部开始查找
+        self.packets
+            .iter()
+            .rev()
+            .find(|(p, _)| p.id == id)
+            .map(|(p, _)| p.clone())
+    }
+
+    pub fn query(&self, query: &PacketQuery) -> Result<PacketPage> {
+        let path_regex = match query.path_regex.as_deref().filter(|r| !r.is_empty()) {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| anyhow!("路径正则表达式无效: {}", e))?),
+            None => None,
+        };
+        let host = query.host.as_deref().map(str::to_lowercase);
+        let content_type = query.content_type.as_deref().map(str::to_lowercase);
+        let limit = match query.limit {
+            0 => 100,
+            n => n.min(MAX_PAGE_SIZE),
+        };
+
+        let matched: Vec<&HttpPacket> = self
+            .packets
+            .iter()
+            .rev()
+            .map(|(packet, _)| packet)
+            .filter(|p| query.packet_type.as_deref().is_none_or(|t| p.packet_type == t))
+            .filter(|p| host.as_deref().is_none_or(|h| p.host.to_lowercase().contains(h)))
+            .filter(|p| {
+                query
+                    .method
+                    .as_deref()
+                    .is_none_or(|m| p.method.as_deref().is_some_and(|pm| pm.eq_ignore_ascii_case(m)))
+            })
+            .filter(|p| {
+                if query.status_min.is_none() && query.status_max.is_none() {
+                    return true;
+                }
+                p.status_code.is_some_and(|code| {
+                    code >= query.status_min.unwrap_or(0) && code <= query.status_max.unwrap_or(u16::MAX)
+                })
+            })
+            .filter(|p| {
+                path_regex
+                    .as_ref()
+                    .is_none_or(|re| p.path.as_deref().is_some_and(|path| re.is_match(path)))
+            })
+            .filter(|p| {
+                content_type
+                    .as_deref()
+                    .is_none_or(|ct| p.content_type.to_lowercase().contains(ct))
+            })
+            .filter(|p| query.device_name.as_deref().is_none_or(|d| p.device_name == d))
+            .filter(|p| query.since.is_none_or(|since| p.timestamp >= since))
+            .filter(|p| query.until.is_none_or(|until| p.timestamp <= until))
+            .collect();
+
+        Ok(PacketPage {
+            total: matched.len(),
+            offset: query.offset,
+            limit,
+            packets: matched
+                .into_iter()
+                .skip(query.offset)
+                .take(limit)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.packets.clear();
+        self.bytes = 0;
+    }
+
+    pub fn set_limits(&mut self, max_packets: usize, max_bytes: usize) {
+        self.max_packets = max_packets;
+        self.max_bytes = max_bytes;
+        self.enforce_limits();
+    }
+
+    pub fn stats(&self) -> PacketStoreStats {
+        PacketStoreStats {
+            count: self.packets.len(),
+            bytes: self.bytes,
+            max_packets: self.max_packets,
+            max_bytes: self.max_bytes,
+            evicted: self.evicted,
+        }
+    }
+
+    fn remove(&mut self, id: u64) {
+        if let Some(pos) = self.packets.iter().rposition(|(p, _)| p.id == id) {
+            if let Some((_, size)) = self.packets.remove(pos) {
+                self.bytes -= size;
+            }
+        }
+    }
+
+    fn enforce_limits(&mut self) {
+        while self.packets.len() > self.max_packets || (self.bytes > self.max_bytes && self.packets.len() > 1) {
+            let Some((_, size)) = self.packets.pop_front() else { break };
+            self.bytes -= size;
+            self.evicted += 1;
+        }
+    }
+}
+
+/// 估算数据包占用的内存
+fn estimate_size(packet: &HttpPacket) -> usize {
+    let headers: usize = packet.headers.iter().map(|(n, v)| n.len() + v.len()).sum();
+    let cookies: usize = packet
+        .cookies
+        .iter()
+        .map(|c| std::mem::size_of::<Cookie>() + c.name.len() + c.value.len())
+        .sum();
+    let set_cookies: usize = packet
+        .set_cookies
+        .iter()
+        .map(|c| {
+            std::mem::size_of::<SetCookie>()
+                + c.name.len()
+                + c.value.len()
+                + [&c.domain, &c.path, &c.expires, &c.same_site]
+                    .iter()
+                    .map(|v| v.as_ref().map_or(0, |v| v.len()))
+                    .sum::<usize>()
+        })
+        .sum();
+    std::mem::size_of::<HttpPacket>()
+        + packet.src_ip.len()
+        + packet.dst_ip.len()
+        + packet.host.len()
+        + packet.path.as_ref().map_or(0, |p| p.len())
+        + packet.content_type.len()
+        + headers
+        + packet.body.len()
+        + packet.raw_body.len()
+        + cookies
+        + set_cookies
+}
+
+/// 保存数据包到全局存储
+pub fn store_packet(packet: HttpPacket) {
+    match PACKET_STORE.write() {
+        Ok(mut store) => store.insert(packet),
+        Err(_) => debug!("数据包存储锁已损坏，跳过保存"),
+    }
+}
+
+/// 分页查询数据包，返回前脱敏
+pub fn query_packets(query: &PacketQuery) -> Result<PacketPage> {
+    let mut page = PACKET_STORE
+        .read()
+        .map_err(|_| anyhow!("数据包存储锁已损坏"))?
+        .query(query)?;
+    page.packets.iter_mut().for_each(redact::redact_packet);
+    Ok(page)
+}
+
+pub fn get_packet_by_id(id: u64) -> Option<HttpPacket> {
+    PACKET_STORE.read().ok()?.get(id)
+}
+
+/// 获取数据包详情，消息体视图在脱敏后生成
+pub fn get_packet_detail(id: u64) -> Option<PacketDetail> {
+    let mut packet = get_packet_by_id(id)?;
+    redact::redact_packet(&mut packet);
+    Some(PacketDetail {
+        body_view: view_for_packet(&packet),
+        packet,
+    })
+}
+
+pub fn clear_packets() {
+    if let Ok(mut store) = PACKET_STORE.write() {
+        store.clear();
+        info!("已清空数据包存储");
+    }
+}
+
+pub fn set_store_limits(max_packets: usize, max_bytes: usize) -> Result<()> {
+    if max_packets == 0 || max_bytes == 0 {
+        return Err(anyhow!("存储上限必须大于 0"));
+    }
+    PACKET_STORE
+        .write()
+        .map_err(|_| anyhow!("数据包存储锁已损坏"))?
+        .set_limits(max_packets, max_bytes);
+    info!("数据包存储上限已更新: {} 个 / {} 字节", max_packets, max_bytes);
+    Ok(())
+}
+
+pub fn store_stats() -> Result<PacketStoreStats> {
+    Ok(PACKET_STORE
+        .read()
+        .map_err(|_| anyhow!("数据包存储锁已损坏"))?
+        .stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(id: u64, packet_type: &str, host: &str, path: &str, status: Option<u16>) -> HttpPacket {
+        HttpPacket {
+            id,
+            timestamp: id,
+            packet_type: packet_type.to_string(),
+            host: host.to_string(),
+            path: Some(path.to_string()),
+            method: (packet_type == "request").then(|| "POST".to_string()),
+            status_code: status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_query_filters_and_paging() {
+        let mut store = PacketStore::new(100, usize::MAX);
+        store.insert(packet(1, "request", "23.210.227.16", "/api/v2/biPlatform/query/byQueryParam", None));
+        store.insert(packet(2, "response", "23.210.227.16", "/api/v2/biPlatform/query/byQueryParam", Some(200)));
+        store.insert(packet(3, "response", "23.210.52.94", "/login", Some(302)));
+
+        let query = PacketQuery {
+            host: Some("227.16".to_string()),
+            path_regex: Some("biPlatform".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.query(&query).unwrap().total, 2);
+
+        let query = PacketQuery { status_min: Some(300), status_max: Some(399), ..Default::default() };
+        let page = store.query(&query).unwrap();
+        assert_eq!(page.packets[0].id, 3);
+
+        let query = PacketQuery { offset: 1, limit: 1, ..Default::default() };
+        let page = store.query(&query).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.packets[0].id, 2);
+    }
+
+    #[test]
+    fn test_eviction_by_count() {
+        let mut store = PacketStore::new(2, usize::MAX);
+        for id in 1..=3 {
+            store.insert(packet(id, "request", "h", "/", None));
+        }
+        assert!(store.get(1).is_none());
+        assert_eq!(store.stats().evicted, 1);
+    }
+
+    #[test]
+    fn test_insert_replaces_existing_id() {
+        let mut store = PacketStore::new(100, usize::MAX);
+        store.insert(packet(1, "request", "h", "/a", None));
+        store.insert(packet(2, "request", "h", "/b", None));
+        let bytes = store.stats().bytes;
+
+        let mut replaced = packet(1, "request", "h", "/a", None);
+        replaced.cookies = vec![Cookie {
+            name: "x_login_pk".to_string(),
+            value: "abc".to_string(),
+        }];
+        store.insert(replaced);
+        let stats = store.stats();
+        assert_eq!(stats.count, 2);
+        // Cookie 计入估算大小
+        assert!(stats.bytes > bytes);
+        assert_eq!(store.get(1).unwrap().cookies.len(), 1);
+    }
+}
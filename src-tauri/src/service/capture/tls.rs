@@ -0,0 +1,608 @@
+use super::reassembly::{FlowKey, TcpSegment};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 同时跟踪的最大 TLS 握手数量
+const MAX_HANDSHAKES: usize = 4096;
+/// 单个方向握手数据的最大缓冲字节数（证书链通常只有几 KB）
+const MAX_HANDSHAKE_BUFFER: usize = 256 * 1024;
+/// 握手空闲超时时间（微秒）
+const HANDSHAKE_IDLE_TIMEOUT_US: u64 = 60 * 1_000_000;
+
+const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+const HANDSHAKE_SERVER_HELLO: u8 = 2;
+const HANDSHAKE_CERTIFICATE: u8 = 11;
+const HANDSHAKE_SERVER_HELLO_DONE: u8 = 14;
+const EXTENSION_SERVER_NAME: u16 = 0;
+const EXTENSION_ALPN: u16 = 16;
+const EXTENSION_SUPPORTED_VERSIONS: u16 = 43;
+
+/// TLS 连接事件
+///
+/// 每个连接最多发送两次：收到 ClientHello 时（stage 为 "client_hello"），
+/// 以及服务器握手信息收集完毕时（stage 为 "server_hello"），两次事件的 id 相同。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsConnectionEvent {
+    pub id: u64,
+    pub stage: String,
+    /// 事件对应握手消息的捕获时间（微秒）
+    pub timestamp_us: u64,
+    pub device_name: String,
+    pub client_ip: String,
+    pub client_port: u16,
+    pub server_ip: String,
+    pub server_port: u16,
+    /// ClientHello 中的 SNI 主机名
+    pub sni: Option<String>,
+    /// 客户端提供的 ALPN 协议列表
+    pub client_alpn: Vec<String>,
+    /// 客户端支持的 TLS 版本
+    pub client_versions: Vec<String>,
+    /// 服务器选择的 ALPN 协议
+    pub alpn: Option<String>,
+    /// 协商的 TLS 版本
+    pub version: Option<String>,
+    /// 协商的密码套件
+    pub cipher_suite: Option<String>,
+    /// 服务器证书（仅 TLS 1.2 及以下可见）
+    pub certificate: Option<TlsCertificateInfo>,
+}
+
+/// 服务器叶子证书信息
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsCertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    /// 十六进制序列号
+    pub serial: String,
+    /// 有效期（Unix 时间戳，秒）
+    pub not_before: i64,
+    pub not_after: i64,
+    /// 证书中的 DNS 备用名称
+    pub dns_names: Vec<String>,
+    /// 服务器发送的证书链长度
+    pub chain_length: usize,
+}
+
+/// 单个方向的握手数据缓冲
+#[derive(Debug, Default)]
+struct HandshakeStream {
+    next_seq: Option<u32>,
+    /// 尚未凑成完整记录的记录层数据
+    records: Vec<u8>,
+    /// 尚未凑成完整消息的握手层数据
+    handshake: Vec<u8>,
+    /// 已出现非握手记录（ChangeCipherSpec 或加密数据），明文握手结束
+    encrypted: bool,
+    /// 数据缺失或超过缓冲上限，放弃解析
+    abandoned: bool,
+}
+
+impl HandshakeStream {
+    /// 追加 TCP 载荷，返回新解析出的握手消息 (类型, 内容)
+    fn push(&mut self, seq: u32, payload: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        if self.abandoned || self.encrypted {
+            return Vec::new();
+        }
+
+        match self.next_seq {
+            // 重传的数据直接忽略
+            Some(next) if (seq.wrapping_sub(next) as i32) < 0 => return Vec::new(),
+            // 中间有数据缺失，无法继续解析
+            Some(next) if seq != next => {
+                self.abandoned = true;
+                return Vec::new();
+            }
+            _ => {}
+        }
+        self.next_seq = Some(seq.wrapping_add(payload.len() as u32));
+
+        if self.records.len() + payload.len() > MAX_HANDSHAKE_BUFFER {
+            self.abandoned = true;
+            return Vec::new();
+        }
+        self.records.extend_from_slice(payload);
+
+        // 拆分记录层，握手记录的内容拼接到握手缓冲
+        let mut pos = 0;
+        while self.records.len() - pos >= 5 {
+            let content_type = self.records[pos];
+            let length = u16::from_be_bytes([self.records[pos + 3], self.records[pos + 4]]) as usize;
+            if self.records.len() - pos < 5 + length {
+                break;
+            }
+            if content_type != CONTENT_TYPE_HANDSHAKE {
+                self.encrypted = true;
+                break;
+            }
+            self.handshake.extend_from_slice(&self.records[pos + 5..pos + 5 + length]);
+            pos += 5 + length;
+        }
+        self.records.drain(..pos);
+
+        // 拆分握手消息
+        let mut messages = Vec::new();
+        let mut pos = 0;
+        while self.handshake.len() - pos >= 4 {
+            let msg_type = self.handshake[pos];
+            let length = u32::from_be_bytes([0, self.handshake[pos + 1], self.handshake[pos + 2], self.handshake[pos + 3]]) as usize;
+            if self.handshake.len() - pos < 4 + length {
+                break;
+            }
+            messages.push((msg_type, self.handshake[pos + 4..pos + 4 + length].to_vec()));
+            pos += 4 + length;
+        }
+        self.handshake.drain(..pos);
+
+        messages
+    }
+}
+
+/// 单个 TLS 连接的握手状态
+#[derive(Debug)]
+struct TlsHandshake {
+    event: TlsConnectionEvent,
+    client: HandshakeStream,
+    server: HandshakeStream,
+    client_hello_seen: bool,
+    server_hello_seen: bool,
+    last_seen_us: u64,
+}
+
+/// TLS 握手跟踪器
+///
+/// 只跟踪以 ClientHello 开头的连接，解析双方的明文握手消息，
+/// 不参与 HTTP 流重组。
+#[derive(Debug, Default)]
+pub struct TlsTracker {
+    /// 以客户端 -> 服务器方向为键
+    handshakes: HashMap<FlowKey, TlsHandshake>,
+    /// 已识别的 TLS 连接数量
+    connections: u64,
+}
+
+impl TlsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connections(&self) -> u64 {
+        self.connections
+    }
+
+    /// 处理一个 TCP 段，返回产生的连接事件
+    pub fn process_segment(&mut self, key: FlowKey, segment: &TcpSegment) -> Vec<TlsConnectionEvent> {
+        let mut events = Vec::new();
+        self.evict_idle(segment.timestamp_us);
+
+        let (client_key, from_client) = if self.handshakes.contains_key(&key) {
+            (key, true)
+        } else if self.handshakes.contains_key(&key.reversed()) {
+            (key.reversed(), false)
+        } else if looks_like_client_hello(segment.payload) {
+            if self.handshakes.len() >= MAX_HANDSHAKES {
+                self.evict_oldest();
+            }
+            self.connections += 1;
+            self.handshakes.insert(key, TlsHandshake {
+                event: TlsConnectionEvent {
+                    id: super::next_packet_id(),
+                    client_ip: key.src.ip().to_string(),
+                    client_port: key.src.port(),
+                    server_ip: key.dst.ip().to_string(),
+                    server_port: key.dst.port(),
+                    ..Default::default()
+                },
+                client: HandshakeStream::default(),
+                server: HandshakeStream::default(),
+                client_hello_seen: false,
+                server_hello_seen: false,
+                last_seen_us: segment.timestamp_us,
+            });
+            (key, true)
+        } else {
+            return events;
+        };
+
+        let Some(handshake) = self.handshakes.get_mut(&client_key) else {
+            return events;
+        };
+        handshake.last_seen_us = segment.timestamp_us;
+
+        let mut finished = segment.fin || segment.rst;
+        if !segment.payload.is_empty() {
+            if from_client {
+                for (msg_type, body) in handshake.client.push(segment.seq, segment.payload) {
+                    if msg_type == HANDSHAKE_CLIENT_HELLO && !handshake.client_hello_seen {
+                        handshake.client_hello_seen = true;
+                        parse_client_hello(&body, &mut handshake.event);
+                        handshake.event.stage = "client_hello".to_string();
+                        handshake.event.timestamp_us = segment.timestamp_us;
+                        events.push(handshake.event.clone());
+                    }
+                }
+            } else {
+                for (msg_type, body) in handshake.server.push(segment.seq, segment.payload) {
+                    match msg_type {
+                        HANDSHAKE_SERVER_HELLO => {
+                            handshake.server_hello_seen = true;
+                            handshake.event.timestamp_us = segment.timestamp_us;
+                            parse_server_hello(&body, &mut handshake.event);
+                            // TLS 1.3 的后续握手消息（包括证书）都是加密的
+                            if handshake.event.version.as_deref() == Some("TLS 1.3") {
+                                finished = true;
+                            }
+                        }
+                        HANDSHAKE_CERTIFICATE => {
+                            handshake.event.certificate = parse_certificate(&body);
+                        }
+                        HANDSHAKE_SERVER_HELLO_DONE => finished = true,
+                        _ => {}
+                    }
+                }
+                finished |= handshake.server.encrypted || handshake.server.abandoned;
+            }
+        }
+
+        if finished {
+            if let Some(mut handshake) = self.handshakes.remove(&client_key) {
+                if handshake.server_hello_seen {
+                    handshake.event.stage = "server_hello".to_string();
+                    events.push(handshake.event);
+                }
+            }
+        }
+
+        events
+    }
+
+    fn evict_idle(&mut self, now_us: u64) {
+        self.handshakes
+            .retain(|_, h| now_us.saturating_sub(h.last_seen_us) < HANDSHAKE_IDLE_TIMEOUT_US);
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(key) = self
+            .handshakes
+            .iter()
+            .min_by_key(|(_, h)| h.last_seen_us)
+            .map(|(key, _)| *key)
+        {
+            debug!("TLS 握手跟踪数量达到上限，丢弃最旧的握手: {} -> {}", key.src, key.dst);
+            self.handshakes.remove(&key);
+        }
+    }
+}
+
+/// 载荷是否以 TLS ClientHello 记录开头
+pub(super) fn looks_like_client_hello(payload: &[u8]) -> bool {
+    payload.len() >= 6
+        && payload[0] == CONTENT_TYPE_HANDSHAKE
+        && payload[1] == 0x03
+        && payload[5] == HANDSHAKE_CLIENT_HELLO
+}
+
+/// 简单的大端读取游标，越界时返回 None
+pub(super) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(super) fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(slice)
+    }
+
+    pub(super) fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    pub(super) fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    pub(super) fn u24(&mut self) -> Option<usize> {
+        self.bytes(3).map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]) as usize)
+    }
+
+    /// 读取以 1 字节长度为前缀的数据
+    pub(super) fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()? as usize;
+        self.bytes(len)
+    }
+
+    /// 读取以 2 字节长度为前缀的数据
+    pub(super) fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.bytes(len)
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+/// 解析扩展列表，返回 (类型, 内容)
+pub(super) fn parse_extensions(reader: &mut Reader) -> Vec<(u16, Vec<u8>)> {
+    let mut extensions = Vec::new();
+    let Some(data) = reader.vec16() else {
+        return extensions;
+    };
+    let mut reader = Reader::new(data);
+    while !reader.is_empty() {
+        let (Some(ext_type), Some(body)) = (reader.u16(), reader.vec16()) else {
+            break;
+        };
+        extensions.push((ext_type, body.to_vec()));
+    }
+    extensions
+}
+
+fn parse_alpn(body: &[u8]) -> Vec<String> {
+    let mut protocols = Vec::new();
+    let Some(list) = Reader::new(body).vec16() else {
+        return protocols;
+    };
+    let mut reader = Reader::new(list);
+    while let Some(protocol) = reader.vec8() {
+        protocols.push(String::from_utf8_lossy(protocol).into_owned());
+    }
+    protocols
+}
+
+fn parse_client_hello(body: &[u8], event: &mut TlsConnectionEvent) {
+    let mut reader = Reader::new(body);
+    let Some(legacy_version) = reader.u16() else {
+        return;
+    };
+    event.version = Some(tls_version_name(legacy_version));
+
+    // random、session_id、cipher_suites、compression_methods
+    if reader.bytes(32).is_none()
+        || reader.vec8().is_none()
+        || reader.vec16().is_none()
+        || reader.vec8().is_none()
+    {
+        return;
+    }
+
+    for (ext_type, ext) in parse_extensions(&mut reader) {
+        match ext_type {
+            EXTENSION_SERVER_NAME => {
+                let Some(list) = Reader::new(&ext).vec16() else { continue };
+                let mut names = Reader::new(list);
+                while let (Some(name_type), Some(name)) = (names.u8(), names.vec16()) {
+                    // 0 表示 host_name
+                    if name_type == 0 {
+                        event.sni = Some(String::from_utf8_lossy(name).into_owned());
+                        break;
+                    }
+                }
+            }
+            EXTENSION_ALPN => event.client_alpn = parse_alpn(&ext),
+            EXTENSION_SUPPORTED_VERSIONS => {
+                let Some(list) = Reader::new(&ext).vec8() else { continue };
+                event.client_versions = list
+                    .chunks_exact(2)
+                    .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                    .filter(|v| !is_grease(*v))
+                    .map(tls_version_name)
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_server_hello(body: &[u8], event: &mut TlsConnectionEvent) {
+    let mut reader = Reader::new(body);
+    let Some(legacy_version) = reader.u16() else {
+        return;
+    };
+    event.version = Some(tls_version_name(legacy_version));
+
+    if reader.bytes(32).is_none() || reader.vec8().is_none() {
+        return;
+    }
+    let Some(cipher_suite) = reader.u16() else {
+        return;
+    };
+    event.cipher_suite = Some(cipher_suite_name(cipher_suite));
+    if reader.u8().is_none() {
+        return;
+    }
+
+    for (ext_type, ext) in parse_extensions(&mut reader) {
+        match ext_type {
+            EXTENSION_ALPN => event.alpn = parse_alpn(&ext).into_iter().next(),
+            // TLS 1.3 的真实版本在 supported_versions 扩展中
+            EXTENSION_SUPPORTED_VERSIONS if ext.len() == 2 => {
+                event.version = Some(tls_version_name(u16::from_be_bytes([ext[0], ext[1]])));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 解析 Certificate 握手消息中的叶子证书
+fn parse_certificate(body: &[u8]) -> Option<TlsCertificateInfo> {
+    let mut reader = Reader::new(body);
+    let list_len = reader.u24()?;
+    let mut list = Reader::new(reader.bytes(list_len)?);
+
+    let mut chain = Vec::new();
+    while let Some(len) = list.u24() {
+        chain.push(list.bytes(len)?);
+    }
+    let leaf = chain.first()?;
+
+    let (_, cert) = match x509_parser::parse_x509_certificate(leaf) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            debug!("解析服务器证书失败: {}", e);
+            return None;
+        }
+    };
+
+    let dns_names = cert
+        .subject_alternative_name()
+        .ok()
+        .flatten()
+        .map(|san| {
+            san.value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    x509_parser::extensions::GeneralName::DNSName(dns) => Some(dns.to_string()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(TlsCertificateInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        serial: cert.raw_serial_as_string(),
+        not_before: cert.validity().not_before.timestamp(),
+        not_after: cert.validity().not_after.timestamp(),
+        dns_names,
+        chain_length: chain.len(),
+    })
+}
+
+/// GREASE 保留值（RFC 8701），不代表真实版本
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && (value >> 8) == (value & 0xff)
+}
+
+fn tls_version_name(version: u16) -> String {
+    match version {
+        0x0300 => "SSL 3.0".to_string(),
+        0x0301 => "TLS 1.0".to_string(),
+        0x0302 => "TLS 1.1".to_string(),
+        0x0303 => "TLS 1.2".to_string(),
+        0x0304 => "TLS 1.3".to_string(),
+        other => format!("0x{:04x}", other),
+    }
+}
+
+fn cipher_suite_name(suite: u16) -> String {
+    let name = match suite {
+        0x1301 => "TLS_AES_128_GCM_SHA256",
+        0x1302 => "TLS_AES_256_GCM_SHA384",
+        0x1303 => "TLS_CHACHA20_POLY1305_SHA256",
+        0xc02b => "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+        0xc02c => "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+        0xc02f => "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+        0xc030 => "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+        0xcca8 => "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xcca9 => "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xc013 => "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA",
+        0xc014 => "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA",
+        0xc027 => "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256",
+        0xc028 => "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA384",
+        0x009c => "TLS_RSA_WITH_AES_128_GCM_SHA256",
+        0x009d => "TLS_RSA_WITH_AES_256_GCM_SHA384",
+        0x002f => "TLS_RSA_WITH_AES_128_CBC_SHA",
+        0x0035 => "TLS_RSA_WITH_AES_256_CBC_SHA",
+        0x003c => "TLS_RSA_WITH_AES_128_CBC_SHA256",
+        0x000a => "TLS_RSA_WITH_3DES_EDE_CBC_SHA",
+        0x00c0 => "TLS_SM4_GCM_SM3",
+        _ => return format!("0x{:04x}", suite),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn with_len16(data: &[u8]) -> Vec<u8> {
+        let mut out = (data.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn extension(ext_type: u16, body: &[u8]) -> Vec<u8> {
+        let mut out = ext_type.to_be_bytes().to_vec();
+        out.extend(with_len16(body));
+        out
+    }
+
+    fn record(msg_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut handshake = vec![msg_type];
+        handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        handshake.extend_from_slice(body);
+        let mut out = vec![CONTENT_TYPE_HANDSHAKE, 0x03, 0x01];
+        out.extend(with_len16(&handshake));
+        out
+    }
+
+    fn client_hello() -> Vec<u8> {
+        let mut sni = vec![0];
+        sni.extend(with_len16(b"portal.example.com"));
+        let mut extensions = extension(EXTENSION_SERVER_NAME, &with_len16(&sni));
+        extensions.extend(extension(EXTENSION_ALPN, &with_len16(b"\x02h2\x08http/1.1")));
+        extensions.extend(extension(EXTENSION_SUPPORTED_VERSIONS, b"\x06\x3a\x3a\x03\x04\x03\x03"));
+
+        let mut body = vec![0x03, 0x03];
+        body.extend([0u8; 32]);
+        body.push(0);
+        body.extend(with_len16(&[0x13, 0x01, 0xc0, 0x2f]));
+        body.extend([1, 0]);
+        body.extend(with_len16(&extensions));
+        record(HANDSHAKE_CLIENT_HELLO, &body)
+    }
+
+    fn server_hello() -> Vec<u8> {
+        let mut extensions = extension(EXTENSION_SUPPORTED_VERSIONS, &[0x03, 0x04]);
+        extensions.extend(extension(EXTENSION_ALPN, &with_len16(b"\x02h2")));
+
+        let mut body = vec![0x03, 0x03];
+        body.extend([0u8; 32]);
+        body.push(0);
+        body.extend([0x13, 0x01, 0]);
+        body.extend(with_len16(&extensions));
+        record(HANDSHAKE_SERVER_HELLO, &body)
+    }
+
+    fn segment(seq: u32, payload: &[u8]) -> TcpSegment<'_> {
+        TcpSegment { seq, syn: false, fin: false, rst: false, payload, timestamp_us: 1 }
+    }
+
+    #[test]
+    fn test_client_and_server_hello() {
+        let key = FlowKey::new(
+            "10.0.0.1:50000".parse::<SocketAddr>().unwrap(),
+            "10.0.0.2:443".parse::<SocketAddr>().unwrap(),
+        );
+        let mut tracker = TlsTracker::new();
+
+        // ClientHello 分两段到达
+        let hello = client_hello();
+        assert!(tracker.process_segment(key, &segment(1, &hello[..20])).is_empty());
+        let events = tracker.process_segment(key, &segment(21, &hello[20..]));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].sni.as_deref(), Some("portal.example.com"));
+        assert_eq!(events[0].client_alpn, vec!["h2", "http/1.1"]);
+        assert_eq!(events[0].client_versions, vec!["TLS 1.3", "TLS 1.2"]);
+
+        let events = tracker.process_segment(key.reversed(), &segment(1, &server_hello()));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].stage, "server_hello");
+        assert_eq!(events[0].version.as_deref(), Some("TLS 1.3"));
+        assert_eq!(events[0].cipher_suite.as_deref(), Some("TLS_AES_128_GCM_SHA256"));
+        assert_eq!(events[0].alpn.as_deref(), Some("h2"));
+        assert_eq!(tracker.connections(), 1);
+    }
+}
@@ -0,0 +1,1176 @@
+use super::reassembly::{FlowKey, ReassembledMessage, StreamState, TcpReassembler, TcpSegment};
+use super::tls::{self, Reader};
+use log::{debug, info};
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::{hkdf, hmac};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// 同时跟踪的最大 TLS 连接数量
+const MAX_SESSIONS: usize = 4096;
+/// 连接空闲超时时间（微秒）
+const SESSION_IDLE_TIMEOUT_US: u64 = 120 * 1_000_000;
+/// 两次清理空闲连接之间的最小间隔（微秒，按数据包时间计算）
+const EVICT_INTERVAL_US: u64 = 1_000_000;
+/// 等待密钥期间单个方向最多缓存的密文字节数
+const MAX_PENDING_CIPHERTEXT: usize = 1024 * 1024;
+/// 单个方向明文握手数据的最大缓冲字节数（只需要解析 Hello）
+const MAX_HANDSHAKE_BUFFER: usize = 64 * 1024;
+/// 记录层密文的最大长度（明文上限加上填充和认证标签）
+const MAX_RECORD_LEN: usize = 16384 + 2048;
+/// 找不到密钥时重新读取密钥日志的最小间隔
+const KEYLOG_REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+/// TLS 1.3 连续解密失败超过该次数后放弃该方向
+const MAX_DECRYPT_FAILURES: u32 = 16;
+const AEAD_TAG_LEN: usize = 16;
+
+const CONTENT_TYPE_CHANGE_CIPHER_SPEC: u8 = 20;
+const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+const CONTENT_TYPE_APPLICATION_DATA: u8 = 23;
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+const HANDSHAKE_SERVER_HELLO: u8 = 2;
+const EXTENSION_SUPPORTED_VERSIONS: u16 = 43;
+const VERSION_TLS13: u16 = 0x0304;
+
+/// 密钥日志中的密钥类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SecretLabel {
+    /// TLS 1.2 主密钥
+    MasterSecret,
+    ClientHandshake,
+    ServerHandshake,
+    ClientTraffic,
+    ServerTraffic,
+}
+
+impl SecretLabel {
+    fn parse(label: &str) -> Option<Self> {
+        match label {
+            "CLIENT_RANDOM" => Some(Self::MasterSecret),
+            "CLIENT_HANDSHAKE_TRAFFIC_SECRET" => Some(Self::ClientHandshake),
+            "SERVER_HANDSHAKE_TRAFFIC_SECRET" => Some(Self::ServerHandshake),
+            "CLIENT_TRAFFIC_SECRET_0" => Some(Self::ClientTraffic),
+            "SERVER_TRAFFIC_SECRET_0" => Some(Self::ServerTraffic),
+            _ => None,
+        }
+    }
+}
+
+/// 单个 TLS 会话的密钥，以 ClientHello 中的 random 为索引
+#[derive(Debug, Clone, Default)]
+struct SessionSecrets {
+    master_secret: Option<Vec<u8>>,
+    client_handshake: Option<Vec<u8>>,
+    server_handshake: Option<Vec<u8>>,
+    client_traffic: Option<Vec<u8>>,
+    server_traffic: Option<Vec<u8>>,
+}
+
+impl SessionSecrets {
+    fn get(&self, label: SecretLabel) -> Option<&Vec<u8>> {
+        match label {
+            SecretLabel::MasterSecret => self.master_secret.as_ref(),
+            SecretLabel::ClientHandshake => self.client_handshake.as_ref(),
+            SecretLabel::ServerHandshake => self.server_handshake.as_ref(),
+            SecretLabel::ClientTraffic => self.client_traffic.as_ref(),
+            SecretLabel::ServerTraffic => self.server_traffic.as_ref(),
+        }
+    }
+
+    fn set(&mut self, label: SecretLabel, secret: Vec<u8>) {
+        let slot = match label {
+            SecretLabel::MasterSecret => &mut self.master_secret,
+            SecretLabel::ClientHandshake => &mut self.client_handshake,
+            SecretLabel::ServerHandshake => &mut self.server_handshake,
+            SecretLabel::ClientTraffic => &mut self.client_traffic,
+            SecretLabel::ServerTraffic => &mut self.server_traffic,
+        };
+        *slot = Some(secret);
+    }
+}
+
+/// SSLKEYLOGFILE 格式的密钥日志
+///
+/// 浏览器在运行期间持续向文件追加密钥，这里记录已读取的位置，
+/// 找不到密钥时只解析新增的完整行。
+#[derive(Debug)]
+pub struct KeyLog {
+    path: PathBuf,
+    /// 已解析到的文件偏移
+    offset: u64,
+    last_refresh: Option<Instant>,
+    secrets: HashMap<[u8; 32], SessionSecrets>,
+}
+
+impl KeyLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            offset: 0,
+            last_refresh: None,
+            secrets: HashMap::new(),
+        }
+    }
+
+    /// 查找指定类型的密钥，找不到时重新读取文件后再查一次
+    fn find(&mut self, client_random: &[u8; 32], label: SecretLabel) -> Option<Vec<u8>> {
+        if let Some(secret) = self.secrets.get(client_random).and_then(|s| s.get(label)) {
+            return Some(secret.clone());
+        }
+        self.refresh();
+        self.secrets.get(client_random).and_then(|s| s.get(label)).cloned()
+    }
+
+    /// 读取文件新增的内容，距上次读取不足刷新间隔时跳过
+    fn refresh(&mut self) {
+        if self.last_refresh.is_some_and(|t| t.elapsed() < KEYLOG_REFRESH_INTERVAL) {
+            return;
+        }
+        self.last_refresh = Some(Instant::now());
+        if let Err(e) = self.read_new_lines() {
+            debug!("读取密钥日志失败 {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn read_new_lines(&mut self) -> std::io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            // 文件被截断或重新创建，从头读取
+            self.offset = 0;
+        }
+        if len == self.offset {
+            return Ok(());
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut data = Vec::new();
+        file.take(len - self.offset).read_to_end(&mut data)?;
+
+        // 最后一行可能还没写完，留到下次读取
+        let Some(end) = data.iter().rposition(|b| *b == b'\n') else {
+            return Ok(());
+        };
+        let before = self.secrets.len();
+        for line in String::from_utf8_lossy(&data[..end]).lines() {
+            self.parse_line(line);
+        }
+        self.offset += end as u64 + 1;
+        debug!("密钥日志新增 {} 个会话，共 {} 个", self.secrets.len() - before, self.secrets.len());
+        Ok(())
+    }
+
+    /// 解析一行 `<标签> <client_random> <密钥>`，忽略注释和不支持的标签
+    fn parse_line(&mut self, line: &str) {
+        let mut parts = line.split_whitespace();
+        let (Some(label), Some(random), Some(secret)) = (parts.next(), parts.next(), parts.next()) else {
+            return;
+        };
+        let Some(label) = SecretLabel::parse(label) else {
+            return;
+        };
+        let (Some(random), Some(secret)) = (decode_hex(random), decode_hex(secret)) else {
+            return;
+        };
+        let Ok(random) = <[u8; 32]>::try_from(random) else {
+            return;
+        };
+        self.secrets.entry(random).or_default().set(label, secret);
+    }
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.is_ascii() || !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HashAlgorithm {
+    Sha256,
+    Sha384,
+}
+
+impl HashAlgorithm {
+    fn hkdf(self) -> hkdf::Algorithm {
+        match self {
+            HashAlgorithm::Sha256 => hkdf::HKDF_SHA256,
+            HashAlgorithm::Sha384 => hkdf::HKDF_SHA384,
+        }
+    }
+
+    fn hmac(self) -> hmac::Algorithm {
+        match self {
+            HashAlgorithm::Sha256 => hmac::HMAC_SHA256,
+            HashAlgorithm::Sha384 => hmac::HMAC_SHA384,
+        }
+    }
+}
+
+/// 支持解密的 AEAD 密码套件
+#[derive(Debug, Clone, Copy)]
+struct CipherSuite {
+    aead: &'static aead::Algorithm,
+    hash: HashAlgorithm,
+    /// TLS 1.2 的 AES-GCM：4 字节隐式 IV 加上记录开头的 8 字节显式 nonce
+    explicit_nonce: bool,
+}
+
+impl CipherSuite {
+    fn from_id(id: u16) -> Option<Self> {
+        let (aead, hash, explicit_nonce) = match id {
+            0x1301 => (&aead::AES_128_GCM, HashAlgorithm::Sha256, false),
+            0x1302 => (&aead::AES_256_GCM, HashAlgorithm::Sha384, false),
+            0x1303 => (&aead::CHACHA20_POLY1305, HashAlgorithm::Sha256, false),
+            0xc02b | 0xc02f | 0x009c | 0x009e => (&aead::AES_128_GCM, HashAlgorithm::Sha256, true),
+            0xc02c | 0xc030 | 0x009d | 0x009f => (&aead::AES_256_GCM, HashAlgorithm::Sha384, true),
+            0xcca8..=0xccaa => (&aead::CHACHA20_POLY1305, HashAlgorithm::Sha256, false),
+            _ => return None,
+        };
+        Some(Self { aead, hash, explicit_nonce })
+    }
+
+    /// TLS 1.2 密钥块中每个方向的 IV 长度
+    fn fixed_iv_len(&self) -> usize {
+        if self.explicit_nonce {
+            4
+        } else {
+            12
+        }
+    }
+}
+
+/// 单个方向的记录层解密密钥
+struct RecordCipher {
+    key: LessSafeKey,
+    iv: [u8; 12],
+    explicit_nonce: bool,
+    tls13: bool,
+}
+
+impl RecordCipher {
+    fn new(suite: CipherSuite, key: &[u8], iv: &[u8], tls13: bool) -> Option<Self> {
+        let key = LessSafeKey::new(UnboundKey::new(suite.aead, key).ok()?);
+        let mut fixed_iv = [0u8; 12];
+        fixed_iv.get_mut(..iv.len())?.copy_from_slice(iv);
+        Some(Self {
+            key,
+            iv: fixed_iv,
+            explicit_nonce: suite.explicit_nonce,
+            tls13,
+        })
+    }
+
+    /// 解密一条记录，返回 (内容类型, 明文)；TLS 1.3 的真实内容类型在明文末尾
+    fn decrypt(&self, seq: u64, content_type: u8, version: u16, body: &[u8]) -> Option<(u8, Vec<u8>)> {
+        let mut nonce = self.iv;
+        let ciphertext = if self.explicit_nonce {
+            nonce[4..].copy_from_slice(body.get(..8)?);
+            &body[8..]
+        } else {
+            for (n, s) in nonce[4..].iter_mut().zip(seq.to_be_bytes()) {
+                *n ^= s;
+            }
+            body
+        };
+        let plain_len = ciphertext.len().checked_sub(AEAD_TAG_LEN)?;
+
+        let mut aad = Vec::with_capacity(13);
+        if self.tls13 {
+            // TLS 1.3 以记录头作为附加数据
+            aad.push(content_type);
+            aad.extend_from_slice(&version.to_be_bytes());
+            aad.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        } else {
+            aad.extend_from_slice(&seq.to_be_bytes());
+            aad.push(content_type);
+            aad.extend_from_slice(&version.to_be_bytes());
+            aad.extend_from_slice(&(plain_len as u16).to_be_bytes());
+        }
+
+        let mut data = ciphertext.to_vec();
+        let len = self
+            .key
+            .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(&aad[..]), &mut data)
+            .ok()?
+            .len();
+        data.truncate(len);
+        if !self.tls13 {
+            return Some((content_type, data));
+        }
+
+        // 去掉末尾的 0 填充，最后一个非 0 字节是内容类型
+        let end = data.iter().rposition(|b| *b != 0)?;
+        let inner_type = data[end];
+        data.truncate(end);
+        Some((inner_type, data))
+    }
+}
+
+/// TLS 1.3 的 HKDF-Expand-Label（上下文为空）
+fn hkdf_expand_label(hash: HashAlgorithm, secret: &[u8], label: &[u8], len: usize) -> Option<Vec<u8>> {
+    struct OutputLen(usize);
+    impl hkdf::KeyType for OutputLen {
+        fn len(&self) -> usize {
+            self.0
+        }
+    }
+
+    let prk = hkdf::Prk::new_less_safe(hash.hkdf(), secret);
+    let length = (len as u16).to_be_bytes();
+    let label_len = [(b"tls13 ".len() + label.len()) as u8];
+    let info: [&[u8]; 5] = [&length, &label_len, b"tls13 ", label, &[0]];
+    let mut out = vec![0u8; len];
+    prk.expand(&info, OutputLen(len)).ok()?.fill(&mut out).ok()?;
+    Some(out)
+}
+
+/// TLS 1.2 的 PRF（P_hash）
+fn prf(hash: HashAlgorithm, secret: &[u8], label: &[u8], seed: &[u8], len: usize) -> Vec<u8> {
+    let key = hmac::Key::new(hash.hmac(), secret);
+    let label_seed = [label, seed].concat();
+    let mut a = hmac::sign(&key, &label_seed).as_ref().to_vec();
+    let mut out = Vec::with_capacity(len);
+    while out.len() < len {
+        let mut context = hmac::Context::with_key(&key);
+        context.update(&a);
+        context.update(&label_seed);
+        out.extend_from_slice(context.sign().as_ref());
+        a = hmac::sign(&key, &a).as_ref().to_vec();
+    }
+    out.truncate(len);
+    out
+}
+
+/// TLS 1.3：由流量密钥派生记录层密钥
+fn tls13_cipher(suite: CipherSuite, secret: &[u8]) -> Option<RecordCipher> {
+    let key = hkdf_expand_label(suite.hash, secret, b"key", suite.aead.key_len())?;
+    let iv = hkdf_expand_label(suite.hash, secret, b"iv", 12)?;
+    RecordCipher::new(suite, &key, &iv, true)
+}
+
+/// TLS 1.2：由主密钥展开指定方向的记录层密钥（AEAD 套件没有 MAC 密钥）
+fn tls12_cipher(
+    suite: CipherSuite,
+    master_secret: &[u8],
+    client_random: &[u8; 32],
+    server_random: &[u8; 32],
+    from_client: bool,
+) -> Option<RecordCipher> {
+    let key_len = suite.aead.key_len();
+    let iv_len = suite.fixed_iv_len();
+    let seed = [&server_random[..], &client_random[..]].concat();
+    let block = prf(suite.hash, master_secret, b"key expansion", &seed, 2 * (key_len + iv_len));
+    let (client_key, rest) = block.split_at(key_len);
+    let (server_key, rest) = rest.split_at(key_len);
+    let (client_iv, server_iv) = rest.split_at(iv_len);
+    if from_client {
+        RecordCipher::new(suite, client_key, client_iv, false)
+    } else {
+        RecordCipher::new(suite, server_key, server_iv, false)
+    }
+}
+
+/// 单条记录的处理结果
+enum RecordOutcome {
+    /// 记录已处理，附带解密出的应用数据
+    Done(Vec<u8>),
+    /// 尚未获得密钥，保留记录稍后重试
+    Pending,
+    /// 无法继续解密该方向
+    Failed,
+}
+
+/// 单个方向的记录层状态
+struct Direction {
+    stream: StreamState,
+    /// 尚未凑成完整消息的明文握手数据
+    handshake: Vec<u8>,
+    cipher: Option<RecordCipher>,
+    /// 当前密钥下的记录序号
+    seq: u64,
+    /// TLS 1.2 收到 ChangeCipherSpec 后，后续记录均为密文
+    encrypted: bool,
+    /// TLS 1.3 已切换到应用数据密钥
+    application_keys: bool,
+    failures: u32,
+    /// 解密后明文流的偏移，作为明文重组的序列号
+    plain_offset: u32,
+    abandoned: bool,
+    closed: bool,
+}
+
+impl Direction {
+    fn new(now_us: u64) -> Self {
+        Self {
+            stream: StreamState::new(now_us),
+            handshake: Vec::new(),
+            cipher: None,
+            seq: 0,
+            encrypted: false,
+            application_keys: false,
+            failures: 0,
+            plain_offset: 0,
+            abandoned: false,
+            closed: false,
+        }
+    }
+
+    fn abandon(&mut self) {
+        self.abandoned = true;
+        self.stream.buffer = Vec::new();
+        self.handshake = Vec::new();
+    }
+}
+
+/// 单个 TLS 连接的解密状态
+struct TlsSession {
+    client_random: Option<[u8; 32]>,
+    server_random: Option<[u8; 32]>,
+    suite: Option<CipherSuite>,
+    tls13: bool,
+    server_hello_seen: bool,
+    client: Direction,
+    server: Direction,
+    /// 是否已成功解密过记录
+    decrypted: bool,
+    last_seen_us: u64,
+}
+
+impl TlsSession {
+    fn new(now_us: u64) -> Self {
+        Self {
+            client_random: None,
+            server_random: None,
+            suite: None,
+            tls13: false,
+            server_hello_seen: false,
+            client: Direction::new(now_us),
+            server: Direction::new(now_us),
+            decrypted: false,
+            last_seen_us: now_us,
+        }
+    }
+
+    fn direction(&mut self, from_client: bool) -> &mut Direction {
+        if from_client {
+            &mut self.client
+        } else {
+            &mut self.server
+        }
+    }
+
+    /// 处理缓冲区中所有完整的记录，返回解密出的应用数据
+    fn drain_records(&mut self, from_client: bool, keylog: &mut KeyLog) -> Vec<u8> {
+        let mut plaintext = Vec::new();
+        loop {
+            let direction = self.direction(from_client);
+            let buffer = &direction.stream.buffer;
+            if direction.abandoned || buffer.len() < 5 {
+                break;
+            }
+            let content_type = buffer[0];
+            let version = u16::from_be_bytes([buffer[1], buffer[2]]);
+            let length = u16::from_be_bytes([buffer[3], buffer[4]]) as usize;
+            if !(CONTENT_TYPE_CHANGE_CIPHER_SPEC..=CONTENT_TYPE_APPLICATION_DATA).contains(&content_type)
+                || buffer[1] != 0x03
+                || length > MAX_RECORD_LEN
+            {
+                // 数据缺失导致记录边界错位，无法继续
+                debug!("TLS 记录头无效，停止解密该方向");
+                direction.abandon();
+                break;
+            }
+            if buffer.len() < 5 + length {
+                break;
+            }
+
+            let body = buffer[5..5 + length].to_vec();
+            match self.handle_record(from_client, content_type, version, &body, keylog) {
+                RecordOutcome::Done(data) => {
+                    self.direction(from_client).stream.buffer.drain(..5 + length);
+                    plaintext.extend_from_slice(&data);
+                }
+                RecordOutcome::Pending => {
+                    let direction = self.direction(from_client);
+                    if direction.stream.buffer.len() > MAX_PENDING_CIPHERTEXT {
+                        debug!("密钥日志中没有该连接的密钥，停止等待");
+                        direction.abandon();
+                    }
+                    break;
+                }
+                RecordOutcome::Failed => {
+                    self.direction(from_client).abandon();
+                    break;
+                }
+            }
+        }
+        plaintext
+    }
+
+    fn handle_record(
+        &mut self,
+        from_client: bool,
+        content_type: u8,
+        version: u16,
+        body: &[u8],
+        keylog: &mut KeyLog,
+    ) -> RecordOutcome {
+        let encrypted = if self.tls13 {
+            // TLS 1.3 在 ServerHello 之后的所有数据都以 application_data 记录发送
+            self.server_hello_seen && content_type == CONTENT_TYPE_APPLICATION_DATA
+        } else {
+            self.direction(from_client).encrypted
+        };
+
+        if !encrypted {
+            match content_type {
+                CONTENT_TYPE_HANDSHAKE => self.read_handshake(from_client, body),
+                CONTENT_TYPE_CHANGE_CIPHER_SPEC if !self.tls13 => {
+                    let direction = self.direction(from_client);
+                    direction.encrypted = true;
+                    direction.seq = 0;
+                }
+                _ => {}
+            }
+            return RecordOutcome::Done(Vec::new());
+        }
+
+        if self.direction(from_client).cipher.is_none() {
+            match self.derive_cipher(from_client, keylog) {
+                Some(cipher) => self.direction(from_client).cipher = Some(cipher),
+                None => return RecordOutcome::Pending,
+            }
+        }
+
+        let tls13 = self.tls13;
+        let direction = self.direction(from_client);
+        let Some(cipher) = direction.cipher.as_ref() else {
+            return RecordOutcome::Pending;
+        };
+        match cipher.decrypt(direction.seq, content_type, version, body) {
+            Some((inner_type, data)) => {
+                direction.seq += 1;
+                direction.failures = 0;
+                self.decrypted = true;
+                if inner_type == CONTENT_TYPE_APPLICATION_DATA {
+                    RecordOutcome::Done(data)
+                } else {
+                    RecordOutcome::Done(Vec::new())
+                }
+            }
+            None if tls13 && !direction.application_keys => {
+                // 握手密钥无法解密，说明握手已结束，改用应用数据密钥重试
+                direction.application_keys = true;
+                direction.cipher = None;
+                direction.seq = 0;
+                self.handle_record(from_client, content_type, version, body, keylog)
+            }
+            None if tls13 && direction.failures < MAX_DECRYPT_FAILURES => {
+                // 缺少握手密钥时，握手阶段的记录无法用应用数据密钥解密，跳过即可
+                direction.failures += 1;
+                RecordOutcome::Done(Vec::new())
+            }
+            None => {
+                debug!("TLS 记录解密失败，停止解密该方向");
+                RecordOutcome::Failed
+            }
+        }
+    }
+
+    /// 根据密钥日志生成指定方向当前阶段的密钥
+    fn derive_cipher(&mut self, from_client: bool, keylog: &mut KeyLog) -> Option<RecordCipher> {
+        let client_random = self.client_random?;
+        let suite = self.suite?;
+
+        if !self.tls13 {
+            let master_secret = keylog.find(&client_random, SecretLabel::MasterSecret)?;
+            return tls12_cipher(suite, &master_secret, &client_random, &self.server_random?, from_client);
+        }
+
+        let (handshake_label, traffic_label) = if from_client {
+            (SecretLabel::ClientHandshake, SecretLabel::ClientTraffic)
+        } else {
+            (SecretLabel::ServerHandshake, SecretLabel::ServerTraffic)
+        };
+        let direction = self.direction(from_client);
+        if !direction.application_keys {
+            if let Some(secret) = keylog.find(&client_random, handshake_label) {
+                return tls13_cipher(suite, &secret);
+            }
+        }
+        // 密钥日志中只有应用数据密钥时直接使用
+        let secret = keylog.find(&client_random, traffic_label)?;
+        direction.application_keys = true;
+        direction.seq = 0;
+        tls13_cipher(suite, &secret)
+    }
+
+    /// 解析明文握手记录，只提取 ClientHello 和 ServerHello
+    fn read_handshake(&mut self, from_client: bool, body: &[u8]) {
+        let wanted = if from_client { self.client_random.is_none() } else { !self.server_hello_seen };
+        let direction = self.direction(from_client);
+        if !wanted || direction.handshake.len() + body.len() > MAX_HANDSHAKE_BUFFER {
+            direction.handshake.clear();
+            return;
+        }
+        direction.handshake.extend_from_slice(body);
+
+        let mut messages = Vec::new();
+        let mut pos = 0;
+        let handshake = &direction.handshake;
+        while handshake.len() - pos >= 4 {
+            let msg_type = handshake[pos];
+            let length = u32::from_be_bytes([0, handshake[pos + 1], handshake[pos + 2], handshake[pos + 3]]) as usize;
+            if handshake.len() - pos < 4 + length {
+                break;
+            }
+            messages.push((msg_type, handshake[pos + 4..pos + 4 + length].to_vec()));
+            pos += 4 + length;
+        }
+        direction.handshake.drain(..pos);
+
+        for (msg_type, message) in messages {
+            match msg_type {
+                HANDSHAKE_CLIENT_HELLO if from_client => self.client_random = hello_random(&message),
+                HANDSHAKE_SERVER_HELLO if !from_client => self.read_server_hello(&message),
+                _ => {}
+            }
+        }
+    }
+
+    fn read_server_hello(&mut self, body: &[u8]) {
+        let mut reader = Reader::new(body);
+        if reader.u16().is_none() {
+            return;
+        }
+        let Some(random) = reader.bytes(32).and_then(|r| <[u8; 32]>::try_from(r).ok()) else {
+            return;
+        };
+        let (Some(_), Some(suite_id), Some(_)) = (reader.vec8(), reader.u16(), reader.u8()) else {
+            return;
+        };
+        self.server_random = Some(random);
+        self.server_hello_seen = true;
+        self.tls13 = tls::parse_extensions(&mut reader).iter().any(|(ext_type, ext)| {
+            *ext_type == EXTENSION_SUPPORTED_VERSIONS
+                && ext.len() == 2
+                && u16::from_be_bytes([ext[0], ext[1]]) == VERSION_TLS13
+        });
+        self.suite = CipherSuite::from_id(suite_id);
+        if self.suite.is_none() {
+            debug!("不支持解密的密码套件 0x{:04x}", suite_id);
+            self.client.abandon();
+            self.server.abandon();
+        }
+    }
+}
+
+/// Hello 消息中的 random
+fn hello_random(body: &[u8]) -> Option<[u8; 32]> {
+    let mut reader = Reader::new(body);
+    reader.u16()?;
+    reader.bytes(32)?.try_into().ok()
+}
+
+/// 使用 SSLKEYLOGFILE 解密 TLS 连接
+///
+/// 只跟踪以 ClientHello 开头的连接，支持 TLS 1.2 和 TLS 1.3 的 AES-GCM 与
+/// ChaCha20-Poly1305 套件。解密出的应用数据按 HTTP/1.1 重新组装成完整消息。
+pub struct TlsDecryptor {
+    keylog: KeyLog,
+    /// 以客户端 -> 服务器方向为键
+    sessions: HashMap<FlowKey, TlsSession>,
+    /// 解密后的明文单独重组，与密文流互不影响
+    plaintext: TcpReassembler,
+    /// 成功解密的连接数量
+    decrypted_connections: u64,
+    /// 上次清理空闲连接时的数据包时间
+    last_evict_us: u64,
+}
+
+impl TlsDecryptor {
+    pub fn new(keylog: KeyLog) -> Self {
+        Self {
+            keylog,
+            sessions: HashMap::new(),
+            plaintext: TcpReassembler::new(),
+            decrypted_connections: 0,
+            last_evict_us: 0,
+        }
+    }
+
+    pub fn decrypted_connections(&self) -> u64 {
+        self.decrypted_connections
+    }
+
+    /// 处理一个 TCP 段，返回解密后完整的 HTTP 消息
+    pub fn process_segment(&mut self, key: FlowKey, segment: &TcpSegment) -> Vec<ReassembledMessage> {
+        self.evict_idle(segment.timestamp_us);
+
+        let (client_key, from_client) = if self.sessions.contains_key(&key) {
+            (key, true)
+        } else if self.sessions.contains_key(&key.reversed()) {
+            (key.reversed(), false)
+        } else if tls::looks_like_client_hello(segment.payload) {
+            if self.sessions.len() >= MAX_SESSIONS {
+                self.evict_oldest();
+            }
+            self.sessions.insert(key, TlsSession::new(segment.timestamp_us));
+            (key, true)
+        } else {
+            return Vec::new();
+        };
+
+        let Some(session) = self.sessions.get_mut(&client_key) else {
+            return Vec::new();
+        };
+        session.last_seen_us = segment.timestamp_us;
+        let was_decrypted = session.decrypted;
+
+        let direction = session.direction(from_client);
+        if segment.fin || segment.rst {
+            direction.closed = true;
+        }
+        if !direction.abandoned {
+            direction.stream.accept(segment);
+        }
+
+        // 另一方向可能有等待密钥的记录，先处理以保证请求在响应之前
+        let other = session.drain_records(!from_client, &mut self.keylog);
+        let current = session.drain_records(from_client, &mut self.keylog);
+
+        if session.decrypted && !was_decrypted {
+            self.decrypted_connections += 1;
+            info!("已使用密钥日志解密 TLS 连接: {} -> {}", client_key.src, client_key.dst);
+        }
+
+        let mut messages = Vec::new();
+        if session.decrypted {
+            let chunks = [
+                (!from_client, other, false, false),
+                (from_client, current, segment.fin, segment.rst),
+            ];
+            for (from_client, data, fin, rst) in chunks {
+                if data.is_empty() && !fin && !rst {
+                    continue;
+                }
+                let flow = if from_client { client_key } else { client_key.reversed() };
+                let direction = session.direction(from_client);
+                let seq = direction.plain_offset;
+                direction.plain_offset = seq.wrapping_add(data.len() as u32);
+                messages.extend(self.plaintext.process_segment(flow, TcpSegment {
+                    seq,
+                    syn: false,
+                    fin,
+                    rst,
+                    payload: &data,
+                    timestamp_us: segment.timestamp_us,
+                }));
+            }
+        }
+
+        if segment.rst || (session.client.closed && session.server.closed) {
+            self.sessions.remove(&client_key);
+        }
+        messages
+    }
+
+    /// 输入结束时输出已解密明文中剩余的消息，并清空全部连接
+    pub fn flush(&mut self) -> Vec<ReassembledMessage> {
+        self.sessions.clear();
+        self.plaintext.flush()
+    }
+
+    /// 清理空闲超时的连接，距上次清理不足清理间隔时跳过
+    fn evict_idle(&mut self, now_us: u64) {
+        if now_us.saturating_sub(self.last_evict_us) < EVICT_INTERVAL_US {
+            return;
+        }
+        self.last_evict_us = now_us;
+        self.sessions
+            .retain(|_, s| now_us.saturating_sub(s.last_seen_us) < SESSION_IDLE_TIMEOUT_US);
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(key) = self
+            .sessions
+            .iter()
+            .min_by_key(|(_, s)| s.last_seen_us)
+            .map(|(key, _)| *key)
+        {
+            debug!("TLS 解密跟踪数量达到上限，丢弃最旧的连接: {} -> {}", key.src, key.dst);
+            self.sessions.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::SocketAddr;
+
+    fn record(content_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = vec![content_type, 0x03, 0x03];
+        out.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn handshake(msg_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = vec![msg_type];
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        out.extend_from_slice(body);
+        record(CONTENT_TYPE_HANDSHAKE, &out)
+    }
+
+    fn client_hello(random: &[u8; 32], suite_id: u16) -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(random);
+        body.extend_from_slice(&[0, 0, 2]);
+        body.extend_from_slice(&suite_id.to_be_bytes());
+        body.extend_from_slice(&[1, 0, 0, 0]);
+        handshake(HANDSHAKE_CLIENT_HELLO, &body)
+    }
+
+    fn server_hello(random: &[u8; 32], suite_id: u16, tls13: bool) -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(random);
+        body.push(0);
+        body.extend_from_slice(&suite_id.to_be_bytes());
+        body.push(0);
+        if tls13 {
+            body.extend_from_slice(&[0, 6, 0, 43, 0, 2, 0x03, 0x04]);
+        } else {
+            body.extend_from_slice(&[0, 0]);
+        }
+        handshake(HANDSHAKE_SERVER_HELLO, &body)
+    }
+
+    /// 按记录层格式加密一条记录，TLS 1.3 的内容类型附在明文末尾
+    fn seal_record(
+        suite: CipherSuite,
+        key: &[u8],
+        iv: &[u8],
+        tls13: bool,
+        seq: u64,
+        content_type: u8,
+        plaintext: &[u8],
+    ) -> Vec<u8> {
+        let key = LessSafeKey::new(UnboundKey::new(suite.aead, key).unwrap());
+        let mut nonce = [0u8; 12];
+        nonce[..iv.len()].copy_from_slice(iv);
+        let mut explicit = Vec::new();
+        if suite.explicit_nonce {
+            // 显式 nonce 与序号无关，解密时必须从记录中读取
+            explicit = (!seq).to_be_bytes().to_vec();
+            nonce[4..].copy_from_slice(&explicit);
+        } else {
+            for (n, s) in nonce[4..].iter_mut().zip(seq.to_be_bytes()) {
+                *n ^= s;
+            }
+        }
+
+        let mut data = plaintext.to_vec();
+        let outer_type = if tls13 {
+            data.push(content_type);
+            CONTENT_TYPE_APPLICATION_DATA
+        } else {
+            content_type
+        };
+        let length = (explicit.len() + data.len() + AEAD_TAG_LEN) as u16;
+        let mut header = vec![outer_type, 0x03, 0x03];
+        header.extend_from_slice(&length.to_be_bytes());
+        let aad = if tls13 {
+            header.clone()
+        } else {
+            let mut aad = seq.to_be_bytes().to_vec();
+            aad.extend_from_slice(&header[..3]);
+            aad.extend_from_slice(&(plaintext.len() as u16).to_be_bytes());
+            aad
+        };
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(&aad[..]), &mut data)
+            .unwrap();
+        header.extend_from_slice(&explicit);
+        header.extend_from_slice(&data);
+        header
+    }
+
+    /// 用 TLS 1.3 流量密钥加密一条记录
+    fn seal(suite_id: u16, secret: &[u8], seq: u64, inner_type: u8, plaintext: &[u8]) -> Vec<u8> {
+        let suite = CipherSuite::from_id(suite_id).unwrap();
+        let key = hkdf_expand_label(suite.hash, secret, b"key", suite.aead.key_len()).unwrap();
+        let iv = hkdf_expand_label(suite.hash, secret, b"iv", 12).unwrap();
+        seal_record(suite, &key, &iv, true, seq, inner_type, plaintext)
+    }
+
+    /// 按 RFC 5246 的顺序切分密钥块：客户端密钥、服务器密钥、客户端 IV、服务器 IV
+    fn tls12_keys(
+        suite: CipherSuite,
+        master_secret: &[u8],
+        client_random: &[u8; 32],
+        server_random: &[u8; 32],
+    ) -> Vec<Vec<u8>> {
+        let (key_len, iv_len) = (suite.aead.key_len(), suite.fixed_iv_len());
+        let seed = [&server_random[..], &client_random[..]].concat();
+        let block = prf(suite.hash, master_secret, b"key expansion", &seed, 2 * (key_len + iv_len));
+        let mut keys = Vec::new();
+        let mut pos = 0;
+        for len in [key_len, key_len, iv_len, iv_len] {
+            keys.push(block[pos..pos + len].to_vec());
+            pos += len;
+        }
+        keys
+    }
+
+    fn send(decryptor: &mut TlsDecryptor, key: FlowKey, seq: &mut u32, payload: &[u8]) -> Vec<ReassembledMessage> {
+        let segment = TcpSegment {
+            seq: *seq,
+            syn: false,
+            fin: false,
+            rst: false,
+            payload,
+            timestamp_us: 1_000_000,
+        };
+        *seq = seq.wrapping_add(payload.len() as u32);
+        decryptor.process_segment(key, &segment)
+    }
+
+    fn flow_keys() -> (FlowKey, FlowKey) {
+        let client: SocketAddr = "10.0.0.2:50000".parse().unwrap();
+        let server: SocketAddr = "93.184.216.34:443".parse().unwrap();
+        (FlowKey::new(client, server), FlowKey::new(server, client))
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn keylog_line(label: &str, client_random: &[u8; 32], secret: &[u8]) -> String {
+        format!("{} {} {}\n", label, hex(client_random), hex(secret))
+    }
+
+    fn temp_keylog(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("keylog-{}-test-{}.txt", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_prf_matches_reference_vector() {
+        // TLS 1.2 PRF (SHA-256) 的公开测试向量
+        let secret = decode_hex("9bbe436ba940f017b17652849a71db35").unwrap();
+        let seed = decode_hex("a0ba9f936cda311827a6f796ffd5198c").unwrap();
+        let expected = decode_hex(concat!(
+            "e3f229ba727be17b8d122620557cd453c2aab21d07c3d495329b52d4e61edb5a",
+            "6b301791e90d35c9c9a46b4e14baf9af0fa022f7077def17abfd3797c0564bab",
+            "4fbc91666e9def9b97fce34f796789baa48082d122ee42c5a72e5a5110fff701",
+            "87347b66"
+        ))
+        .unwrap();
+
+        assert_eq!(prf(HashAlgorithm::Sha256, &secret, b"test label", &seed, 100), expected);
+        // 输出长度不是摘要长度的整数倍时截取前缀
+        assert_eq!(prf(HashAlgorithm::Sha256, &secret, b"test label", &seed, 40), expected[..40]);
+    }
+
+    #[test]
+    fn test_decrypt_tls12_after_change_cipher_spec() {
+        let client_random = [0x55u8; 32];
+        let server_random = [0x66u8; 32];
+        let master_secret = [0x77u8; 48];
+        let finished = [20, 0, 0, 12, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        let request = b"GET /login HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let (up, down) = flow_keys();
+
+        // AES-GCM 使用 4 字节隐式 IV 加显式 nonce，ChaCha20-Poly1305 使用与序号异或的 12 字节 IV
+        for suite_id in [0xc02f, 0xc030, 0xcca8] {
+            let suite = CipherSuite::from_id(suite_id).unwrap();
+            let path = temp_keylog(
+                &format!("tls12-{:04x}", suite_id),
+                &keylog_line("CLIENT_RANDOM", &client_random, &master_secret),
+            );
+            let keys = tls12_keys(suite, &master_secret, &client_random, &server_random);
+            let seal12 = |from_client: bool, seq: u64, content_type: u8, plaintext: &[u8]| {
+                let (key, iv) = if from_client { (&keys[0], &keys[2]) } else { (&keys[1], &keys[3]) };
+                seal_record(suite, key, iv, false, seq, content_type, plaintext)
+            };
+
+            let mut decryptor = TlsDecryptor::new(KeyLog::new(&path));
+            let (mut client_seq, mut server_seq) = (1000u32, 5000u32);
+            assert!(send(&mut decryptor, up, &mut client_seq, &client_hello(&client_random, suite_id)).is_empty());
+            let hello = server_hello(&server_random, suite_id, false);
+            assert!(send(&mut decryptor, down, &mut server_seq, &hello).is_empty());
+            // ChangeCipherSpec 之前的握手记录是明文，不能当作密文解密
+            assert!(send(&mut decryptor, up, &mut client_seq, &handshake(16, &[0x42; 33])).is_empty());
+
+            // ChangeCipherSpec 之后序号从 0 开始，Finished 是第一条密文记录
+            let mut client_flight = record(CONTENT_TYPE_CHANGE_CIPHER_SPEC, &[1]);
+            client_flight.extend(seal12(true, 0, CONTENT_TYPE_HANDSHAKE, &finished));
+            assert!(send(&mut decryptor, up, &mut client_seq, &client_flight).is_empty());
+            let mut server_flight = record(CONTENT_TYPE_CHANGE_CIPHER_SPEC, &[1]);
+            server_flight.extend(seal12(false, 0, CONTENT_TYPE_HANDSHAKE, &finished));
+            assert!(send(&mut decryptor, down, &mut server_seq, &server_flight).is_empty());
+
+            let request_record = seal12(true, 1, CONTENT_TYPE_APPLICATION_DATA, request);
+            let requests = send(&mut decryptor, up, &mut client_seq, &request_record);
+            let response_record = seal12(false, 1, CONTENT_TYPE_APPLICATION_DATA, response);
+            let responses = send(&mut decryptor, down, &mut server_seq, &response_record);
+
+            assert_eq!(requests.len(), 1, "套件 0x{:04x}", suite_id);
+            assert_eq!(requests[0].key, up);
+            assert_eq!(requests[0].data, request);
+            assert_eq!(responses.len(), 1, "套件 0x{:04x}", suite_id);
+            assert_eq!(responses[0].key, down);
+            assert_eq!(responses[0].data, response);
+            assert_eq!(decryptor.decrypted_connections(), 1);
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_tls13_request_with_keylog() {
+        let client_random = [0x11u8; 32];
+        let handshake_secret = [0x33u8; 32];
+        let traffic_secret = [0x44u8; 32];
+        let path = temp_keylog(
+            "tls13",
+            &format!(
+                "# SSL/TLS secrets log file\n{}{}",
+                keylog_line("CLIENT_HANDSHAKE_TRAFFIC_SECRET", &client_random, &handshake_secret),
+                keylog_line("CLIENT_TRAFFIC_SECRET_0", &client_random, &traffic_secret)
+            ),
+        );
+
+        let request = b"GET /api/user HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let (up, down) = flow_keys();
+
+        let mut decryptor = TlsDecryptor::new(KeyLog::new(&path));
+        let mut client_seq = 1000u32;
+        assert!(send(&mut decryptor, up, &mut client_seq, &client_hello(&client_random, 0x1301)).is_empty());
+        assert!(send(&mut decryptor, down, &mut 5000, &server_hello(&[0x22; 32], 0x1301, true)).is_empty());
+        // 客户端 Finished 使用握手密钥，之后的请求使用应用数据密钥
+        let finished = seal(0x1301, &handshake_secret, 0, CONTENT_TYPE_HANDSHAKE, &[20, 0, 0, 32]);
+        assert!(send(&mut decryptor, up, &mut client_seq, &finished).is_empty());
+        let messages = send(
+            &mut decryptor,
+            up,
+            &mut client_seq,
+            &seal(0x1301, &traffic_secret, 0, CONTENT_TYPE_APPLICATION_DATA, request),
+        );
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].key, up);
+        assert_eq!(messages[0].data, request);
+        assert_eq!(decryptor.decrypted_connections(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_tls13_chacha20_with_only_application_keys() {
+        let client_random = [0x12u8; 32];
+        let client_traffic = [0x21u8; 32];
+        let server_traffic = [0x31u8; 32];
+        // 密钥日志中只有应用数据密钥，握手阶段的记录无法解密，应当跳过
+        let path = temp_keylog(
+            "tls13-chacha",
+            &format!(
+                "{}{}",
+                keylog_line("CLIENT_TRAFFIC_SECRET_0", &client_random, &client_traffic),
+                keylog_line("SERVER_TRAFFIC_SECRET_0", &client_random, &server_traffic)
+            ),
+        );
+        let request = b"POST /api/login HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}";
+        let response = b"HTTP/1.1 204 No Content\r\n\r\n";
+        let (up, down) = flow_keys();
+
+        let mut decryptor = TlsDecryptor::new(KeyLog::new(&path));
+        let (mut client_seq, mut server_seq) = (1000u32, 5000u32);
+        assert!(send(&mut decryptor, up, &mut client_seq, &client_hello(&client_random, 0x1303)).is_empty());
+        let mut server_flight = server_hello(&[0x22; 32], 0x1303, true);
+        server_flight.extend(seal(0x1303, &[0x98; 32], 0, CONTENT_TYPE_HANDSHAKE, &[8, 0, 0, 2, 0, 0]));
+        assert!(send(&mut decryptor, down, &mut server_seq, &server_flight).is_empty());
+        // 兼容模式下的 ChangeCipherSpec 在 TLS 1.3 中直接忽略
+        let mut client_flight = record(CONTENT_TYPE_CHANGE_CIPHER_SPEC, &[1]);
+        client_flight.extend(seal(0x1303, &[0x99; 32], 0, CONTENT_TYPE_HANDSHAKE, &[20, 0, 0, 32]));
+        assert!(send(&mut decryptor, up, &mut client_seq, &client_flight).is_empty());
+
+        let requests = send(
+            &mut decryptor,
+            up,
+            &mut client_seq,
+            &seal(0x1303, &client_traffic, 0, CONTENT_TYPE_APPLICATION_DATA, request),
+        );
+        let responses = send(
+            &mut decryptor,
+            down,
+            &mut server_seq,
+            &seal(0x1303, &server_traffic, 0, CONTENT_TYPE_APPLICATION_DATA, response),
+        );
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].data, request);
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].key, down);
+        assert_eq!(responses[0].data, response);
+        assert_eq!(decryptor.decrypted_connections(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_keylog_partial_lines_and_truncation() {
+        fn master_secret(keylog: &KeyLog, client_random: &[u8; 32]) -> Option<Vec<u8>> {
+            keylog
+                .secrets
+                .get(client_random)
+                .and_then(|s| s.get(SecretLabel::MasterSecret))
+                .cloned()
+        }
+
+        let (first, second, third) = ([0x01u8; 32], [0x02u8; 32], [0x03u8; 32]);
+        let second_line = keylog_line("CLIENT_RANDOM", &second, &[0xbb; 48]);
+        let (head, tail) = second_line.split_at(40);
+        // 注释、未知标签和长度错误的 random 都被忽略
+        let path = temp_keylog(
+            "partial",
+            &format!(
+                "# comment\nUNKNOWN_LABEL {r} 00\nCLIENT_RANDOM 0102 00\n{}{}",
+                keylog_line("CLIENT_RANDOM", &first, &[0xaa; 48]),
+                head,
+                r = hex(&first)
+            ),
+        );
+        let mut keylog = KeyLog::new(&path);
+
+        keylog.read_new_lines().unwrap();
+        assert_eq!(master_secret(&keylog, &first), Some(vec![0xaa; 48]));
+        // 还没写完的行留到下次读取
+        assert_eq!(master_secret(&keylog, &second), None);
+        assert_eq!(keylog.secrets.len(), 1);
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(tail.as_bytes())
+            .unwrap();
+        keylog.read_new_lines().unwrap();
+        assert_eq!(master_secret(&keylog, &second), Some(vec![0xbb; 48]));
+
+        // 文件被截断后重新写入，从头读取
+        std::fs::write(&path, keylog_line("CLIENT_RANDOM", &third, &[0xcc; 48])).unwrap();
+        keylog.read_new_lines().unwrap();
+        assert_eq!(master_secret(&keylog, &third), Some(vec![0xcc; 48]));
+        assert_eq!(keylog.offset, std::fs::metadata(&path).unwrap().len());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,222 @@
+use super::reassembly::FlowKey;
+use super::HttpPacket;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// 每个连接最多等待配对的请求数量（HTTP/1.1 管线化）
+const MAX_PENDING_PER_CONNECTION: usize = 64;
+/// 同时跟踪的最大连接数量
+const MAX_CONNECTIONS: usize = 4096;
+/// 请求等待响应的超时时间（微秒）
+const PENDING_TIMEOUT_US: u64 = 300 * 1_000_000;
+
+/// HTTP 事务：一次请求及其对应的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpTransaction {
+    /// 事务ID，与请求数据包ID相同
+    pub id: u64,
+    pub request: HttpPacket,
+    pub response: Option<HttpPacket>,
+    /// 请求发出到响应到达的往返时间（微秒）
+    pub round_trip_us: Option<u64>,
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    packet: HttpPacket,
+    timestamp_us: u64,
+}
+
+/// 请求/响应配对跟踪器
+///
+/// 以客户端到服务端的四元组为键，按请求顺序排队，
+/// 响应按到达顺序依次与队首请求配对，从而支持 keep-alive 管线化。
+#[derive(Debug, Default)]
+pub struct TransactionTracker {
+    pending: HashMap<FlowKey, VecDeque<PendingRequest>>,
+}
+
+impl TransactionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一个请求，等待其响应
+    pub fn on_request(&mut self, key: FlowKey, packet: &HttpPacket, timestamp_us: u64) -> Vec<HttpTransaction> {
+        let expired = self.expire(timestamp_us);
+
+        if !self.pending.contains_key(&key) && self.pending.len() >= MAX_CONNECTIONS {
+            if let Some(oldest) = self
+                .pending
+                .iter()
+                .filter_map(|(k, q)| q.back().map(|p| (*k, p.timestamp_us)))
+                .min_by_key(|(_, ts)| *ts)
+                .map(|(k, _)| k)
+            {
+                self.pending.remove(&oldest);
+            }
+        }
+
+        let queue = self.pending.entry(key).or_default();
+        if queue.len() >= MAX_PENDING_PER_CONNECTION {
+            debug!("连接 {} -> {} 等待配对的请求过多，丢弃最早的请求", key.src, key.dst);
+            queue.pop_front();
+        }
+        queue.push_back(PendingRequest {
+            packet: packet.clone(),
+            timestamp_us,
+        });
+
+        expired
+    }
+
+    /// 为响应寻找对应的请求，补全响应中的 host/path 并返回完整事务
+    ///
+    /// `key` 为响应方向（服务端 -> 客户端）的流标识。
+    /// 1xx 临时响应同样补全配对信息，但不结束事务，每个请求只产生一个事务。
+    pub fn on_response(&mut self, key: FlowKey, response: &mut HttpPacket, timestamp_us: u64) -> Option<HttpTransaction> {
+        let request_key = key.reversed();
+        let queue = self.pending.get_mut(&request_key)?;
+
+        let interim = matches!(response.status_code, Some(code) if (100..200).contains(&code) && code != 101);
+        if interim {
+            let pending = queue.front()?;
+            annotate_response(response, pending, timestamp_us);
+            return None;
+        }
+
+        let pending = queue.pop_front()?;
+        if queue.is_empty() {
+            self.pending.remove(&request_key);
+        }
+
+        let round_trip_us = annotate_response(response, &pending, timestamp_us);
+        Some(HttpTransaction {
+            id: pending.packet.id,
+            request: pending.packet,
+            response: Some(response.clone()),
+            round_trip_us: Some(round_trip_us),
+        })
+    }
+
+    /// 清理超时未响应的请求，作为没有响应的事务返回
+    fn expire(&mut self, now_us: u64) -> Vec<HttpTransaction> {
+        let mut expired = Vec::new();
+        for queue in self.pending.values_mut() {
+            while queue
+                .front()
+                .is_some_and(|p| now_us.saturating_sub(p.timestamp_us) >= PENDING_TIMEOUT_US)
+            {
+                if let Some(pending) = queue.pop_front() {
+                    expired.push(HttpTransaction {
+                        id: pending.packet.id,
+                        request: pending.packet,
+                        response: None,
+                        round_trip_us: None,
+                    });
+                }
+            }
+        }
+        self.pending.retain(|_, q| !q.is_empty());
+        expired
+    }
+}
+
+// 用请求补全响应的 host/path 和配对信息，返回往返时间
+fn annotate_response(response: &mut HttpPacket, pending: &PendingRequest, timestamp_us: u64) -> u64 {
+    let round_trip_us = timestamp_us.saturating_sub(pending.timestamp_us);
+    response.host = pending.packet.host.clone();
+    response.path = pending.packet.path.clone();
+    response.paired_id = Some(pending.packet.id);
+    response.round_trip_us = Some(round_trip_us);
+    round_trip_us
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> FlowKey {
+        FlowKey::new("10.0.0.2:50000".parse().unwrap(), "10.0.0.1:80".parse().unwrap())
+    }
+
+    fn request(id: u64, path: &str) -> HttpPacket {
+        HttpPacket {
+            id,
+            packet_type: "request".to_string(),
+            method: Some("GET".to_string()),
+            path: Some(path.to_string()),
+            host: "bi.example.com".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn response(status_code: u16) -> HttpPacket {
+        HttpPacket {
+            packet_type: "response".to_string(),
+            status_code: Some(status_code),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pipelined_requests_pair_in_order() {
+        let mut tracker = TransactionTracker::new();
+        tracker.on_request(client(), &request(1, "/a"), 1_000);
+        tracker.on_request(client(), &request(2, "/b"), 2_000);
+
+        let mut first = response(200);
+        let transaction = tracker.on_response(client().reversed(), &mut first, 5_000).unwrap();
+        assert_eq!(transaction.id, 1);
+        assert_eq!(transaction.round_trip_us, Some(4_000));
+        assert_eq!(first.paired_id, Some(1));
+        assert_eq!(first.round_trip_us, Some(4_000));
+        assert_eq!(first.host, "bi.example.com");
+        assert_eq!(first.path.as_deref(), Some("/a"));
+
+        let mut second = response(404);
+        let transaction = tracker.on_response(client().reversed(), &mut second, 6_000).unwrap();
+        assert_eq!(transaction.id, 2);
+        assert_eq!(transaction.response.unwrap().status_code, Some(404));
+        assert_eq!(second.path.as_deref(), Some("/b"));
+        assert!(tracker.pending.is_empty());
+    }
+
+    #[test]
+    fn test_interim_responses_keep_request_pending() {
+        let mut tracker = TransactionTracker::new();
+        tracker.on_request(client(), &request(1, "/upload"), 0);
+        tracker.on_request(client(), &request(2, "/ws"), 10);
+
+        // 100 Continue 只补全配对信息，不产生事务，最终响应仍与同一请求配对
+        let mut interim = response(100);
+        assert!(tracker.on_response(client().reversed(), &mut interim, 20).is_none());
+        assert_eq!(interim.paired_id, Some(1));
+        assert_eq!(interim.path.as_deref(), Some("/upload"));
+        let mut final_response = response(201);
+        let transaction = tracker.on_response(client().reversed(), &mut final_response, 30).unwrap();
+        assert_eq!(transaction.id, 1);
+        assert_eq!(transaction.response.unwrap().status_code, Some(201));
+
+        // 101 Switching Protocols 是最终响应
+        let mut switching = response(101);
+        assert_eq!(tracker.on_response(client().reversed(), &mut switching, 40).unwrap().id, 2);
+        assert_eq!(switching.paired_id, Some(2));
+        assert!(tracker.pending.is_empty());
+    }
+
+    #[test]
+    fn test_unmatched_response() {
+        let mut tracker = TransactionTracker::new();
+        let mut orphan = response(200);
+        assert!(tracker.on_response(client().reversed(), &mut orphan, 0).is_none());
+        assert_eq!(orphan.paired_id, None);
+        assert_eq!(orphan.round_trip_us, None);
+
+        // 方向错误的响应也不会配对
+        tracker.on_request(client(), &request(1, "/"), 0);
+        let mut wrong_direction = response(200);
+        assert!(tracker.on_response(client(), &mut wrong_direction, 10).is_none());
+    }
+}
@@ -8,6 +8,7 @@ use log::{Record, Metadata};
 use std::time::Duration;
 use tokio::time;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crate::service::redact;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
@@ -163,7 +164,8 @@ impl log::Log for HybridLogger {
             let entry = LogEntry {
                 timestamp: chrono::Utc::now().timestamp_millis() as u64,
                 level: record.level().to_string().to_lowercase(),
-                message: record.args().to_string(),
+                // 写入缓冲区之前隐藏 Cookie、Token 等敏感内容
+                message: redact::redact_log(&record.args().to_string()).into_owned(),
                 module: record.module_path().map(|s| s.to_string()),
             };
             
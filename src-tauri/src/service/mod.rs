@@ -1,4 +1,6 @@
 pub mod capture;
 pub mod auth;
 pub mod logread;
-pub mod file_match;
\ No newline at end of file
+pub mod file_match;
+pub mod redact;
+pub mod subscription;
\ No newline at end of file
@@ -0,0 +1,311 @@
+use crate::service::capture::HttpPacket;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+/// 替换敏感内容的掩码
+pub const MASK: &str = "***";
+
+static POLICY: Lazy<RwLock<Arc<CompiledPolicy>>> = Lazy::new(|| {
+    let policy = CompiledPolicy::compile(RedactionPolicy::default()).expect("默认脱敏策略无效");
+    RwLock::new(Arc::new(policy))
+});
+
+/// 已解锁“显示敏感信息”的捕获会话
+static UNLOCKED_SESSIONS: Lazy<RwLock<HashSet<String>>> = Lazy::new(|| RwLock::new(HashSet::new()));
+
+/// 脱敏策略
+///
+/// 数据包发往前端（实时推送、历史查询、HAR 导入）以及日志写入缓冲区之前按此策略脱敏，
+/// 后端内部（数据包存储、认证系统）始终保留原始数据。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionPolicy {
+    pub enabled: bool,
+    /// 整个值都需要隐藏的头部（不区分大小写）
+    pub header_names: Vec<String>,
+    /// 需要隐藏值的 Cookie 名称，同时作用于 Cookie/Set-Cookie 头和日志
+    pub cookie_names: Vec<String>,
+    /// 消息体、路径和日志中的敏感内容正则，有捕获组时只隐藏第一个捕获组
+    pub body_patterns: Vec<String>,
+    /// 是否对日志脱敏
+    pub redact_logs: bool,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            header_names: ["authorization", "proxy-authorization", "cookie", "set-cookie", "x-csrf-token", "x-xsrf-token"]
+                .map(String::from)
+                .to_vec(),
+            cookie_names: ["x_login_pk", "pdp_cqdrs_session", "wdcid", "JSESSIONID", "SESSION"]
+                .map(String::from)
+                .to_vec(),
+            body_patterns: vec![
+                r#"(?i)"(?:password|passwd|pwd|token|access_token|refresh_token|secret)"\s*:\s*"([^"]*)""#.to_string(),
+                r"(?i)\b(?:password|passwd|pwd|token|access_token|refresh_token|secret)=([^&\s]*)".to_string(),
+            ],
+            redact_logs: true,
+        }
+    }
+}
+
+/// 编译后的策略
+#[derive(Debug)]
+struct CompiledPolicy {
+    policy: RedactionPolicy,
+    headers: HashSet<String>,
+    cookies: HashSet<String>,
+    body_patterns: Vec<Regex>,
+    /// 日志专用：头部、Cookie 以及消息体规则
+    log_patterns: Vec<Regex>,
+}
+
+impl CompiledPolicy {
+    fn compile(policy: RedactionPolicy) -> Result<Self> {
+        let body_patterns = policy
+            .body_patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| anyhow!("脱敏正则表达式无效 {}: {}", p, e)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut log_patterns = Vec::new();
+        for header in &policy.header_names {
+            let name = regex::escape(header);
+            // "Authorization: xxx" 形式和 ("Authorization", "xxx") 调试输出形式
+            log_patterns.push(Regex::new(&format!(r"(?i)\b{}\s*:\s*([^\r\n]+)", name))?);
+            log_patterns.push(Regex::new(&format!(r#"(?i)"{}",\s*"([^"]*)""#, name))?);
+        }
+        for cookie in &policy.cookie_names {
+            log_patterns.push(Regex::new(&format!(r#"\b{}=([^;\s&"]+)"#, regex::escape(cookie)))?);
+        }
+        log_patterns.extend(body_patterns.iter().cloned());
+
+        Ok(Self {
+            headers: policy.header_names.iter().map(|h| h.to_ascii_lowercase()).collect(),
+            cookies: policy.cookie_names.iter().cloned().collect(),
+            body_patterns,
+            log_patterns,
+            policy,
+        })
+    }
+
+    fn mask_text<'a>(&self, patterns: &[Regex], text: &'a str) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(text);
+        for re in patterns {
+            if let Cow::Owned(masked) = mask_matches(re, &result) {
+                result = Cow::Owned(masked);
+            }
+        }
+        result
+    }
+
+    /// 隐藏 Cookie 头中指定名称的值
+    fn mask_cookie_header(&self, value: &str) -> String {
+        value
+            .split(';')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if self.cookies.contains(name.trim()) => format!("{}={}", name, MASK),
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+}
+
+/// 把正则匹配到的内容（有捕获组时为第一个捕获组）替换为掩码
+fn mask_matches<'a>(re: &Regex, text: &'a str) -> Cow<'a, str> {
+    let mut output = String::new();
+    let mut last = 0;
+    let mut changed = false;
+    for captures in re.captures_iter(text) {
+        let Some(secret) = captures.get(1).or_else(|| captures.get(0)) else {
+            continue;
+        };
+        if secret.start() < last || secret.as_str() == MASK {
+            continue;
+        }
+        output.push_str(&text[last..secret.start()]);
+        output.push_str(MASK);
+        last = secret.end();
+        changed = true;
+    }
+    if !changed {
+        return Cow::Borrowed(text);
+    }
+    output.push_str(&text[last..]);
+    Cow::Owned(output)
+}
+
+fn current() -> Arc<CompiledPolicy> {
+    match POLICY.read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+pub fn get_policy() -> RedactionPolicy {
+    current().policy.clone()
+}
+
+/// 更新脱敏策略，正则表达式无效时返回错误且不修改当前策略
+pub fn set_policy(policy: RedactionPolicy) -> Result<()> {
+    let compiled = Arc::new(CompiledPolicy::compile(policy)?);
+    match POLICY.write() {
+        Ok(mut guard) => *guard = compiled,
+        Err(poisoned) => *poisoned.into_inner() = compiled,
+    }
+    // 写锁释放后再记录日志，日志脱敏需要读取策略
+    log::info!("脱敏策略已更新");
+    Ok(())
+}
+
+/// 为捕获会话开启或关闭“显示敏感信息”，只在本次运行期间有效
+pub fn set_show_secrets(session_id: &str, show: bool) {
+    if let Ok(mut sessions) = UNLOCKED_SESSIONS.write() {
+        if show {
+            sessions.insert(session_id.to_string());
+        } else {
+            sessions.remove(session_id);
+        }
+    }
+    if show {
+        log::warn!("捕获会话 [{}] 已解锁显示敏感信息", session_id);
+    } else {
+        log::info!("捕获会话 [{}] 已恢复脱敏", session_id);
+    }
+}
+
+pub fn is_unlocked(session_id: &str) -> bool {
+    UNLOCKED_SESSIONS
+        .read()
+        .map(|sessions| sessions.contains(session_id))
+        .unwrap_or(false)
+}
+
+/// 对发往前端的数据包脱敏，数据包所属会话已解锁时保持原样
+pub fn redact_packet(packet: &mut HttpPacket) {
+    let policy = current();
+    if !policy.policy.enabled || is_unlocked(&packet.session_id) {
+        return;
+    }
+
+    let mut changed = false;
+
+    for (name, value) in packet.headers.iter_mut() {
+        let lower = name.to_ascii_lowercase();
+        let masked = if policy.headers.contains(&lower) {
+            MASK.to_string()
+        } else if lower == "cookie" || lower == "set-cookie" {
+            policy.mask_cookie_header(value)
+        } else {
+            continue;
+        };
+        if *value != masked {
+            *value = masked;
+            changed = true;
+        }
+    }
+
+    let mask_all_cookies = policy.headers.contains("cookie");
+    for cookie in packet.cookies.iter_mut() {
+        if (mask_all_cookies || policy.cookies.contains(&cookie.name)) && cookie.value != MASK {
+            cookie.value = MASK.to_string();
+            changed = true;
+        }
+    }
+    let mask_all_set_cookies = policy.headers.contains("set-cookie");
+    for cookie in packet.set_cookies.iter_mut() {
+        if (mask_all_set_cookies || policy.cookies.contains(&cookie.name)) && cookie.value != MASK {
+            cookie.value = MASK.to_string();
+            changed = true;
+        }
+    }
+
+    if let Some(path) = &packet.path {
+        if let Cow::Owned(masked) = policy.mask_text(&policy.body_patterns, path) {
+            packet.path = Some(masked);
+            changed = true;
+        }
+    }
+
+    // 消息体被改写后，原始数据改为脱敏后的明文，避免从 raw_body 还原
+    if let Cow::Owned(masked) = policy.mask_text(&policy.body_patterns, &packet.body) {
+        packet.raw_body = BASE64.encode(masked.as_bytes());
+        packet.transfer_encoding = None;
+        packet.content_encoding = None;
+        packet.body = masked;
+        changed = true;
+    }
+
+    packet.redacted |= changed;
+}
+
+/// 对日志消息脱敏
+pub fn redact_log(message: &str) -> Cow<'_, str> {
+    // 日志记录器可能在策略写锁持有期间被调用，拿不到锁时直接跳过
+    let policy = match POLICY.try_read() {
+        Ok(guard) => guard.clone(),
+        Err(_) => return Cow::Borrowed(message),
+    };
+    if !policy.policy.enabled || !policy.policy.redact_logs {
+        return Cow::Borrowed(message);
+    }
+    match policy.mask_text(&policy.log_patterns, message) {
+        Cow::Borrowed(_) => Cow::Borrowed(message),
+        Cow::Owned(masked) => Cow::Owned(masked),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::capture::cookie::parse_cookie_header;
+
+    #[test]
+    fn test_redact_packet_and_log() {
+        let policy = CompiledPolicy::compile(RedactionPolicy {
+            header_names: vec!["authorization".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let masked = policy.mask_cookie_header("x_login_pk=abc; qbi_locale=zh-CN");
+        assert_eq!(masked, "x_login_pk=***; qbi_locale=zh-CN");
+
+        let line = r#"headers: [("Authorization", "Bearer abc"), ("Cookie", "pdp_cqdrs_session=s1; a=b")] body: {"password":"p@ss"}"#;
+        let masked = policy.mask_text(&policy.log_patterns, line);
+        assert!(!masked.contains("Bearer abc"));
+        assert!(!masked.contains("s1"));
+        assert!(!masked.contains("p@ss"));
+        assert!(masked.contains("a=b"));
+
+        let mut packet = HttpPacket {
+            session_id: "redact-test".to_string(),
+            headers: vec![("Cookie".to_string(), "x_login_pk=abc; qbi_locale=zh-CN".to_string())],
+            cookies: parse_cookie_header("x_login_pk=abc; qbi_locale=zh-CN"),
+            body: "user=a&password=secret".to_string(),
+            ..Default::default()
+        };
+        redact_packet(&mut packet);
+        assert_eq!(packet.headers[0].1, MASK);
+        assert_eq!(packet.cookies[0].value, MASK);
+        assert_eq!(packet.body, "user=a&password=***");
+        assert!(packet.redacted);
+
+        let mut packet = HttpPacket {
+            session_id: "redact-test-unlocked".to_string(),
+            body: "password=secret".to_string(),
+            ..Default::default()
+        };
+        set_show_secrets("redact-test-unlocked", true);
+        redact_packet(&mut packet);
+        assert_eq!(packet.body, "password=secret");
+    }
+}
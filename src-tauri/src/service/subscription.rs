@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use tauri::ipc::Channel;
+
+// 进程内唯一的订阅者ID，所有订阅表共用
+static NEXT_SUBSCRIBER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 订阅者的过滤条件，只有匹配的事件才会发送给该订阅者
+pub trait EventFilter<T>: Send + Sync {
+    fn matches(&self, event: &T) -> bool;
+}
+
+/// 不做过滤，接收全部事件
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllEvents;
+
+impl<T> EventFilter<T> for AllEvents {
+    fn matches(&self, _event: &T) -> bool {
+        true
+    }
+}
+
+struct Subscriber<T, F> {
+    channel: Channel<T>,
+    filter: F,
+    /// 订阅所在的窗口标签，窗口销毁时一并取消订阅
+    owner: String,
+}
+
+/// 前端事件的订阅表
+///
+/// 每个窗口可以各自订阅同一类事件，按订阅者ID取消订阅。
+/// 发送失败的通道（窗口已关闭或页面已刷新）会被自动移除。
+pub struct SubscriptionRegistry<T, F> {
+    /// 事件名称，用于日志
+    name: &'static str,
+    subscribers: RwLock<HashMap<u64, Subscriber<T, F>>>,
+}
+
+impl<T, F> SubscriptionRegistry<T, F>
+where
+    T: Serialize + Clone,
+    F: EventFilter<T>,
+{
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            subscribers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 添加订阅，返回订阅者ID
+    pub fn subscribe(&self, channel: Channel<T>, filter: F, owner: &str) -> Result<u64> {
+        let id = NEXT_SUBSCRIBER_ID.fetch_add(1, Ordering::Relaxed);
+        let mut subscribers = self.subscribers.write().map_err(|_| anyhow!("{}订阅表锁已损坏", self.name))?;
+        subscribers.insert(
+            id,
+            Subscriber {
+                channel,
+                filter,
+                owner: owner.to_string(),
+            },
+        );
+        info!("窗口 [{}] 订阅{}，订阅者ID {}，当前 {} 个订阅者", owner, self.name, id, subscribers.len());
+        Ok(id)
+    }
+
+    /// 取消订阅，返回订阅者是否存在
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        let Ok(mut subscribers) = self.subscribers.write() else {
+            return false;
+        };
+        let removed = subscribers.remove(&id).is_some();
+        if removed {
+            info!("取消{}订阅 {}，剩余 {} 个订阅者", self.name, id, subscribers.len());
+        }
+        removed
+    }
+
+    /// 移除窗口的全部订阅，返回移除的数量
+    pub fn remove_owner(&self, owner: &str) -> usize {
+        let Ok(mut subscribers) = self.subscribers.write() else {
+            return 0;
+        };
+        let before = subscribers.len();
+        subscribers.retain(|_, s| s.owner != owner);
+        let removed = before - subscribers.len();
+        if removed > 0 {
+            info!("窗口 [{}] 已关闭，移除 {} 个{}订阅", owner, removed, self.name);
+        }
+        removed
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.read().map_or(0, |s| s.len())
+    }
+
+    /// 发送事件给所有匹配的订阅者，发送失败的订阅者被移除
+    pub fn send(&self, event: &T) {
+        let mut dead = Vec::new();
+        {
+            let Ok(subscribers) = self.subscribers.read() else {
+                debug!("{}订阅表锁已损坏，跳过此次发送", self.name);
+                return;
+            };
+            for (id, subscriber) in subscribers.iter() {
+                if !subscriber.filter.matches(event) {
+                    continue;
+                }
+                if let Err(e) = subscriber.channel.send(event.clone()) {
+                    debug!("发送{}到订阅者 {} 失败: {}", self.name, id, e);
+                    dead.push(*id);
+                }
+            }
+        }
+
+        if dead.is_empty() {
+            return;
+        }
+        if let Ok(mut subscribers) = self.subscribers.write() {
+            for id in dead {
+                if subscribers.remove(&id).is_some() {
+                    info!("{}订阅者 {} 的通道已失效，已移除", self.name, id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    struct MinValue(u32);
+
+    impl EventFilter<u32> for MinValue {
+        fn matches(&self, event: &u32) -> bool {
+            *event >= self.0
+        }
+    }
+
+    fn counting_channel(count: Arc<AtomicUsize>) -> Channel<u32> {
+        Channel::new(move |_| {
+            count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_filters_unsubscribe_and_dead_channels() {
+        let registry = SubscriptionRegistry::new("测试事件");
+        let all = Arc::new(AtomicUsize::new(0));
+        let large = Arc::new(AtomicUsize::new(0));
+        let all_id = registry.subscribe(counting_channel(all.clone()), MinValue(0), "main").unwrap();
+        registry.subscribe(counting_channel(large.clone()), MinValue(10), "packet-1").unwrap();
+        registry
+            .subscribe(Channel::new(|_| Err(anyhow!("窗口已关闭").into())), MinValue(0), "packet-2")
+            .unwrap();
+
+        registry.send(&1);
+        registry.send(&20);
+        assert_eq!(all.load(Ordering::SeqCst), 2);
+        assert_eq!(large.load(Ordering::SeqCst), 1);
+        // 失效的通道在第一次发送失败后被移除
+        assert_eq!(registry.subscriber_count(), 2);
+
+        assert!(registry.unsubscribe(all_id));
+        assert!(!registry.unsubscribe(all_id));
+        assert_eq!(registry.remove_owner("packet-1"), 1);
+        assert_eq!(registry.subscriber_count(), 0);
+    }
+}
@@ -45,6 +45,11 @@ export type TokenEvent =
       failed_at: number;
     };
 
+// Token事件订阅过滤条件，system_ids 为空时接收全部系统的事件
+export interface TokenEventFilter {
+  system_ids?: string[];
+}
+
 export const useAuthStore = defineStore('auth', () => {
   // 状态
   const tokenStatuses = ref<TokenStatus[]>([]);
@@ -113,9 +118,9 @@ export const useAuthStore = defineStore('auth', () => {
         updateTokenStatusFromEvent(event);
       };
 
-      // 发送通道到后端
-      await invoke('set_token_event_channel', { channel: tokenEventChannel });
-      console.log('✅ Token事件通道设置成功');
+      // 订阅Token事件（每个窗口各自订阅，不会替换其他窗口的订阅）
+      const subscriberId = await invoke('subscribe_token_events', { channel: tokenEventChannel }) as number;
+      console.log('✅ Token事件订阅成功:', subscriberId);
 
       // 获取初始状态
       await refreshTokenStatuses();
@@ -37,25 +37,145 @@ export interface NetworkDevice {
   name: string;
   description: string;
   is_loopback: boolean;
-  addresses: string[];
+  is_up: boolean;
+  is_running: boolean;
+  is_wireless: boolean;
+  connection_status: 'unknown' | 'connected' | 'disconnected' | 'not_applicable';
+  addresses: DeviceAddress[];
+  recommended: boolean; // 默认路由所在的设备
+}
+
+export interface DeviceAddress {
+  addr: string;
+  family: 'ipv4' | 'ipv6';
+  netmask?: string;
+  broadcast?: string;
+}
+
+// 定义网络设备变化事件
+export interface DeviceChangeEvent {
+  added: NetworkDevice[];
+  removed: NetworkDevice[];
+  changed: NetworkDevice[];
+  devices: NetworkDevice[]; // 变化后的完整设备列表
 }
 
 // 定义捕获状态类型
 export interface CaptureStatus {
+  session_id: string;
   running: boolean;
   message: string;
   device_name: string;
   start_time: number;
+  options?: CaptureOptions;
+  filter?: string;
+  stats?: CaptureStats;
+}
+
+// 定义捕获运行统计（与Rust结构体对应）
+export interface CaptureStats {
+  session_id: string;
+  pcap_received: number;
+  pcap_dropped: number;
+  pcap_if_dropped: number;
+  frames: number;
+  bytes: number;
+  parse_errors: number;
+  http_requests: number;
+  http_responses: number;
+  tls_connections: number;
+  tls_decrypted: number; // 使用密钥日志解密的 TLS 连接数
+  dns_messages: number;
+  ip_fragments: number;
+  ip_reassembled: number;
+  ip_reassembly_failures: number;
+  packets_per_second: number;
+  bytes_per_second: number;
+}
+
+// 定义捕获选项类型（与Rust结构体对应）
+export interface CaptureOptions {
+  bpf_filter?: string | null;
+  extra_ports: number[];
+  snaplen: number;
+  buffer_size?: number | null;
+  promisc: boolean;
+  immediate_mode: boolean;
+  timeout_ms: number;
+  stop: StopConditions;
+  keylog_file?: string | null; // SSLKEYLOGFILE 路径，用于解密 HTTPS
+}
+
+// 定义代理捕获选项（与Rust结构体对应）
+export interface ProxyOptions {
+  bind_address: string;
+  port: number;
+  intercept_tls: boolean; // 是否解密 HTTPS
+  verify_upstream: boolean; // 是否校验上游证书
+  stop: StopConditions;
+}
+
+// 代理根证书，需导入受信任根证书后才能解密 HTTPS
+export interface ProxyCaInfo {
+  cert_path: string;
+  cert_pem: string;
+}
+
+export interface ProxyInfo {
+  session_id: string;
+  listen_addr: string;
+  ca?: ProxyCaInfo;
+}
+
+// 定义自动停止条件（任意一个满足即停止）
+export interface StopConditions {
+  max_duration_secs?: number | null;
+  max_http_packets?: number | null;
+  max_bytes?: number | null;
+  token_acquired?: string | null; // 系统ID
+  matching_requests?: {
+    host?: string | null;
+    method?: string | null;
+    path_regex?: string | null;
+    count: number;
+  } | null;
+}
+
+// 定义捕获停止原因
+export type StopReason =
+  | { kind: 'manual' }
+  | { kind: 'max_duration'; secs: number }
+  | { kind: 'max_http_packets'; count: number }
+  | { kind: 'max_bytes'; bytes: number }
+  | { kind: 'token_acquired'; system_id: string }
+  | { kind: 'matching_requests'; count: number }
+  | { kind: 'error'; message: string };
+
+// 定义捕获会话结束摘要
+export interface CaptureSummary {
+  session_id: string;
+  device_name: string;
+  reason: StopReason;
+  message: string;
+  start_time: number;
+  end_time: number;
+  duration_secs: number;
+  stats: CaptureStats;
 }
 
 // 定义后端 HTTP 数据包类型（与Rust结构体对应）
 export interface HttpPacket {
   id: number;
-  timestamp: number;
+  timestamp: number; // 捕获时间（秒）
+  timestamp_us: number; // 捕获时间（微秒）
+  device_name: string; // 捕获该数据包的网络设备
+  session_id: string; // 所属捕获会话（离线分析和 HAR 导入时为文件名）
   src_ip: string;
   src_port: number;
   dst_ip: string;
   dst_port: number;
+  src_name?: string; // DNS 解析出的源地址主机名
+  dst_name?: string; // DNS 解析出的目标地址主机名
   packet_type: string; // "request" 或 "response"
 
   // 请求字段
@@ -72,19 +192,267 @@ export interface HttpPacket {
   content_type: string;
   content_length?: number;
   headers: [string, string][];
-  body: string;
+  body: string; // 解码后的消息体文本
+
+  // 消息体编码字段
+  raw_body: string; // 原始消息体（base64）
+  transfer_encoding?: string;
+  content_encoding?: string;
+  body_truncated: boolean;
+  body_decode_error?: string;
+
+  // Cookie 字段
+  cookies: { name: string; value: string }[];
+  set_cookies: SetCookie[];
+  redacted: boolean; // 是否隐藏了敏感内容
+
+  // 配对字段（仅响应）
+  paired_id?: number;
+  round_trip_us?: number;
+}
+
+// 结构化消息体视图（按 Content-Type 解析）
+export interface FormField {
+  name: string;
+  value: string;
+  json?: string; // 字段值为 JSON 时的格式化结果
+}
+
+export interface MultipartPart {
+  name?: string;
+  filename?: string;
+  content_type?: string;
+  headers: [string, string][];
+  size: number;
+  body: BodyView;
+}
+
+export type BodyView =
+  | { kind: 'empty' }
+  | { kind: 'json'; pretty: string }
+  | { kind: 'form'; fields: FormField[] }
+  | { kind: 'multipart'; boundary: string; parts: MultipartPart[] }
+  | { kind: 'text'; text: string }
+  | { kind: 'binary'; base64: string; size: number };
+
+// 数据包详情（get_packet_by_id 返回）
+export interface PacketDetail extends HttpPacket {
+  body_view: BodyView;
+}
+
+// 定义后端 HTTP 事务类型（请求与响应配对）
+export interface HttpTransaction {
+  id: number;
+  request: HttpPacket;
+  response?: HttpPacket;
+  round_trip_us?: number;
+}
+
+// 定义 Set-Cookie 记录
+export interface SetCookie {
+  name: string;
+  value: string;
+  domain?: string;
+  path?: string;
+  expires?: string;
+  expires_at?: number; // Unix 时间戳（秒）
+  max_age?: number;
+  http_only: boolean;
+  secure: boolean;
+  same_site?: string;
+}
+
+// 定义 TLS 服务器证书信息
+export interface TlsCertificateInfo {
+  subject: string;
+  issuer: string;
+  serial: string;
+  not_before: number; // Unix 时间戳（秒）
+  not_after: number;
+  dns_names: string[];
+  chain_length: number;
+}
+
+// 定义 TLS 连接事件（同一连接的 client_hello 与 server_hello 事件 id 相同）
+export interface TlsConnectionEvent {
+  id: number;
+  stage: 'client_hello' | 'server_hello';
+  timestamp_us: number;
+  device_name: string;
+  client_ip: string;
+  client_port: number;
+  server_ip: string;
+  server_port: number;
+  sni?: string;
+  client_alpn: string[];
+  client_versions: string[];
+  alpn?: string;
+  version?: string;
+  cipher_suite?: string;
+  certificate?: TlsCertificateInfo;
+}
+
+// DNS 查询日志（每个响应一条）
+export interface DnsAnswer {
+  name: string;
+  record_type: string;
+  ttl: number;
+  data: string;
+}
+
+export interface DnsEvent {
+  id: number;
+  timestamp_us: number;
+  device_name: string;
+  session_id: string;
+  client_ip: string;
+  client_port: number;
+  server_ip: string;
+  transport: 'udp' | 'tcp';
+  transaction_id: number;
+  query_name: string;
+  query_type: string;
+  response_code: string;
+  answers: DnsAnswer[];
+  latency_us?: number;
+}
+
+// 事件订阅过滤条件，未设置的条件不过滤
+export interface HttpPacketFilter {
+  session_id?: string;
+  host?: string; // Host 头或 DNS 主机名包含该字符串（不区分大小写）
+  packet_type?: 'request' | 'response';
+}
+
+export interface StatusFilter {
+  session_id?: string;
+}
+
+// TCP 连接跟踪
+export type TcpState = 'syn_sent' | 'syn_received' | 'established' | 'closing' | 'closed' | 'reset';
+
+export interface FlowDirectionStats {
+  bytes: number;
+  segments: number;
+  retransmissions: number;
+  zero_window: number;
+}
+
+export interface FlowTransaction {
+  id: number;
+  method?: string;
+  path?: string;
+  request_us: number;
+  status_code?: number;
+  round_trip_us?: number;
+}
+
+export interface FlowInfo {
+  id: number;
+  session_id: string;
+  device_name: string;
+  client_ip: string;
+  client_port: number;
+  server_ip: string;
+  server_port: number;
+  server_name?: string;
+  first_seen_us: number;
+  last_seen_us: number;
+  state: TcpState;
+  client_to_server: FlowDirectionStats;
+  server_to_client: FlowDirectionStats;
+  transaction_count: number;
+  transactions: FlowTransaction[];
+}
+
+export interface FlowUpdateEvent {
+  session_id: string;
+  flows: FlowInfo[];
+}
+
+export interface FlowQuery {
+  session_id?: string;
+  ip?: string;
+  port?: number;
+  state?: TcpState;
+  active_only?: boolean;
+  offset?: number;
+  limit?: number;
+}
+
+export interface FlowPage {
+  total: number;
+  offset: number;
+  limit: number;
+  flows: FlowInfo[];
+}
+
+// 敏感信息脱敏策略
+export interface RedactionPolicy {
+  enabled: boolean;
+  header_names: string[];
+  cookie_names: string[];
+  body_patterns: string[]; // 正则表达式，有捕获组时只隐藏第一个捕获组
+  redact_logs: boolean;
+}
+
+// 捕获前置条件诊断报告
+export interface DeviceAccess {
+  name: string;
+  can_open: boolean;
+  error?: string;
+}
+
+export interface CapturePrerequisiteReport {
+  platform: string;
+  ready: boolean;
+  driver_installed: boolean;
+  is_root: boolean;
+  effective_capabilities: string[];
+  missing_capabilities: string[];
+  binary_path?: string;
+  binary_capabilities?: string;
+  devices: DeviceAccess[];
+  fix_command?: string;
+  issues: string[];
 }
 
 export const useProxyStore = defineStore('proxy', () => {
   // 状态
   const captureStatus = ref<CaptureStatus>({
+    session_id: '',
     running: false,
     message: '未初始化',
     device_name: '',
     start_time: 0
   });
 
+  // 各捕获会话的状态（以会话ID为键）
+  const sessionStatuses = ref<Record<string, CaptureStatus>>({});
+
   const packets = ref<PacketData[]>([]);
+  // 最近一次捕获前置条件诊断结果
+  const prerequisiteReport = ref<CapturePrerequisiteReport | null>(null);
+  // TLS 连接（按连接 id 合并两次事件）
+  const tlsConnections = ref<Record<number, TlsConnectionEvent>>({});
+  // DNS 查询日志（最新的在前）
+  const dnsQueries = ref<DnsEvent[]>([]);
+  // TCP 连接（按连接 id，更新时整体替换）
+  const flows = ref<Record<number, FlowInfo>>({});
+  // 本窗口的后端订阅者ID，清理时取消订阅
+  const subscriptionIds: Record<'status' | 'http' | 'tls' | 'dns' | 'flow' | 'summary' | 'device', number | null> = {
+    status: null,
+    http: null,
+    tls: null,
+    dns: null,
+    flow: null,
+    summary: null,
+    device: null,
+  };
+  // 正在运行的代理捕获
+  const proxyInfo = ref<ProxyInfo | null>(null);
+  // 已结束捕获会话的摘要（按会话ID）
+  const captureSummaries = ref<Record<string, CaptureSummary>>({});
   const devices = ref<NetworkDevice[]>([]);
   const selectedDevice = ref<string>('');
   const error = ref<string>('');
@@ -104,7 +472,7 @@ export const useProxyStore = defineStore('proxy', () => {
   });
 
   // 计算属性
-  const isCapturing = computed(() => captureStatus.value.running);
+  const isCapturing = computed(() => Object.values(sessionStatuses.value).some(s => s.running));
 
   // 修改：使用独立计数器
   const packetCount = computed(() => totalPacketCount.value);
@@ -182,7 +550,7 @@ export const useProxyStore = defineStore('proxy', () => {
   const convertHttpPacketToPacketData = (httpPacket: HttpPacket): PacketData => {
     return {
       id: httpPacket.id.toString(),
-      timestamp: httpPacket.timestamp * 1000, // 转换为毫秒
+      timestamp: httpPacket.timestamp_us ? Math.floor(httpPacket.timestamp_us / 1000) : httpPacket.timestamp * 1000, // 转换为毫秒
       type: httpPacket.packet_type as 'request' | 'response',
       protocol: 'HTTP',
       srcIp: httpPacket.src_ip,
@@ -220,6 +588,7 @@ export const useProxyStore = defineStore('proxy', () => {
       const statusChannel = new Channel<CaptureStatus>();
       statusChannel.onmessage = (status: CaptureStatus) => {
         console.log('收到状态更新:', status);
+        sessionStatuses.value[status.session_id] = status;
         captureStatus.value = status;
       };
 
@@ -238,9 +607,54 @@ export const useProxyStore = defineStore('proxy', () => {
         }
       };
 
-      // 发送通道到后端
-      await invoke('set_status_channel', { channel: statusChannel });
-      await invoke('set_http_channel', { channel: httpChannel });
+      // 设置 TLS 连接事件通道
+      const tlsChannel = new Channel<TlsConnectionEvent>();
+      tlsChannel.onmessage = (event: TlsConnectionEvent) => {
+        tlsConnections.value[event.id] = { ...tlsConnections.value[event.id], ...event };
+      };
+
+      // 设置 DNS 查询日志通道
+      const dnsChannel = new Channel<DnsEvent>();
+      dnsChannel.onmessage = (event: DnsEvent) => {
+        dnsQueries.value.unshift(event);
+        if (dnsQueries.value.length > 1000) {
+          dnsQueries.value = dnsQueries.value.slice(0, 1000);
+        }
+      };
+
+      // 设置 TCP 连接更新通道
+      const flowChannel = new Channel<FlowUpdateEvent>();
+      flowChannel.onmessage = (event: FlowUpdateEvent) => {
+        for (const flow of event.flows) {
+          flows.value[flow.id] = flow;
+        }
+      };
+
+      // 设置捕获会话结束摘要通道
+      const summaryChannel = new Channel<CaptureSummary>();
+      summaryChannel.onmessage = (summary: CaptureSummary) => {
+        console.log(`捕获会话 [${summary.session_id}] 已结束:`, summary.message);
+        captureSummaries.value[summary.session_id] = summary;
+      };
+
+      // 设置网络设备变化通道，保持设备下拉列表与系统一致
+      const deviceChannel = new Channel<DeviceChangeEvent>();
+      deviceChannel.onmessage = (event: DeviceChangeEvent) => {
+        devices.value = event.devices;
+        // 当前选择的设备消失时改选推荐设备
+        if (selectedDevice.value && !event.devices.some(device => device.name === selectedDevice.value)) {
+          selectedDevice.value = pickDefaultDevice(event.devices)?.name ?? '';
+        }
+      };
+
+      // 发送通道到后端（按窗口订阅，不会替换其他窗口的订阅）
+      subscriptionIds.status = await invoke('subscribe_capture_status', { channel: statusChannel }) as number;
+      subscriptionIds.http = await subscribeHttpPackets(httpChannel);
+      subscriptionIds.tls = await invoke('subscribe_tls_events', { channel: tlsChannel }) as number;
+      subscriptionIds.dns = await invoke('subscribe_dns_events', { channel: dnsChannel }) as number;
+      subscriptionIds.flow = await invoke('subscribe_flow_updates', { channel: flowChannel }) as number;
+      subscriptionIds.summary = await invoke('subscribe_capture_summaries', { channel: summaryChannel }) as number;
+      subscriptionIds.device = await invoke('subscribe_device_changes', { channel: deviceChannel }) as number;
 
       // 获取初始状态
       await getCaptureStatus();
@@ -275,6 +689,27 @@ export const useProxyStore = defineStore('proxy', () => {
     }
   };
 
+  // 启动代理捕获（无需抓包权限，可解密 HTTPS）
+  const startProxyCapture = async (options?: Partial<ProxyOptions>) => {
+    try {
+      isLoading.value = true;
+      error.value = '';
+      proxyInfo.value = await invoke('start_proxy_capture', { options: options ?? null }) as ProxyInfo;
+      return proxyInfo.value;
+    } catch (err) {
+      error.value = `启动代理失败: ${err}`;
+      console.error('启动代理失败:', err);
+      throw err;
+    } finally {
+      isLoading.value = false;
+    }
+  };
+
+  // 获取代理根证书
+  const getProxyCa = async () => {
+    return await invoke('get_proxy_ca') as ProxyCaInfo;
+  };
+
   // 停止捕获
   const stopCapture = async () => {
     try {
@@ -294,15 +729,22 @@ export const useProxyStore = defineStore('proxy', () => {
   // 清空数据包
   const clearPackets = () => {
     packets.value = [];
+    tlsConnections.value = {};
+    dnsQueries.value = [];
+    flows.value = {};
     totalPacketCount.value = 0;  // 新增：重置总计数
   };
 
   // 获取捕获状态
   const getCaptureStatus = async () => {
     try {
-      const status = await invoke('get_capture_status') as CaptureStatus;
-      captureStatus.value = status;
-      return status;
+      const statuses = await invoke('get_capture_status') as CaptureStatus[];
+      sessionStatuses.value = Object.fromEntries(statuses.map(s => [s.session_id, s]));
+      const status = statuses.find(s => s.running) ?? statuses[0];
+      if (status) {
+        captureStatus.value = status;
+      }
+      return statuses;
     } catch (err) {
       console.error('获取状态失败:', err);
       throw err;
@@ -312,17 +754,47 @@ export const useProxyStore = defineStore('proxy', () => {
   // 检查权限
   const checkPermissions = async () => {
     try {
-      const hasPermission = await invoke('has_pcap') as boolean;
-      if (!hasPermission) {
-        error.value = '缺少网络捕获权限，请安装 ChmodBPF 权限包';
+      const report = await invoke('get_capture_prerequisites') as CapturePrerequisiteReport;
+      prerequisiteReport.value = report;
+      if (!report.ready) {
+        const reason = report.issues[0] ?? '缺少网络捕获权限';
+        error.value = report.fix_command ? `${reason}，可执行: ${report.fix_command}` : reason;
       }
-      return hasPermission;
+      return report.ready;
     } catch (err) {
       console.error('权限检查失败:', err);
       throw err;
     }
   };
 
+  // 为捕获会话显示或隐藏敏感信息，之后获取的数据包生效
+  const setShowSecrets = async (sessionId: string, show: boolean) => {
+    await invoke('set_show_secrets', { sessionId, show });
+  };
+
+  // 订阅 HTTP 数据包，返回订阅者ID；详情窗口可以只订阅某个主机
+  const subscribeHttpPackets = async (channel: Channel<HttpPacket>, filter?: HttpPacketFilter) => {
+    return await invoke('subscribe_http_packets', { channel, filter }) as number;
+  };
+
+  const unsubscribeHttpPackets = async (subscriberId: number) => {
+    return await invoke('unsubscribe_http_packets', { subscriberId }) as boolean;
+  };
+
+  // 按条件分页查询 TCP 连接（按最近活动时间倒序）
+  const listFlows = async (query: FlowQuery = {}) => {
+    return await invoke('list_flows', { query }) as FlowPage;
+  };
+
+  const getFlow = async (id: number) => {
+    return await invoke('get_flow', { id }) as FlowInfo;
+  };
+
+  // 推荐设备优先，其次是第一个非回环设备
+  const pickDefaultDevice = (deviceList: NetworkDevice[]) => {
+    return deviceList.find(device => device.recommended) ?? deviceList.find(device => !device.is_loopback);
+  };
+
   // 获取网络设备列表
   const getNetworkDevices = async () => {
     try {
@@ -332,10 +804,10 @@ export const useProxyStore = defineStore('proxy', () => {
       const deviceList = await invoke('get_network_devices') as NetworkDevice[];
       devices.value = deviceList;
 
-      // 自动选择第一个非回环设备
-      const nonLoopbackDevice = deviceList.find(device => !device.is_loopback);
-      if (nonLoopbackDevice && !selectedDevice.value) {
-        selectedDevice.value = nonLoopbackDevice.name;
+      // 自动选择推荐设备
+      const defaultDevice = pickDefaultDevice(deviceList);
+      if (defaultDevice && !selectedDevice.value) {
+        selectedDevice.value = defaultDevice.name;
       }
 
       return deviceList;
@@ -351,6 +823,28 @@ export const useProxyStore = defineStore('proxy', () => {
     // 清理资源
   const cleanup = async () => {
     try {
+      if (subscriptionIds.status !== null) {
+        await invoke('unsubscribe_capture_status', { subscriberId: subscriptionIds.status });
+        subscriptionIds.status = null;
+      }
+      if (subscriptionIds.http !== null) {
+        await unsubscribeHttpPackets(subscriptionIds.http);
+        subscriptionIds.http = null;
+      }
+      const unsubscribeCommands = {
+        tls: 'unsubscribe_tls_events',
+        dns: 'unsubscribe_dns_events',
+        flow: 'unsubscribe_flow_updates',
+        summary: 'unsubscribe_capture_summaries',
+        device: 'unsubscribe_device_changes',
+      } as const;
+      for (const [key, command] of Object.entries(unsubscribeCommands) as [keyof typeof unsubscribeCommands, string][]) {
+        const subscriberId = subscriptionIds[key];
+        if (subscriberId !== null) {
+          await invoke(command, { subscriberId });
+          subscriptionIds[key] = null;
+        }
+      }
       if (captureStatus.value.running) {
         localStorage.clear();
         await stopCapture();
@@ -413,7 +907,14 @@ export const useProxyStore = defineStore('proxy', () => {
   return {
     // 状态
     captureStatus,
+    sessionStatuses,
     packets,
+    prerequisiteReport,
+    tlsConnections,
+    dnsQueries,
+    flows,
+    captureSummaries,
+    proxyInfo,
     devices,
     selectedDevice,
     error,
@@ -433,9 +934,16 @@ export const useProxyStore = defineStore('proxy', () => {
     initialize,
     startCapture,
     stopCapture,
+    startProxyCapture,
+    getProxyCa,
     clearPackets,
     getCaptureStatus,
     checkPermissions,
+    setShowSecrets,
+    subscribeHttpPackets,
+    unsubscribeHttpPackets,
+    listFlows,
+    getFlow,
     getNetworkDevices,
     cleanup,
     savePacketForWindow,